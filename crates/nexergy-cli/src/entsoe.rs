@@ -0,0 +1,547 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::{Args, Subcommand, ValueEnum};
+use nexergy_core::asof::{with_knowledge_time_by, KnowledgeRule};
+use nexergy_core::io::{write_partitioned, LakeTable};
+use nexergy_ingest::entsoe::{
+    self, BackfillOptions, BiddingZone, DocumentType, EntsoeClient, EntsoeQuery, RetryPolicy,
+    DEFAULT_BASE_URL,
+};
+use polars::prelude::DataFrame;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct Connection {
+    #[arg(long, default_value = DEFAULT_BASE_URL)]
+    base_url: String,
+    #[arg(long)]
+    security_token: String,
+    /// Maximum requests per minute (ENTSO-E throttles at roughly 400)
+    #[arg(long, default_value_t = 300)]
+    requests_per_minute: u32,
+    /// Stop after this many requests; rerun to resume
+    #[arg(long)]
+    max_requests: Option<usize>,
+    /// Retries for rate-limited (429) and server (5xx) responses
+    #[arg(long, default_value_t = 5)]
+    max_retries: u32,
+}
+
+impl Connection {
+    fn client(&self) -> Result<EntsoeClient> {
+        let retry = RetryPolicy {
+            max_retries: self.max_retries,
+            ..RetryPolicy::default()
+        };
+        Ok(EntsoeClient::new(&self.base_url, &self.security_token)?
+            .with_retry(retry)
+            .with_requests_per_minute(self.requests_per_minute)
+            .with_max_requests(self.max_requests)
+            .with_on_retry(|e, wait| {
+                eprintln!("retrying in {:.1}s after: {e}", wait.as_secs_f64())
+            }))
+    }
+}
+
+#[derive(Args)]
+pub struct ZoneWindow {
+    /// Friendly bidding zone name (NL, DE-LU, BE, FR, GB, NO2, DK1) or EIC code
+    #[arg(long)]
+    zone: BiddingZone,
+    /// First day (inclusive, UTC), YYYY-MM-DD
+    #[arg(long)]
+    from: NaiveDate,
+    /// Last day (exclusive, UTC), YYYY-MM-DD
+    #[arg(long)]
+    to: NaiveDate,
+    #[arg(long, name = "out-dir")]
+    out_dir: PathBuf,
+    /// Start from --from even if partitions were already written
+    #[arg(long)]
+    no_resume: bool,
+    /// Resample every series to this resolution before writing (e.g. PT60M, PT15M)
+    #[arg(long)]
+    resolution: Option<String>,
+}
+
+impl ZoneWindow {
+    fn options(&self, table: LakeTable) -> BackfillOptions {
+        BackfillOptions {
+            resume: !self.no_resume,
+            table,
+            ..BackfillOptions::default()
+        }
+    }
+}
+
+// Outages are partitioned by outage start, which is often months after the query window, so the
+// written partitions say nothing about how far a previous run got: every run pulls the whole window
+// (rows are merged on their key) and there is no --no-resume.
+#[derive(Args)]
+pub struct OutageWindow {
+    /// Friendly bidding zone name (NL, DE-LU, BE, FR, GB, NO2, DK1) or EIC code
+    #[arg(long)]
+    zone: BiddingZone,
+    /// First day (inclusive, UTC), YYYY-MM-DD
+    #[arg(long)]
+    from: NaiveDate,
+    /// Last day (exclusive, UTC), YYYY-MM-DD
+    #[arg(long)]
+    to: NaiveDate,
+    #[arg(long, name = "out-dir")]
+    out_dir: PathBuf,
+}
+
+#[derive(Subcommand)]
+pub enum EntsoeCommand {
+    /// A44 day-ahead prices
+    Prices {
+        #[command(flatten)]
+        conn: Connection,
+        #[command(flatten)]
+        window: ZoneWindow,
+    },
+    /// A44 day-ahead prices of several zones into one dataset partitioned by zone= and dt=
+    ZonePrices {
+        #[command(flatten)]
+        conn: Connection,
+        /// Bidding zones (comma separated); defaults to NL, DE-LU, BE, FR, NO2
+        #[arg(long, value_delimiter = ',')]
+        zones: Vec<BiddingZone>,
+        /// First day (inclusive, UTC), YYYY-MM-DD
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last day (exclusive, UTC), YYYY-MM-DD
+        #[arg(long)]
+        to: NaiveDate,
+        #[arg(long, name = "out-dir")]
+        out_dir: PathBuf,
+        /// Start from --from even if partitions were already written
+        #[arg(long)]
+        no_resume: bool,
+        /// Price resolution; 15-minute MTU prices are averaged to hours by default
+        #[arg(long, default_value = "PT60M")]
+        resolution: String,
+    },
+    /// A65 day-ahead total load forecast
+    Load {
+        #[command(flatten)]
+        conn: Connection,
+        #[command(flatten)]
+        window: ZoneWindow,
+        /// Write a bitemporal dataset with knowledge times from this rule (e.g. d-1@10:00)
+        #[arg(long)]
+        knowledge: Option<KnowledgeRule>,
+    },
+    /// A69 day-ahead wind and solar forecast
+    WindSolar {
+        #[command(flatten)]
+        conn: Connection,
+        #[command(flatten)]
+        window: ZoneWindow,
+        /// Write a bitemporal dataset with knowledge times from this rule (e.g. d-1@18:00)
+        #[arg(long)]
+        knowledge: Option<KnowledgeRule>,
+    },
+    /// A80/A77/A78 unavailability (outages) as an interval table
+    Outages {
+        #[command(flatten)]
+        conn: Connection,
+        #[command(flatten)]
+        window: OutageWindow,
+        #[arg(long, value_enum, default_value_t = OutageKind::Generation)]
+        kind: OutageKind,
+        /// Counterpart zone of the border for transmission outages
+        #[arg(long)]
+        to_zone: Option<BiddingZone>,
+    },
+    /// A11/A09/A61 cross-border flows for every border of --zone, in both directions
+    Flows {
+        #[command(flatten)]
+        conn: Connection,
+        #[command(flatten)]
+        window: ZoneWindow,
+        #[arg(long, value_enum, default_value_t = FlowKind::Physical)]
+        kind: FlowKind,
+        /// Neighbouring zones (comma separated); required unless --zone is NL (defaults to its borders)
+        #[arg(long, value_delimiter = ',')]
+        neighbours: Vec<BiddingZone>,
+    },
+    /// Hourly net import position of a zone from an ingested flows directory
+    NetImport {
+        /// Output directory of `ingest-entsoe flows`
+        #[arg(long)]
+        flows_dir: PathBuf,
+        #[arg(long)]
+        zone: BiddingZone,
+        /// Flows to net: physical (A11) or scheduled (A09) exchanges
+        #[arg(long, value_enum, default_value_t = FlowKind::Physical)]
+        kind: FlowKind,
+        /// Output Parquet file
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Free-form query: key=value API parameters
+    Raw {
+        #[command(flatten)]
+        conn: Connection,
+        #[arg(long, num_args = 1..)]
+        params: Vec<String>,
+        #[arg(long, name = "out-dir")]
+        out_dir: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutageKind {
+    /// A80 generation units
+    Generation,
+    /// A77 production units
+    Production,
+    /// A78 transmission infrastructure
+    Transmission,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FlowKind {
+    /// A11 physical flows
+    Physical,
+    /// A09 total scheduled commercial exchanges
+    Scheduled,
+    /// A61 day-ahead forecasted transfer capacity
+    Capacity,
+}
+
+fn flow_query(kind: FlowKind, from: BiddingZone, to: BiddingZone, w: &ZoneWindow) -> EntsoeQuery {
+    let (start, end) = (midnight(w.from), midnight(w.to));
+    match kind {
+        FlowKind::Physical => EntsoeQuery::physical_flows(from, to, start, end),
+        FlowKind::Scheduled => EntsoeQuery::scheduled_exchanges(from, to, start, end),
+        FlowKind::Capacity => EntsoeQuery::forecasted_transfer_capacity(from, to, start, end),
+    }
+}
+
+fn outage_query(
+    kind: OutageKind,
+    w: &OutageWindow,
+    to_zone: Option<BiddingZone>,
+) -> Result<EntsoeQuery> {
+    let (from, to) = (midnight(w.from), midnight(w.to));
+    Ok(match kind {
+        OutageKind::Generation => EntsoeQuery::generation_unavailability(w.zone, from, to),
+        OutageKind::Production => EntsoeQuery::production_unavailability(w.zone, from, to),
+        OutageKind::Transmission => {
+            let other = to_zone
+                .ok_or_else(|| anyhow::anyhow!("--to-zone is required for transmission outages"))?;
+            EntsoeQuery::transmission_unavailability(w.zone, other, from, to)
+        }
+    })
+}
+
+fn midnight(d: NaiveDate) -> chrono::NaiveDateTime {
+    d.and_hms_opt(0, 0, 0).expect("midnight is a valid time")
+}
+
+fn run_query<F>(
+    conn: &Connection,
+    query: &EntsoeQuery,
+    window: &ZoneWindow,
+    table: LakeTable,
+    parse: F,
+) -> Result<()>
+where
+    F: FnMut(&str) -> Result<DataFrame>,
+{
+    let mut client = conn.client()?;
+    let opts = window.options(table);
+    run_backfill(&mut client, query, &window.out_dir, &opts, parse)
+}
+
+fn run_backfill<F>(
+    client: &mut EntsoeClient,
+    query: &EntsoeQuery,
+    out_dir: &Path,
+    opts: &BackfillOptions,
+    parse: F,
+) -> Result<()>
+where
+    F: FnMut(&str) -> Result<DataFrame>,
+{
+    let report = entsoe::backfill(client, query, out_dir, opts, parse)?;
+    println!(
+        "resumed_from={:?}, windows={}, no_data_windows={}, rows={}, requests={}, retries={}, budget_exhausted={}",
+        report.resumed_from,
+        report.windows,
+        report.no_data_windows,
+        report.rows,
+        report.requests,
+        report.retries,
+        report.budget_exhausted
+    );
+    Ok(())
+}
+
+// Time series queries, optionally resampled to --resolution before writing.
+fn run_timeseries(conn: &Connection, query: &EntsoeQuery, window: &ZoneWindow) -> Result<()> {
+    match window.resolution.as_deref() {
+        Some(res) => run_query(
+            conn,
+            query,
+            window,
+            entsoe::timeseries_lake_table(),
+            |xml| entsoe::resample_timeseries(&entsoe::parse_timeseries_xml(xml)?, res),
+        ),
+        None => run_query(
+            conn,
+            query,
+            window,
+            entsoe::timeseries_lake_table(),
+            entsoe::parse_timeseries_xml,
+        ),
+    }
+}
+
+// Forecast time series; with a knowledge rule every row gets valid_time (its start) and
+// knowledge_time and is written to a bitemporal dataset. The knowledge time is the rule's, or the
+// document's createdDateTime when that is earlier: responses are created when they are requested,
+// so in a backfill the rule holds and only documents fetched before it count as known earlier.
+fn run_forecast(
+    conn: &Connection,
+    query: &EntsoeQuery,
+    window: &ZoneWindow,
+    knowledge: Option<KnowledgeRule>,
+) -> Result<()> {
+    let Some(rule) = knowledge else {
+        return run_timeseries(conn, query, window);
+    };
+    let res = window.resolution.as_deref();
+    run_query(
+        conn,
+        query,
+        window,
+        entsoe::timeseries_bitemporal_lake_table(),
+        |xml| {
+            let long = entsoe::parse_timeseries_xml(xml)?;
+            let long = match res {
+                Some(r) => entsoe::resample_timeseries(&long, r)?,
+                None => long,
+            };
+            with_knowledge_time_by(&long, "start", rule, entsoe::document_created(xml)?)
+        },
+    )
+}
+
+// Every border direction is backfilled into its own from_zone=/to_zone= partition of one dataset, so
+// each resumes independently.
+fn run_flows(
+    conn: &Connection,
+    window: &ZoneWindow,
+    kind: FlowKind,
+    neighbours: &[BiddingZone],
+) -> Result<()> {
+    // Only the NL borders are known; other zones border zones outside the zone table too.
+    if neighbours.is_empty() && window.zone.name != "NL" {
+        return Err(anyhow::anyhow!(
+            "--neighbours is required for flows of {} (the default borders are NL's)",
+            window.zone
+        ));
+    }
+    let neighbours: Vec<BiddingZone> = if neighbours.is_empty() {
+        entsoe::NL_NEIGHBOURS
+            .iter()
+            .filter_map(|n| BiddingZone::lookup(n))
+            .collect()
+    } else {
+        neighbours.to_vec()
+    };
+    let mut client = conn.client()?;
+    for (from, to) in entsoe::border_pairs(window.zone, &neighbours) {
+        print!("{from} -> {to}: ");
+        let q = flow_query(kind, from, to, window);
+        let res = window.resolution.as_deref();
+        let mut opts = window.options(entsoe::flows_lake_table());
+        opts.resume_dir = Some(
+            window
+                .out_dir
+                .join(format!("from_zone={from}"))
+                .join(format!("to_zone={to}")),
+        );
+        run_backfill(&mut client, &q, &window.out_dir, &opts, |xml| {
+            let long = entsoe::parse_timeseries_xml(xml)?;
+            let long = match res {
+                Some(r) => entsoe::resample_timeseries(&long, r)?,
+                None => long,
+            };
+            entsoe::border_flows(&long)
+        })?;
+        if client.budget_exhausted() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// One backfill per zone into the zone=<Z>/ partition of one dataset, so zones resume independently.
+fn run_zone_prices(
+    conn: &Connection,
+    zones: &[BiddingZone],
+    (from, to): (NaiveDate, NaiveDate),
+    out_dir: &Path,
+    resume: bool,
+    resolution: &str,
+) -> Result<()> {
+    let zones: Vec<BiddingZone> = if zones.is_empty() {
+        entsoe::NL_PRICE_ZONES
+            .iter()
+            .filter_map(|z| BiddingZone::lookup(z))
+            .collect()
+    } else {
+        zones.to_vec()
+    };
+    let mut client = conn.client()?;
+    for zone in zones {
+        print!("{zone}: ");
+        let q = EntsoeQuery::day_ahead_prices(zone, midnight(from), midnight(to));
+        let opts = BackfillOptions {
+            resume,
+            resume_dir: Some(out_dir.join(format!("zone={zone}"))),
+            table: entsoe::zone_prices_lake_table(),
+            ..BackfillOptions::default()
+        };
+        run_backfill(&mut client, &q, out_dir, &opts, |xml| {
+            let long =
+                entsoe::resample_timeseries(&entsoe::parse_timeseries_xml(xml)?, resolution)?;
+            entsoe::zone_prices(&long, zone)
+        })?;
+        if client.budget_exhausted() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn run_net_import(flows_dir: &Path, zone: BiddingZone, kind: FlowKind, out: &Path) -> Result<()> {
+    let document_type = match kind {
+        FlowKind::Physical => DocumentType::PhysicalFlows,
+        FlowKind::Scheduled => DocumentType::FinalisedSchedule,
+        FlowKind::Capacity => {
+            return Err(anyhow::anyhow!(
+                "transfer capacities have no net import position"
+            ))
+        }
+    };
+    let flows = nexergy_model::read_partitioned_parquet(flows_dir)?;
+    let mut df = entsoe::net_import_position(&flows, zone.name, document_type)?;
+    if let Some(parent) = out.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    nexergy_core::io::write_parquet(&mut df, out)?;
+    println!("wrote {} hours to {}", df.height(), out.display());
+    Ok(())
+}
+
+fn run_raw(conn: &Connection, params: &[String], out_dir: &Path) -> Result<()> {
+    // Parse key=value pairs
+    let mut q: HashMap<&str, String> = HashMap::new();
+    q.insert("securityToken", conn.security_token.clone());
+    for kv in params {
+        if let Some((k, v)) = kv.split_once('=') {
+            q.insert(k, v.to_string());
+        }
+    }
+    let docs = match entsoe::fetch_documents(&conn.base_url, &q) {
+        Ok(docs) => docs,
+        Err(e) if e.is_no_data() => {
+            eprintln!("skipping: {e}");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    for xml in docs.iter() {
+        let (df, table) = if xml.contains("Unavailability_MarketDocument") {
+            (
+                entsoe::parse_unavailability_xml(xml)?,
+                entsoe::outage_lake_table(),
+            )
+        } else {
+            (
+                entsoe::parse_timeseries_xml(xml)?,
+                entsoe::timeseries_lake_table(),
+            )
+        };
+        write_partitioned(&df, out_dir, &table.source(&conn.base_url))?;
+    }
+    Ok(())
+}
+
+pub fn run(command: EntsoeCommand) -> Result<()> {
+    match command {
+        EntsoeCommand::Prices { conn, window: w } => {
+            let q = EntsoeQuery::day_ahead_prices(w.zone, midnight(w.from), midnight(w.to));
+            run_timeseries(&conn, &q, &w)
+        }
+        EntsoeCommand::ZonePrices {
+            conn,
+            zones,
+            from,
+            to,
+            out_dir,
+            no_resume,
+            resolution,
+        } => run_zone_prices(&conn, &zones, (from, to), &out_dir, !no_resume, &resolution),
+        EntsoeCommand::Load {
+            conn,
+            window: w,
+            knowledge,
+        } => {
+            let q = EntsoeQuery::load_forecast(w.zone, midnight(w.from), midnight(w.to));
+            run_forecast(&conn, &q, &w, knowledge)
+        }
+        EntsoeCommand::WindSolar {
+            conn,
+            window: w,
+            knowledge,
+        } => {
+            let q = EntsoeQuery::wind_solar_forecast(w.zone, midnight(w.from), midnight(w.to));
+            run_forecast(&conn, &q, &w, knowledge)
+        }
+        EntsoeCommand::Outages {
+            conn,
+            window: w,
+            kind,
+            to_zone,
+        } => {
+            let q = outage_query(kind, &w, to_zone)?;
+            let opts = BackfillOptions {
+                resume: false,
+                table: entsoe::outage_lake_table(),
+                ..BackfillOptions::default()
+            };
+            run_backfill(
+                &mut conn.client()?,
+                &q,
+                &w.out_dir,
+                &opts,
+                entsoe::parse_unavailability_xml,
+            )
+        }
+        EntsoeCommand::Flows {
+            conn,
+            window: w,
+            kind,
+            neighbours,
+        } => run_flows(&conn, &w, kind, &neighbours),
+        EntsoeCommand::NetImport {
+            flows_dir,
+            zone,
+            kind,
+            out,
+        } => run_net_import(&flows_dir, zone, kind, &out),
+        EntsoeCommand::Raw {
+            conn,
+            params,
+            out_dir,
+        } => run_raw(&conn, &params, &out_dir),
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, Result};
+use clap::Subcommand;
+use nexergy_core::manifest::{read_manifest, Manifest};
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum LakeCommand {
+    /// List the datasets under the lake root with their manifest summary
+    Ls {
+        #[arg(long, default_value = "data/curated")]
+        root: PathBuf,
+    },
+    /// Show a dataset's manifest: schema, primary key, sources and partitions
+    Show {
+        /// Dataset name under --root, or a dataset directory
+        dataset: String,
+        #[arg(long, default_value = "data/curated")]
+        root: PathBuf,
+        /// List every partition instead of the first and last few
+        #[arg(long)]
+        partitions: bool,
+    },
+}
+
+fn summary(m: &Manifest) -> String {
+    format!(
+        "partitions={} rows={} range={}..{} updated={}",
+        m.partitions.len(),
+        m.rows(),
+        m.min_timestamp().unwrap_or("-"),
+        m.max_timestamp().unwrap_or("-"),
+        m.updated_at
+    )
+}
+
+fn run_ls(root: &Path) -> Result<()> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(root)
+        .map_err(|e| anyhow!("read lake root {}: {e}", root.display()))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    dirs.sort();
+    for dir in dirs {
+        let name = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        match read_manifest(&dir)? {
+            Some(m) => println!("{name:<24} {}", summary(&m)),
+            None => println!("{name:<24} (no manifest)"),
+        }
+    }
+    Ok(())
+}
+
+fn resolve_dataset(root: &Path, dataset: &str) -> PathBuf {
+    let direct = PathBuf::from(dataset);
+    if direct.is_dir() && !root.join(dataset).is_dir() {
+        direct
+    } else {
+        root.join(dataset)
+    }
+}
+
+// Number of partitions printed at each end unless --partitions is given.
+const PARTITION_PREVIEW: usize = 5;
+
+fn run_show(root: &Path, dataset: &str, all_partitions: bool) -> Result<()> {
+    let dir = resolve_dataset(root, dataset);
+    let m = read_manifest(&dir)?.ok_or_else(|| {
+        anyhow!(
+            "no manifest in {} (written by the lake writer)",
+            dir.display()
+        )
+    })?;
+    println!("dataset:        {}", m.dataset);
+    println!("path:           {}", dir.display());
+    println!("{}", summary(&m));
+    println!("created:        {}", m.created_at);
+    println!("tool version:   {}", m.tool_version);
+    println!("layout:         {} on {}", m.layout, m.date_column);
+    println!("partition keys: {:?}", m.partition_keys);
+    println!("primary key:    {:?}", m.primary_key);
+    println!("sources:");
+    for s in m.sources() {
+        println!("  {s}");
+    }
+    println!("schema:");
+    for c in m.schema.iter() {
+        println!("  {:<24} {}", c.name, c.dtype);
+    }
+    println!("partitions:");
+    let n = m.partitions.len();
+    for (i, (path, p)) in m.partitions.iter().enumerate() {
+        let shown = all_partitions || i < PARTITION_PREVIEW || i + PARTITION_PREVIEW >= n;
+        if !shown {
+            if i == PARTITION_PREVIEW {
+                println!(
+                    "  ... {} more (use --partitions)",
+                    n - 2 * PARTITION_PREVIEW
+                );
+            }
+            continue;
+        }
+        println!(
+            "  {path:<32} rows={:<6} {}..{} sha256={} ingested={}",
+            p.rows,
+            p.min_timestamp.as_deref().unwrap_or("-"),
+            p.max_timestamp.as_deref().unwrap_or("-"),
+            &p.checksum[..p.checksum.len().min(12)],
+            p.ingested_at
+        );
+    }
+    Ok(())
+}
+
+pub fn run(command: LakeCommand) -> Result<()> {
+    match command {
+        LakeCommand::Ls { root } => run_ls(&root),
+        LakeCommand::Show {
+            dataset,
+            root,
+            partitions,
+        } => run_show(&root, &dataset, partitions),
+    }
+}
@@ -1,8 +1,19 @@
 use anyhow::Result;
+use chrono::NaiveDate;
 use clap::{Parser, Subcommand};
+use nexergy_core::asof::{with_knowledge_time, KnowledgeRule};
+use nexergy_core::io::{write_partitioned, LakeTable};
+use nexergy_core::quality::{validate_series, QualityOptions};
+use nexergy_core::time::{column_utc, format_ts};
+use polars::prelude::DataFrame;
 use std::path::Path;
 use std::path::PathBuf;
 
+mod entsoe;
+mod lake;
+mod model;
+mod validate;
+
 #[derive(Parser)]
 #[command(author, version, about)]
 struct Cli {
@@ -18,6 +29,9 @@ enum Commands {
         input: PathBuf,
         #[arg(long, name = "out-dir")]
         out_dir: PathBuf,
+        /// Data quality limits checked before writing (see `validate prices`)
+        #[command(flatten)]
+        quality: validate::ThresholdArgs,
     },
     #[command(name = "ingest-gas")]
     Gas {
@@ -25,6 +39,10 @@ enum Commands {
         input: PathBuf,
         #[arg(long, name = "out-dir")]
         out_dir: PathBuf,
+        /// Write a bitemporal dataset with knowledge times from this rule (+<n>m|h|d after the
+        /// delivery time, d-1@HH:MM local, or now)
+        #[arg(long)]
+        knowledge: Option<KnowledgeRule>,
     },
     #[command(name = "ingest-knmi-hourly")]
     KnmiHourly {
@@ -54,40 +72,141 @@ enum Commands {
     },
     #[command(name = "ingest-entsoe")]
     Entsoe {
+        #[command(subcommand)]
+        command: entsoe::EntsoeCommand,
+    },
+    /// Inspect dataset manifests of the Parquet lake
+    Lake {
+        #[command(subcommand)]
+        command: lake::LakeCommand,
+    },
+    /// Check curated datasets for gaps, duplicates, DST anomalies and spikes
+    Validate {
+        #[command(subcommand)]
+        command: validate::ValidateCommand,
+    },
+    #[command(name = "train-eval")]
+    TrainEval {
+        #[command(flatten)]
+        model: model::ModelArgs,
+        #[arg(long, default_value_t = 2024)]
+        cutoff_year: i32,
+        /// Also forecast these quantiles (e.g. 0.05,0.25,0.5,0.75,0.95) with one quantile model
+        /// each (linear or gbt) and score them by pinball loss and coverage
+        #[arg(long, value_delimiter = ',')]
+        quantiles: Vec<f64>,
+        /// Conformal prediction intervals at these coverages (e.g. 0.8,0.9) around the point
+        /// forecast, calibrated on its residuals
+        #[arg(long, value_delimiter = ',')]
+        conformal: Vec<f64>,
+        /// Days held out at the end of training to calibrate conformal intervals (and the rolling
+        /// residual window)
+        #[arg(long, default_value_t = 90)]
+        calibration_days: usize,
+        /// Calibrate conformal intervals on residuals of the same hour of day only
         #[arg(long)]
-        base_url: String,
+        conformal_by_hour: bool,
+        /// Calibrate on the held-out window only instead of rolling it forward through the test
+        /// period
         #[arg(long)]
-        security_token: String,
+        conformal_split: bool,
+    },
+    /// Fit a model as train-eval does and save it with its options, training window and test
+    /// metrics
+    Train {
+        #[command(flatten)]
+        model: model::ModelArgs,
+        #[arg(long, default_value_t = 2024)]
+        cutoff_year: i32,
+        /// Model artifact (JSON) to write
         #[arg(long)]
-        params: Vec<String>,
-        #[arg(long, name = "out-dir")]
-        out_dir: PathBuf,
+        save: PathBuf,
     },
-    #[command(name = "train-eval")]
-    TrainEval {
+    /// Forecast a local delivery day with a model saved by `train`
+    Predict {
+        /// Model artifact written by `train --save`
+        #[arg(long)]
+        model: PathBuf,
         #[arg(long, name = "prices-dir")]
         prices_dir: PathBuf,
-        #[arg(long, default_value_t = 2024)]
-        cutoff_year: i32,
-        #[arg(long, default_value_t = 24)]
-        lags: usize,
-        #[arg(long, default_value_t = 0.01)]
-        learning_rate: f64,
-        #[arg(long, default_value_t = 2000)]
-        epochs: usize,
+        /// Local delivery day to forecast, YYYY-MM-DD
+        #[arg(long)]
+        date: NaiveDate,
+    },
+    /// Walk forward through a test period, re-fitting before each block of days, and write every
+    /// forecast with its issue time
+    Backtest {
+        #[command(flatten)]
+        model: model::ModelArgs,
+        /// First local delivery day to forecast (inclusive), YYYY-MM-DD
+        #[arg(long)]
+        start: NaiveDate,
+        /// Last local delivery day to forecast (exclusive), YYYY-MM-DD
+        #[arg(long)]
+        end: NaiveDate,
+        /// Days between re-fits (1 = daily, 7 = weekly)
+        #[arg(long, default_value_t = 1)]
+        refit_days: usize,
+        /// Train on this many days before each fit day instead of all earlier days
+        #[arg(long)]
+        window_days: Option<usize>,
+        /// Lake directory for the forecast/actual table
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
     },
 }
 
+fn write_lake(df: &DataFrame, out_dir: &Path, table: &LakeTable) -> Result<()> {
+    let report = write_partitioned(df, out_dir, table)?;
+    println!(
+        "partitions={}, rows_written={}, rows_replaced={}, rows_skipped={}",
+        report.partitions, report.rows_written, report.rows_replaced, report.rows_skipped
+    );
+    Ok(())
+}
+
+fn print_baselines(baselines: &[nexergy_model::BaselineScore]) {
+    for b in baselines {
+        println!("baseline {}: MAE={:.3}, RMSE={:.3}", b.name, b.mae, b.rmse);
+    }
+}
+
+fn knmi_source(url: &str, stns: &str, vars: &str) -> String {
+    format!("{url}?stns={stns}&vars={vars}")
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
     match cli.command {
-        Commands::Prices { input, out_dir } => {
+        Commands::Prices {
+            input,
+            out_dir,
+            quality,
+        } => {
             let df = nexergy_ingest::prices::read_price_json_array_to_df(&input)?;
-            nexergy_ingest::prices::write_partitioned_by_date(&df, &out_dir)?;
+            let thresholds = quality.thresholds();
+            if !thresholds.is_empty() {
+                let report = validate_series(&df, &QualityOptions::default())?;
+                validate::print_summary(&report);
+                report.check(&thresholds)?;
+            }
+            let table = nexergy_ingest::prices::lake_table().source(&input.display().to_string());
+            write_lake(&df, &out_dir, &table)?;
         }
-        Commands::Gas { input, out_dir } => {
+        Commands::Gas {
+            input,
+            out_dir,
+            knowledge,
+        } => {
             let df = nexergy_ingest::gas::read_gas_json_array_to_df(&input)?;
-            nexergy_ingest::gas::write_partitioned_by_date(&df, &out_dir)?;
+            let (df, table) = match knowledge {
+                Some(rule) => (
+                    with_knowledge_time(&df, "datetime_utc", rule)?,
+                    nexergy_ingest::gas::bitemporal_lake_table(),
+                ),
+                None => (df, nexergy_ingest::gas::lake_table()),
+            };
+            write_lake(&df, &out_dir, &table.source(&input.display().to_string()))?;
         }
         Commands::KnmiHourly {
             start,
@@ -97,7 +216,13 @@ fn main() -> Result<()> {
             out_dir,
         } => {
             let df = nexergy_ingest::knmi::fetch_knmi_hourly_to_df(&start, &end, &stns, &vars)?;
-            nexergy_ingest::knmi::write_partitioned_by_date(&df, "YYYYMMDD", &out_dir)?;
+            let df = nexergy_ingest::knmi::with_date_column(&df)?;
+            let source = knmi_source(nexergy_ingest::knmi::KNMI_HOURLY_URL, &stns, &vars);
+            write_lake(
+                &df,
+                &out_dir,
+                &nexergy_ingest::knmi::hourly_lake_table().source(&source),
+            )?;
         }
         Commands::KnmiDaily {
             start,
@@ -107,66 +232,225 @@ fn main() -> Result<()> {
             out_dir,
         } => {
             let df = nexergy_ingest::knmi::fetch_knmi_daily_to_df(&start, &end, &stns, &vars)?;
-            nexergy_ingest::knmi::write_partitioned_by_date(&df, "YYYYMMDD", &out_dir)?;
+            let df = nexergy_ingest::knmi::with_date_column(&df)?;
+            let source = knmi_source(nexergy_ingest::knmi::KNMI_DAILY_URL, &stns, &vars);
+            write_lake(
+                &df,
+                &out_dir,
+                &nexergy_ingest::knmi::daily_lake_table().source(&source),
+            )?;
         }
-        Commands::Entsoe {
-            base_url,
-            security_token,
-            params,
-            out_dir,
+        Commands::Entsoe { command } => entsoe::run(command)?,
+        Commands::Lake { command } => lake::run(command)?,
+        Commands::Validate { command } => validate::run(command)?,
+        Commands::TrainEval {
+            model,
+            cutoff_year,
+            quantiles,
+            conformal,
+            calibration_days,
+            conformal_by_hour,
+            conformal_split,
         } => {
-            // Parse key=value pairs
-            let mut q: std::collections::HashMap<&str, String> = std::collections::HashMap::new();
-            q.insert("securityToken", security_token);
-            for kv in params {
-                if let Some((k, v)) = kv.split_once('=') {
-                    q.insert(Box::leak(k.to_string().into_boxed_str()), v.to_string());
+            let (prices_dir, solver) = (model.prices_dir.clone(), model.solver);
+            let opts = nexergy_model::TrainEvalOptions {
+                cutoff_year,
+                quantiles,
+                conformal: (!conformal.is_empty()).then_some(nexergy_model::ConformalOptions {
+                    coverages: conformal,
+                    calibration_days,
+                    by_hour: conformal_by_hour,
+                    rolling: !conformal_split,
+                }),
+                ..model.options()
+            };
+            let res = nexergy_model::train_eval(&prices_dir, &opts)?;
+            println!(
+                "train_n={}, test_n={}, missing_steps={}, MAE={:.3}, RMSE={:.3}",
+                res.train_n, res.test_n, res.missing_steps, res.mae, res.rmse
+            );
+            print_baselines(&res.baselines);
+            match res.model {
+                Some(nexergy_model::Regressor::Linear(model)) => {
+                    println!(
+                        "features={}, weights={:?}",
+                        model.feature_names.len(),
+                        model.weights
+                    );
+                    if let Some(path) = res.path {
+                        let kept = model.kept_features();
+                        println!(
+                            "lambda={:.6} ({} of {} on the path), kept {}/{} features: {}",
+                            path.lambda(),
+                            path.selected + 1,
+                            path.points.len(),
+                            kept.len(),
+                            model.feature_names.len(),
+                            kept.join(",")
+                        );
+                    }
                 }
+                Some(nexergy_model::Regressor::GradientBoosting(model)) => println!(
+                    "features={}, trees={}, validation_loss={:?}",
+                    model.feature_names.len(),
+                    model.trees.len(),
+                    model.validation_loss
+                ),
+                Some(nexergy_model::Regressor::RandomForest(model)) => println!(
+                    "features={}, trees={}, OOB_MAE={:.3}, OOB_RMSE={:.3} (oob_n={})",
+                    model.feature_names.len(),
+                    model.trees.len(),
+                    model.oob_mae,
+                    model.oob_rmse,
+                    model.oob_n
+                ),
+                None => {}
+            }
+            if let Some(model) = res.day_ahead_model {
+                println!(
+                    "features={}, period_models={}",
+                    model.feature_names.len(),
+                    model.periods.iter().flatten().count()
+                );
+                let forests: Vec<&nexergy_model::ForestModel> = model
+                    .periods
+                    .iter()
+                    .flatten()
+                    .filter_map(|m| match m {
+                        nexergy_model::Regressor::RandomForest(f) => Some(f),
+                        _ => None,
+                    })
+                    .collect();
+                if !forests.is_empty() {
+                    let n = forests.len() as f64;
+                    println!(
+                        "mean period OOB_MAE={:.3}, OOB_RMSE={:.3}",
+                        forests.iter().map(|f| f.oob_mae).sum::<f64>() / n,
+                        forests.iter().map(|f| f.oob_rmse).sum::<f64>() / n
+                    );
+                }
+                if matches!(solver, model::Solver::ElasticNet) {
+                    let kept: Vec<String> = model
+                        .kept_features()
+                        .iter()
+                        .map(|(name, n)| format!("{name}({n})"))
+                        .collect();
+                    println!("kept features (period models): {}", kept.join(","));
+                }
+            }
+            for q in &res.quantiles {
+                println!(
+                    "quantile {}: pinball={:.3}, coverage={:.3}",
+                    q.quantile, q.pinball, q.coverage
+                );
+            }
+            for i in &res.intervals {
+                println!(
+                    "interval {}-{} (nominal {:.3}): coverage={:.3}, mean_width={:.3}",
+                    i.lower,
+                    i.upper,
+                    i.upper - i.lower,
+                    i.coverage,
+                    i.mean_width
+                );
+            }
+            for c in &res.conformal {
+                println!(
+                    "conformal {:.3}: coverage={:.3}, mean_width={:.3}",
+                    c.nominal, c.coverage, c.mean_width
+                );
             }
-            let xml = nexergy_ingest::entsoe::fetch_xml_to_string(&base_url, &q)?;
-            let df = nexergy_ingest::entsoe::parse_timeseries_xml(&xml)?;
-            nexergy_ingest::entsoe::write_partitioned_by_start_date(&df, &out_dir)?;
         }
-        Commands::TrainEval {
-            prices_dir,
+        Commands::Train {
+            model,
             cutoff_year,
-            lags,
-            learning_rate,
-            epochs,
+            save,
         } => {
-            let res = nexergy_model::train_eval_from_curated(
-                Path::new(&prices_dir),
-                "price_eur_mwh",
-                "datetime_utc",
-                lags,
+            let prices_dir = model.prices_dir.clone();
+            let opts = nexergy_model::TrainEvalOptions {
                 cutoff_year,
-                learning_rate,
-                epochs,
-            )?;
-            if let Some(model) = res.model {
+                ..model.options()
+            };
+            let res = nexergy_model::train_eval(&prices_dir, &opts)?;
+            println!(
+                "train_n={}, test_n={}, missing_steps={}, MAE={:.3}, RMSE={:.3}",
+                res.train_n, res.test_n, res.missing_steps, res.mae, res.rmse
+            );
+            print_baselines(&res.baselines);
+            let artifact = nexergy_model::ModelArtifact::new(&opts, &res)?;
+            artifact.save(&save)?;
+            println!(
+                "saved model (features={}, trained on {} to {}) to {}",
+                artifact.feature_names().len(),
+                artifact.training.first,
+                artifact.training.last,
+                save.display()
+            );
+        }
+        Commands::Predict {
+            model,
+            prices_dir,
+            date,
+        } => {
+            let artifact = nexergy_model::ModelArtifact::load(&model)?;
+            let forecasts = nexergy_model::predict(&prices_dir, &artifact, date)?;
+            let times = column_utc(forecasts.column(&artifact.options.date_col)?)?;
+            let actual = forecasts.column("actual")?.as_materialized_series();
+            let forecast = forecasts.column("forecast")?.as_materialized_series();
+            println!("{},forecast,actual", artifact.options.date_col);
+            for ((t, f), a) in times.iter().zip(forecast.f64()?).zip(actual.f64()?) {
+                let fmt = |v: Option<f64>| v.map(|v| format!("{v:.3}")).unwrap_or_default();
                 println!(
-                    "train_n={}, test_n={}, MAE={:.3}, RMSE={:.3}, baseline_MAE={:?}, baseline_RMSE={:?}, features={}, weights={:?}",
-                    res.train_n,
-                    res.test_n,
-                    res.mae,
-                    res.rmse,
-                    res.baseline_mae,
-                    res.baseline_rmse,
-                    model.feature_names.len(),
-                    model.weights
+                    "{},{},{}",
+                    t.map(format_ts).unwrap_or_default(),
+                    fmt(f),
+                    fmt(a)
                 );
-            } else {
+            }
+            if actual.null_count() < actual.len() {
                 println!(
-                    "train_n={}, test_n={}, MAE={:.3}, RMSE={:.3}, baseline_MAE={:?}, baseline_RMSE={:?}",
-                    res.train_n,
-                    res.test_n,
-                    res.mae,
-                    res.rmse,
-                    res.baseline_mae,
-                    res.baseline_rmse,
+                    "MAE={:.3}, RMSE={:.3} over {} periods with a price",
+                    nexergy_model::mean_absolute_error(actual, forecast)?,
+                    nexergy_model::root_mean_squared_error(actual, forecast)?,
+                    actual.len() - actual.null_count()
                 );
             }
         }
+        Commands::Backtest {
+            model,
+            start,
+            end,
+            refit_days,
+            window_days,
+            out_dir,
+        } => {
+            let prices_dir = model.prices_dir.clone();
+            let opts = nexergy_model::BacktestOptions {
+                model: model.options(),
+                start,
+                end,
+                refit_days,
+                window: window_days.map_or(
+                    nexergy_model::TrainWindow::Expanding,
+                    nexergy_model::TrainWindow::Rolling,
+                ),
+            };
+            let res = nexergy_model::backtest(&prices_dir, &opts)?;
+            println!(
+                "fits={}, forecasts={}, missing_steps={}, MAE={:.3}, RMSE={:.3}",
+                res.fits,
+                res.forecasts.height(),
+                res.missing_steps,
+                res.mae,
+                res.rmse
+            );
+            print_baselines(&res.baselines);
+            if let Some(dir) = out_dir {
+                let table = nexergy_model::forecast_table(&opts.model.date_col)
+                    .source(&prices_dir.display().to_string());
+                write_lake(&res.forecasts, &dir, &table)?;
+            }
+        }
     }
     Ok(())
 }
@@ -0,0 +1,227 @@
+use chrono::{Duration, NaiveDate, NaiveTime};
+use clap::{Args, ValueEnum};
+use nexergy_model::{
+    AsOfFeature, ElasticNetOptions, Estimator, ForecastMode, ForestOptions, GbtOptions,
+    LambdaSelection, LinearSolver, NeighbourFeatures, TrainEvalOptions,
+};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Mode {
+    OneStep,
+    DayAhead,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum EstimatorKind {
+    Linear,
+    Gbt,
+    Forest,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Solver {
+    Ridge,
+    Gd,
+    ElasticNet,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LambdaSelect {
+    Cv,
+    Bic,
+    Aic,
+}
+
+// Features and model shared by train-eval and backtest.
+#[derive(Args)]
+pub struct ModelArgs {
+    #[arg(long, name = "prices-dir")]
+    pub prices_dir: PathBuf,
+    /// one-step: each hour from the hours before it; day-ahead: all periods of day D from data
+    /// known at the D-1 gate
+    #[arg(long, value_enum, default_value_t = Mode::OneStep)]
+    mode: Mode,
+    #[arg(long, default_value_t = 24)]
+    lags: usize,
+    /// Day-ahead mode: same-period prices this many days back
+    #[arg(long, value_delimiter = ',', default_value = "1,2,7")]
+    day_lags: Vec<usize>,
+    /// Spacing of the price series in minutes (60 or 15); lags are multiples of it
+    #[arg(long, default_value_t = 60)]
+    step_minutes: i64,
+    /// linear: linear regression (see --solver); gbt: histogram gradient-boosted trees; forest:
+    /// random forest
+    #[arg(long, value_enum, default_value_t = EstimatorKind::Linear)]
+    estimator: EstimatorKind,
+    /// ridge: closed-form least squares with an L2 penalty; gd: gradient descent; elastic-net:
+    /// coordinate descent along a lasso/elastic net penalty path
+    #[arg(long, value_enum, default_value_t = Solver::Ridge)]
+    pub solver: Solver,
+    /// Ridge L2 penalty on the standardized weights (0 = ordinary least squares)
+    #[arg(long, default_value_t = 0.01)]
+    l2: f64,
+    /// Gradient descent step size
+    #[arg(long, default_value_t = 0.01)]
+    learning_rate: f64,
+    /// Gradient descent epochs
+    #[arg(long, default_value_t = 2000)]
+    epochs: usize,
+    /// Elastic net: share of the penalty that is L1 (1 = lasso)
+    #[arg(long, default_value_t = 1.0)]
+    l1_ratio: f64,
+    /// Elastic net: number of penalties on the path
+    #[arg(long, default_value_t = 50)]
+    n_lambdas: usize,
+    /// Elastic net: penalty selection by time-series cross-validation or information criterion
+    #[arg(long, value_enum, default_value_t = LambdaSelect::Cv)]
+    lambda_select: LambdaSelect,
+    /// Elastic net: cross-validation folds
+    #[arg(long, default_value_t = 5)]
+    cv_folds: usize,
+    /// Multi-zone price dataset (ingest-entsoe zone-prices) to join neighbour prices from
+    #[arg(long)]
+    zone_prices_dir: Option<PathBuf>,
+    /// Neighbour zones whose prices and spreads become features
+    #[arg(long, value_delimiter = ',', default_value = "DE-LU,BE,FR,NO2")]
+    neighbour_zones: Vec<String>,
+    /// Hour lags of the neighbour price and spread features (day-ahead mode uses --day-lags)
+    #[arg(long, value_delimiter = ',', default_value = "1,24")]
+    neighbour_lags: Vec<usize>,
+    /// First day to read (inclusive, UTC), YYYY-MM-DD; earlier partitions are skipped
+    #[arg(long)]
+    from: Option<NaiveDate>,
+    /// Last day to read (exclusive, UTC), YYYY-MM-DD
+    #[arg(long)]
+    to: Option<NaiveDate>,
+    /// Bitemporal feature joined as known at the gate closure:
+    /// NAME=DIR[,value=COLUMN][,mode=exact|latest][,COLUMN=VALUE...]; repeatable
+    #[arg(long = "asof-feature")]
+    asof_features: Vec<AsOfFeature>,
+    /// Day-ahead gate closure (local time on the day before delivery), HH:MM
+    #[arg(long, default_value = "12:00")]
+    gate: NaiveTime,
+    #[command(flatten)]
+    trees: TreeArgs,
+}
+
+// Tree ensemble settings; unset values take the estimator's defaults.
+#[derive(Args)]
+#[command(next_help_heading = "Trees")]
+pub struct TreeArgs {
+    /// Number of trees (an upper bound with early stopping)
+    #[arg(long)]
+    trees: Option<usize>,
+    #[arg(long)]
+    max_depth: Option<usize>,
+    #[arg(long)]
+    min_samples_leaf: Option<usize>,
+    /// Histogram bins per feature (at most 256)
+    #[arg(long)]
+    max_bins: Option<usize>,
+    /// Boosting: shrinkage of every tree
+    #[arg(long)]
+    gbt_learning_rate: Option<f64>,
+    /// Boosting: share of rows drawn for each tree
+    #[arg(long)]
+    subsample: Option<f64>,
+    /// Boosting: L2 penalty on leaf values
+    #[arg(long)]
+    leaf_l2: Option<f64>,
+    /// Boosting: latest share of the training rows held out for early stopping (0 = off)
+    #[arg(long)]
+    validation_fraction: Option<f64>,
+    /// Boosting: stop after this many trees without a better validation error
+    #[arg(long)]
+    early_stopping_rounds: Option<usize>,
+    /// Forest: features tried per split (default: a third of them)
+    #[arg(long)]
+    max_features: Option<usize>,
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+impl TreeArgs {
+    fn forest(&self) -> ForestOptions {
+        let d = ForestOptions::default();
+        ForestOptions {
+            n_trees: self.trees.unwrap_or(d.n_trees),
+            max_depth: self.max_depth.unwrap_or(d.max_depth),
+            min_samples_leaf: self.min_samples_leaf.unwrap_or(d.min_samples_leaf),
+            max_features: self.max_features.or(d.max_features),
+            max_bins: self.max_bins.unwrap_or(d.max_bins),
+            seed: self.seed.unwrap_or(d.seed),
+        }
+    }
+
+    fn gbt(&self) -> GbtOptions {
+        let d = GbtOptions::default();
+        GbtOptions {
+            loss: d.loss,
+            n_trees: self.trees.unwrap_or(d.n_trees),
+            max_depth: self.max_depth.unwrap_or(d.max_depth),
+            learning_rate: self.gbt_learning_rate.unwrap_or(d.learning_rate),
+            subsample: self.subsample.unwrap_or(d.subsample),
+            l2: self.leaf_l2.unwrap_or(d.l2),
+            min_samples_leaf: self.min_samples_leaf.unwrap_or(d.min_samples_leaf),
+            max_bins: self.max_bins.unwrap_or(d.max_bins),
+            validation_fraction: self.validation_fraction.unwrap_or(d.validation_fraction),
+            early_stopping_rounds: self
+                .early_stopping_rounds
+                .unwrap_or(d.early_stopping_rounds),
+            seed: self.seed.unwrap_or(d.seed),
+        }
+    }
+}
+
+impl ModelArgs {
+    fn linear_solver(&self) -> LinearSolver {
+        match self.solver {
+            Solver::Ridge => LinearSolver::Ridge { l2: self.l2 },
+            Solver::Gd => LinearSolver::GradientDescent {
+                learning_rate: self.learning_rate,
+                epochs: self.epochs,
+            },
+            Solver::ElasticNet => LinearSolver::ElasticNet(ElasticNetOptions {
+                l1_ratio: self.l1_ratio,
+                n_lambdas: self.n_lambdas,
+                selection: match self.lambda_select {
+                    LambdaSelect::Cv => LambdaSelection::TimeSeriesCv {
+                        folds: self.cv_folds,
+                    },
+                    LambdaSelect::Bic => LambdaSelection::Bic,
+                    LambdaSelect::Aic => LambdaSelection::Aic,
+                },
+                ..ElasticNetOptions::default()
+            }),
+        }
+    }
+
+    pub fn options(self) -> TrainEvalOptions {
+        let estimator = match self.estimator {
+            EstimatorKind::Linear => Estimator::Linear(self.linear_solver()),
+            EstimatorKind::Gbt => Estimator::GradientBoosting(self.trees.gbt()),
+            EstimatorKind::Forest => Estimator::RandomForest(self.trees.forest()),
+        };
+        TrainEvalOptions {
+            mode: match self.mode {
+                Mode::OneStep => ForecastMode::OneStep,
+                Mode::DayAhead => ForecastMode::DayAhead,
+            },
+            lags: self.lags,
+            day_lags: self.day_lags,
+            step: Duration::minutes(self.step_minutes),
+            estimator,
+            neighbours: self.zone_prices_dir.map(|dir| NeighbourFeatures {
+                zone_prices_dir: dir,
+                zones: self.neighbour_zones,
+                lags: self.neighbour_lags,
+            }),
+            from: self.from,
+            to: self.to,
+            asof_features: self.asof_features,
+            gate: self.gate,
+            ..TrainEvalOptions::default()
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::{Args, Subcommand};
+use nexergy_core::quality::{validate_series, QualityOptions, QualityReport, QualityThresholds};
+use nexergy_core::scan::{read_lake, LakeScan};
+use std::path::{Path, PathBuf};
+
+// Issue limits; any limit given makes the command (or ingestion) fail when it is exceeded.
+#[derive(Args, Clone, Default)]
+pub struct ThresholdArgs {
+    /// Fail when more hours than this are missing
+    #[arg(long)]
+    max_missing: Option<usize>,
+    /// Fail when more duplicate timestamps than this are found
+    #[arg(long)]
+    max_duplicates: Option<usize>,
+    /// Fail when more rows than this are out of order
+    #[arg(long)]
+    max_out_of_order: Option<usize>,
+    /// Fail when more values than this are null, NaN or infinite
+    #[arg(long)]
+    max_non_finite: Option<usize>,
+    /// Fail when more rows than this have no timestamp
+    #[arg(long)]
+    max_null_timestamps: Option<usize>,
+    /// Fail when more spikes than this are found
+    #[arg(long)]
+    max_spikes: Option<usize>,
+    /// Fail when more DST days than this have the wrong number of hours
+    #[arg(long)]
+    max_dst_anomalies: Option<usize>,
+}
+
+impl ThresholdArgs {
+    pub fn thresholds(&self) -> QualityThresholds {
+        QualityThresholds {
+            max_missing: self.max_missing,
+            max_duplicates: self.max_duplicates,
+            max_out_of_order: self.max_out_of_order,
+            max_non_finite: self.max_non_finite,
+            max_null_timestamps: self.max_null_timestamps,
+            max_spikes: self.max_spikes,
+            max_dst_anomalies: self.max_dst_anomalies,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ValidateCommand {
+    /// Report gaps, duplicates, out-of-order rows, non-finite values, DST anomalies and spikes per day
+    Prices {
+        #[arg(long, default_value = "data/curated/prices")]
+        prices_dir: PathBuf,
+        /// First day to check (inclusive, UTC), YYYY-MM-DD
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to check (exclusive, UTC), YYYY-MM-DD
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// Spike threshold in median absolute deviations from the day's median
+        #[arg(long, default_value_t = 10.0)]
+        spike_mad: f64,
+        /// Minimum distance (EUR/MWh) from the day's median for a spike
+        #[arg(long, default_value_t = 150.0)]
+        spike_min_abs: f64,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        thresholds: ThresholdArgs,
+    },
+}
+
+pub fn print_summary(r: &QualityReport) {
+    println!(
+        "rows={} range={}..{} missing={} duplicates={} out_of_order={} non_finite={} null_timestamps={} spikes={} dst_anomalies={}",
+        r.rows,
+        r.first.as_deref().unwrap_or("-"),
+        r.last.as_deref().unwrap_or("-"),
+        r.missing,
+        r.duplicates,
+        r.out_of_order,
+        r.non_finite,
+        r.null_timestamps,
+        r.spikes,
+        r.dst_anomalies
+    );
+}
+
+// Number of gaps and days printed unless --json is given.
+const PREVIEW: usize = 20;
+
+fn print_report(r: &QualityReport) {
+    print_summary(r);
+    if !r.gaps.is_empty() {
+        println!("gaps ({}):", r.gaps.len());
+        for g in r.gaps.iter().take(PREVIEW) {
+            println!(
+                "  {}..{} ({} missing)",
+                g.first_missing, g.last_missing, g.steps
+            );
+        }
+    }
+    // Days without any rows are covered by the gap listing.
+    let (empty, days): (Vec<_>, Vec<_>) = r.issue_days().partition(|d| d.observed == 0);
+    if !empty.is_empty() {
+        println!("days without data: {}", empty.len());
+    }
+    if !days.is_empty() {
+        println!("days with issues ({}):", days.len());
+        for d in days.iter().take(PREVIEW) {
+            println!(
+                "  {} hours={}/{} missing={} duplicates={} out_of_order={} non_finite={} spikes={}{}",
+                d.date,
+                d.observed,
+                d.expected,
+                d.missing,
+                d.duplicates,
+                d.out_of_order,
+                d.non_finite,
+                d.spikes,
+                if d.dst_anomaly { " dst-anomaly" } else { "" }
+            );
+        }
+    }
+}
+
+fn run_prices(
+    prices_dir: &Path,
+    range: (Option<NaiveDate>, Option<NaiveDate>),
+    opts: &QualityOptions,
+    json: bool,
+    thresholds: &QualityThresholds,
+) -> Result<()> {
+    let df = read_lake(
+        prices_dir,
+        &LakeScan::new()
+            .between(range.0, range.1)
+            .columns(&[&opts.timestamp_col, &opts.value_col]),
+    )?;
+    let report = validate_series(&df, opts)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    report.check(thresholds)
+}
+
+pub fn run(command: ValidateCommand) -> Result<()> {
+    match command {
+        ValidateCommand::Prices {
+            prices_dir,
+            from,
+            to,
+            spike_mad,
+            spike_min_abs,
+            json,
+            thresholds,
+        } => {
+            let opts = QualityOptions {
+                spike_mad,
+                spike_min_abs,
+                ..QualityOptions::default()
+            };
+            run_prices(
+                &prices_dir,
+                (from, to),
+                &opts,
+                json,
+                &thresholds.thresholds(),
+            )
+        }
+    }
+}
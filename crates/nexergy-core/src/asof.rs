@@ -0,0 +1,254 @@
+use crate::time::{column_utc, utc_column, utc_to_wall_clock, wall_clock_to_utc, LOCAL_TZ};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDateTime, NaiveTime, Utc};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Bitemporal records carry the instant a value applies to (`valid_time`, e.g. the delivery hour of a
+// forecast) and the instant it became known (`knowledge_time`, e.g. its publication). Both are UTC.
+// A revised value is a new row with a later knowledge time; earlier versions are kept.
+pub const VALID_TIME: &str = "valid_time";
+pub const KNOWLEDGE_TIME: &str = "knowledge_time";
+
+// When the values of a source became known, for sources that do not say so themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnowledgeRule {
+    // Known this long after the valid time (observations, settlement prices).
+    Delay(Duration),
+    // Known at this local (Europe/Amsterdam) time on the day before the valid time's local day
+    // (day-ahead forecasts).
+    DayBefore(NaiveTime),
+    // Known at a fixed instant, e.g. the ingestion time of a live snapshot.
+    At(NaiveDateTime),
+}
+
+impl KnowledgeRule {
+    pub fn knowledge_time(&self, valid: NaiveDateTime) -> Option<NaiveDateTime> {
+        match *self {
+            KnowledgeRule::Delay(d) => Some(valid + d),
+            KnowledgeRule::DayBefore(at) => {
+                let day = utc_to_wall_clock(valid, LOCAL_TZ).date().pred_opt()?;
+                wall_clock_to_utc(day.and_time(at), LOCAL_TZ)
+            }
+            KnowledgeRule::At(t) => Some(t),
+        }
+    }
+}
+
+// `+90m`, `+6h`, `+1d` (delay after the valid time), `d-1@10:00` (local time on the day before) or
+// `now` (ingestion time).
+impl FromStr for KnowledgeRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s == "now" {
+            return Ok(KnowledgeRule::At(Utc::now().naive_utc()));
+        }
+        if let Some(at) = s.strip_prefix("d-1@") {
+            let t = NaiveTime::parse_from_str(at, "%H:%M")
+                .map_err(|e| anyhow!("invalid time in knowledge rule '{s}': {e}"))?;
+            return Ok(KnowledgeRule::DayBefore(t));
+        }
+        let bad = || anyhow!("invalid knowledge rule '{s}'; expected +<n>m|h|d, d-1@HH:MM or now");
+        let rest = s.strip_prefix('+').ok_or_else(bad)?;
+        let (num, unit) = rest.split_at(rest.len().saturating_sub(1));
+        let n: i64 = num.parse().map_err(|_| bad())?;
+        match unit {
+            "m" => Ok(KnowledgeRule::Delay(Duration::minutes(n))),
+            "h" => Ok(KnowledgeRule::Delay(Duration::hours(n))),
+            "d" => Ok(KnowledgeRule::Delay(Duration::days(n))),
+            _ => Err(bad()),
+        }
+    }
+}
+
+// Add `valid_time` (the UTC instants of `valid_col`) and `knowledge_time` (per `rule`) to `df`.
+pub fn with_knowledge_time(
+    df: &DataFrame,
+    valid_col: &str,
+    rule: KnowledgeRule,
+) -> Result<DataFrame> {
+    with_knowledge_time_by(df, valid_col, rule, None)
+}
+
+// As with_knowledge_time, for rows that were published by `published` at the latest (e.g. the
+// creation time of the document they came from): each knowledge time is the earlier of the rule's
+// and `published`.
+pub fn with_knowledge_time_by(
+    df: &DataFrame,
+    valid_col: &str,
+    rule: KnowledgeRule,
+    published: Option<NaiveDateTime>,
+) -> Result<DataFrame> {
+    let valid = column_utc(df.column(valid_col)?)?;
+    let valid: Vec<NaiveDateTime> = valid
+        .into_iter()
+        .map(|t| t.ok_or_else(|| anyhow!("null timestamp in {valid_col}")))
+        .collect::<Result<_>>()?;
+    let known: Vec<NaiveDateTime> = valid
+        .iter()
+        .map(|&t| {
+            rule.knowledge_time(t)
+                .map(|k| published.map_or(k, |p| k.min(p)))
+                .ok_or_else(|| anyhow!("no knowledge time for {t}"))
+        })
+        .collect::<Result<_>>()?;
+    let mut out = df.clone();
+    out.with_column(utc_column(VALID_TIME, &valid)?)?;
+    out.with_column(utc_column(KNOWLEDGE_TIME, &known)?)?;
+    Ok(out)
+}
+
+// Day-ahead gate closure for a delivery instant: `gate` local time on the day before its local day.
+pub fn gate_closure(valid: NaiveDateTime, gate: NaiveTime) -> Option<NaiveDateTime> {
+    KnowledgeRule::DayBefore(gate).knowledge_time(valid)
+}
+
+fn bitemporal_times(df: &DataFrame) -> Result<Vec<Option<(NaiveDateTime, NaiveDateTime)>>> {
+    let valid = column_utc(df.column(VALID_TIME)?)?;
+    let known = column_utc(df.column(KNOWLEDGE_TIME)?)?;
+    Ok(valid
+        .into_iter()
+        .zip(known)
+        .map(|(v, k)| Some((v?, k?)))
+        .collect())
+}
+
+// The dataset as it was known at `decision`: rows known by then, keeping the latest version of every
+// (keys, valid_time).
+pub fn snapshot_as_of(df: &DataFrame, decision: NaiveDateTime, keys: &[&str]) -> Result<DataFrame> {
+    let times = bitemporal_times(df)?;
+    let mask: BooleanChunked = times
+        .iter()
+        .map(|t| t.is_some_and(|(_, k)| k <= decision))
+        .collect();
+    let known = df
+        .filter(&mask)?
+        .sort([KNOWLEDGE_TIME], SortMultipleOptions::default())?;
+    let mut subset: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+    subset.push(VALID_TIME.to_string());
+    Ok(known.unique_stable(Some(&subset), UniqueKeepStrategy::Last, None)?)
+}
+
+// Which version of a bitemporal series an as-of join picks for a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AsOfMatch {
+    // The value for the row's own valid time, in its latest version known at the decision time
+    // (forecasts for the delivery hour).
+    Exact,
+    // The value with the latest valid time among those known at the decision time (the last observed
+    // value, e.g. yesterday's gas settlement).
+    Latest,
+}
+
+// Join one bitemporal series `right` (valid_time, knowledge_time and value columns; filter it to one
+// series first) onto `left`, using only what was known at each row's `decision_col` instant.
+// `columns` maps value columns of `right` to output names; values unknown at the decision are null.
+pub fn asof_join(
+    left: &DataFrame,
+    valid_col: &str,
+    decision_col: &str,
+    right: &DataFrame,
+    columns: &[(&str, &str)],
+    mode: AsOfMatch,
+) -> Result<DataFrame> {
+    let valid = column_utc(left.column(valid_col)?)?;
+    let decision = column_utc(left.column(decision_col)?)?;
+    let mut versions: Vec<(NaiveDateTime, NaiveDateTime, usize)> = bitemporal_times(right)?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, t)| t.map(|(v, k)| (v, k, i)))
+        .collect();
+
+    let picked: Vec<Option<IdxSize>> = match mode {
+        AsOfMatch::Exact => {
+            // valid time -> versions sorted by knowledge time
+            let mut by_valid: HashMap<NaiveDateTime, Vec<(NaiveDateTime, usize)>> = HashMap::new();
+            for (v, k, i) in versions {
+                by_valid.entry(v).or_default().push((k, i));
+            }
+            for vs in by_valid.values_mut() {
+                vs.sort();
+            }
+            valid
+                .iter()
+                .zip(decision.iter())
+                .map(|(v, d)| {
+                    let (v, d) = ((*v)?, (*d)?);
+                    let vs = by_valid.get(&v)?;
+                    let n = vs.partition_point(|(k, _)| *k <= d);
+                    n.checked_sub(1).map(|j| vs[j].1 as IdxSize)
+                })
+                .collect()
+        }
+        AsOfMatch::Latest => {
+            // Sweep decisions in order while admitting versions by knowledge time, tracking the
+            // version with the greatest (valid time, knowledge time).
+            versions.sort_by_key(|&(v, k, _)| (k, v));
+            let mut order: Vec<usize> = (0..left.height())
+                .filter(|&i| decision[i].is_some())
+                .collect();
+            order.sort_by_key(|&i| decision[i]);
+            let mut picked: Vec<Option<IdxSize>> = vec![None; left.height()];
+            let mut best: Option<(NaiveDateTime, NaiveDateTime, usize)> = None;
+            let mut next = versions.iter().peekable();
+            for i in order {
+                let d = decision[i].expect("filtered above");
+                while let Some(&&(v, k, r)) = next.peek() {
+                    if k > d {
+                        break;
+                    }
+                    if best.is_none_or(|(bv, bk, _)| (v, k) >= (bv, bk)) {
+                        best = Some((v, k, r));
+                    }
+                    next.next();
+                }
+                picked[i] = best.map(|(_, _, r)| r as IdxSize);
+            }
+            picked
+        }
+    };
+
+    let idx = IdxCa::from_iter(picked);
+    let mut out = left.clone();
+    for (src, name) in columns {
+        let s = right.column(src)?.take(&idx)?.with_name((*name).into());
+        out.with_column(s)?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn publication_only_moves_knowledge_times_earlier() {
+        // 2024-03-05 23:00 UTC is the first hour of March 6 in Amsterdam.
+        let valid = [utc("2024-03-05 23:00"), utc("2024-03-06 12:00")];
+        let df = DataFrame::new(vec![utc_column("start", &valid).unwrap()]).unwrap();
+        let rule = KnowledgeRule::DayBefore(NaiveTime::from_hms_opt(10, 0, 0).unwrap());
+        let known = |published| {
+            let out = with_knowledge_time_by(&df, "start", rule, published).unwrap();
+            column_utc(out.column(KNOWLEDGE_TIME).unwrap())
+                .unwrap()
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .unwrap()
+        };
+        let gate = utc("2024-03-05 09:00");
+        assert_eq!(known(None), vec![gate, gate]);
+        // A backfill creates its documents long after the gate: the rule holds.
+        assert_eq!(known(Some(utc("2025-01-01 00:00"))), vec![gate, gate]);
+        // A forecast fetched before the gate was known when it was fetched.
+        let fetched = utc("2024-03-05 07:30");
+        assert_eq!(known(Some(fetched)), vec![fetched, fetched]);
+    }
+}
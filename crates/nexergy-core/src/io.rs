@@ -1,6 +1,15 @@
-use anyhow::Result;
+use crate::asof::{KNOWLEDGE_TIME, VALID_TIME};
+use crate::manifest::{record_partitions, ColumnSchema, PartitionWrite};
+use crate::time::{align_timestamps, column_dates, column_utc, format_ts};
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDate};
+use polars::functions::concat_df_diagonal;
 use polars::prelude::*;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+// Single data file per partition; merges rewrite it in place.
+pub const PART_FILE: &str = "part-0001.parquet";
 
 pub fn write_parquet<P: AsRef<Path>>(df: &mut DataFrame, path: P) -> Result<()> {
     let mut file = std::fs::File::create(path)?;
@@ -9,3 +18,461 @@ pub fn write_parquet<P: AsRef<Path>>(df: &mut DataFrame, path: P) -> Result<()>
         .finish(df)?;
     Ok(())
 }
+
+// Write to a temporary file next to `path` and rename it into place, so readers never see a
+// half-written file and an interrupted write leaves the previous version intact.
+pub fn write_parquet_atomic<P: AsRef<Path>>(df: &mut DataFrame, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow!("invalid parquet path {}", path.display()))?;
+    let tmp = path.with_file_name(format!(".{name}.{}.tmp", std::process::id()));
+    if let Err(e) = write_parquet(df, &tmp) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+pub fn read_parquet<P: AsRef<Path>>(path: P) -> Result<DataFrame> {
+    Ok(ParquetReader::new(std::fs::File::open(path)?).finish()?)
+}
+
+// Directory layout of the date part of a partition path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateLayout {
+    // dt=YYYY-MM-DD
+    Dt,
+    // year=YYYY/month=MM/day=DD
+    YearMonthDay,
+}
+
+impl DateLayout {
+    fn path(self, date: NaiveDate) -> PathBuf {
+        match self {
+            DateLayout::Dt => PathBuf::from(format!("dt={}", date.format("%Y-%m-%d"))),
+            DateLayout::YearMonthDay => PathBuf::from(format!(
+                "year={:04}/month={:02}/day={:02}",
+                date.year(),
+                date.month(),
+                date.day()
+            )),
+        }
+    }
+}
+
+// How a dataset is laid out on disk: `<root>/<key>=<value>/.../<date dirs>/part-0001.parquet`.
+// `date_column` holds timestamps (Datetime, partitioned by the day in the column's time zone), dates,
+// or strings starting with YYYY-MM-DD. Rows are
+// de-duplicated on `primary_key` when merged into existing partitions, newer rows winning; an empty
+// key means whole-row identity. `source` (URL or input file) is recorded in the dataset manifest.
+#[derive(Debug, Clone)]
+pub struct LakeTable {
+    pub date_column: String,
+    pub layout: DateLayout,
+    pub partition_keys: Vec<String>,
+    pub primary_key: Vec<String>,
+    pub source: Option<String>,
+}
+
+impl LakeTable {
+    pub fn new(date_column: &str) -> Self {
+        Self {
+            date_column: date_column.to_string(),
+            layout: DateLayout::Dt,
+            partition_keys: Vec::new(),
+            primary_key: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn layout(mut self, layout: DateLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    // Extra partition columns, nested outside the date directories in the given order.
+    pub fn partition_by(mut self, keys: &[&str]) -> Self {
+        self.partition_keys = keys.iter().map(|k| k.to_string()).collect();
+        self
+    }
+
+    pub fn primary_key(mut self, cols: &[&str]) -> Self {
+        self.primary_key = cols.iter().map(|k| k.to_string()).collect();
+        self
+    }
+
+    pub fn source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    // Bitemporal dataset (see crate::asof) partitioned by the day of `valid_time`. `keys` identify a
+    // series (e.g. area, psr_type); every (keys, valid_time, knowledge_time) version is kept.
+    pub fn bitemporal(keys: &[&str]) -> Self {
+        let mut pk: Vec<&str> = keys.to_vec();
+        pk.extend([VALID_TIME, KNOWLEDGE_TIME]);
+        Self::new(VALID_TIME).primary_key(&pk)
+    }
+
+    fn partition_dir(&self, root: &Path, key_values: &[String], date: NaiveDate) -> PathBuf {
+        let mut dir = root.to_path_buf();
+        for (k, v) in self.partition_keys.iter().zip(key_values) {
+            dir.push(format!("{k}={v}"));
+        }
+        dir.join(self.layout.path(date))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteReport {
+    pub partitions: usize,
+    pub rows_written: usize,
+    // Existing rows superseded by a new row with the same primary key.
+    pub rows_replaced: usize,
+    // Rows without a parseable date or partition value.
+    pub rows_skipped: usize,
+}
+
+fn cell_string(col: &Column, idx: usize) -> Result<Option<String>> {
+    Ok(match col.get(idx)? {
+        AnyValue::Null => None,
+        AnyValue::String(v) => Some(v.to_string()),
+        AnyValue::StringOwned(ref v) => Some(v.to_string()),
+        av => Some(av.to_string()),
+    })
+}
+
+fn parquet_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|x| x.to_str()) == Some("parquet"))
+        .filter(|p| {
+            !p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with('.'))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+// Merge `new` into the partition directory: existing files are read, combined with the new rows
+// (diagonally, so added columns are null-filled), de-duplicated on the primary key keeping the last
+// occurrence, and written back atomically as a single file. Returns the number of replaced rows.
+fn merge_partition(
+    dir: &Path,
+    new: &DataFrame,
+    table: &LakeTable,
+) -> Result<(usize, PartitionWrite)> {
+    std::fs::create_dir_all(dir)?;
+    let existing_files = parquet_files(dir)?;
+    let mut frames = Vec::with_capacity(existing_files.len() + 1);
+    for f in existing_files.iter() {
+        let mut df = read_parquet(f)?;
+        align_timestamps(&mut df, new.schema())?;
+        frames.push(df);
+    }
+    let existing_rows: usize = frames.iter().map(|f| f.height()).sum();
+    frames.push(new.clone());
+    let combined = if frames.len() == 1 {
+        new.clone()
+    } else {
+        concat_df_diagonal(&frames)?
+    };
+    let subset = (!table.primary_key.is_empty()).then_some(table.primary_key.as_slice());
+    let mut merged = combined.unique_stable(subset, UniqueKeepStrategy::Last, None)?;
+    let replaced = existing_rows + new.height() - merged.height();
+
+    let target = dir.join(PART_FILE);
+    write_parquet_atomic(&mut merged, &target)?;
+    for f in existing_files.iter().filter(|f| **f != target) {
+        std::fs::remove_file(f)?;
+    }
+    let (min_timestamp, max_timestamp) = timestamp_range(&merged, &table.date_column)?;
+    let schema = merged
+        .schema()
+        .iter()
+        .map(|(name, dtype)| ColumnSchema {
+            name: name.to_string(),
+            dtype: dtype.to_string(),
+        })
+        .collect();
+    let write = PartitionWrite {
+        dir: dir.to_path_buf(),
+        file: target,
+        rows: merged.height(),
+        min_timestamp,
+        max_timestamp,
+        schema,
+    };
+    Ok((replaced, write))
+}
+
+fn timestamp_range(df: &DataFrame, column: &str) -> Result<(Option<String>, Option<String>)> {
+    let values = column_utc(df.column(column)?)?;
+    let min = values.iter().flatten().min().copied();
+    let max = values.iter().flatten().max().copied();
+    Ok((min.map(format_ts), max.map(format_ts)))
+}
+
+// Write `df` under `root` partitioned per `table`, merging into partitions that already exist
+// instead of overwriting them, and record the written partitions in `<root>/_manifest.json`.
+pub fn write_partitioned<P: AsRef<Path>>(
+    df: &DataFrame,
+    root: P,
+    table: &LakeTable,
+) -> Result<WriteReport> {
+    let root = root.as_ref();
+    std::fs::create_dir_all(root)?;
+    let mut report = WriteReport::default();
+    if df.height() == 0 {
+        return Ok(report);
+    }
+    for k in table.primary_key.iter() {
+        df.column(k)?;
+    }
+
+    let dates = column_dates(df.column(&table.date_column)?)?;
+    let key_cols: Vec<&Column> = table
+        .partition_keys
+        .iter()
+        .map(|k| df.column(k))
+        .collect::<PolarsResult<_>>()?;
+    let mut parts: BTreeMap<(Vec<String>, NaiveDate), Vec<IdxSize>> = BTreeMap::new();
+    'rows: for (idx, date) in dates.into_iter().enumerate() {
+        let Some(date) = date else {
+            report.rows_skipped += 1;
+            continue;
+        };
+        let mut values = Vec::with_capacity(key_cols.len());
+        for c in key_cols.iter() {
+            match cell_string(c, idx)? {
+                Some(v) if !v.is_empty() && !v.contains(['/', '\\']) => values.push(v),
+                _ => {
+                    report.rows_skipped += 1;
+                    continue 'rows;
+                }
+            }
+        }
+        parts
+            .entry((values, date))
+            .or_default()
+            .push(idx as IdxSize);
+    }
+
+    let mut writes = Vec::with_capacity(parts.len());
+    for ((values, date), indices) in parts.into_iter() {
+        let part = df.take(&IdxCa::from_vec("idx".into(), indices))?;
+        let dir = table.partition_dir(root, &values, date);
+        let (replaced, write) = merge_partition(&dir, &part, table)?;
+        report.rows_replaced += replaced;
+        report.rows_written += part.height();
+        report.partitions += 1;
+        writes.push(write);
+    }
+    record_partitions(root, table, &writes)?;
+    Ok(report)
+}
+
+// Last date of the unbroken run of daily partitions under `dir` (date directories directly below it)
+// holding a Parquet file that starts at `from`; None when `from` has no such partition.
+pub fn partition_run_end(
+    dir: &Path,
+    layout: DateLayout,
+    from: NaiveDate,
+) -> Result<Option<NaiveDate>> {
+    let mut written = BTreeSet::new();
+    for (date, path) in date_dirs(dir, layout)? {
+        if date >= from && !parquet_files(&path)?.is_empty() {
+            written.insert(date);
+        }
+    }
+    let (mut end, mut day) = (None, Some(from));
+    while let Some(d) = day.filter(|d| written.contains(d)) {
+        end = Some(d);
+        day = d.succ_opt();
+    }
+    Ok(end)
+}
+
+fn child_values(dir: &Path, key: &str) -> Result<Vec<(u32, PathBuf)>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let prefix = format!("{key}=");
+    let mut out = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let v = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix(prefix.as_str()))
+            .and_then(|v| v.parse::<u32>().ok());
+        if let Some(v) = v {
+            out.push((v, path));
+        }
+    }
+    Ok(out)
+}
+
+// Date directories directly under `dir` for a layout.
+pub fn date_dirs(dir: &Path, layout: DateLayout) -> Result<Vec<(NaiveDate, PathBuf)>> {
+    let mut out = Vec::new();
+    if !dir.is_dir() {
+        return Ok(out);
+    }
+    match layout {
+        DateLayout::Dt => {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                let date = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_prefix("dt="))
+                    .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+                if let Some(date) = date {
+                    out.push((date, path));
+                }
+            }
+        }
+        DateLayout::YearMonthDay => {
+            for (y, ydir) in child_values(dir, "year")? {
+                for (m, mdir) in child_values(&ydir, "month")? {
+                    for (d, ddir) in child_values(&mdir, "day")? {
+                        if let Some(date) = NaiveDate::from_ymd_opt(y as i32, m, d) {
+                            out.push((date, ddir));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fresh scratch directory per test, removed when dropped.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("nexergy-io-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn table() -> LakeTable {
+        LakeTable::new("date").primary_key(&["date", "station"])
+    }
+
+    fn frame(stations: &[&str], values: &[f64]) -> DataFrame {
+        df!(
+            "date" => vec!["2024-01-01"; stations.len()],
+            "station" => stations,
+            "value" => values,
+        )
+        .unwrap()
+    }
+
+    fn partition(root: &Path) -> PathBuf {
+        root.join("dt=2024-01-01")
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn sorted_by_station(df: DataFrame) -> DataFrame {
+        df.sort(["station"], SortMultipleOptions::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_replaces_rows_with_the_same_key() {
+        let dir = Scratch::new("merge");
+        write_partitioned(&frame(&["a", "b"], &[1.0, 2.0]), &dir.0, &table()).unwrap();
+        let report =
+            write_partitioned(&frame(&["b", "c"], &[20.0, 30.0]), &dir.0, &table()).unwrap();
+        assert_eq!(report.rows_written, 2);
+        assert_eq!(report.rows_replaced, 1);
+
+        let df = sorted_by_station(read_parquet(partition(&dir.0).join(PART_FILE)).unwrap());
+        let stations: Vec<&str> = df
+            .column("station")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        let values: Vec<f64> = df
+            .column("value")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        assert_eq!(stations, vec!["a", "b", "c"]);
+        assert_eq!(values, vec![1.0, 20.0, 30.0]);
+    }
+
+    #[test]
+    fn merge_null_fills_columns_missing_on_either_side() {
+        let dir = Scratch::new("diagonal");
+        write_partitioned(&frame(&["a"], &[1.0]), &dir.0, &table()).unwrap();
+        let mut new = frame(&["b"], &[2.0]);
+        new.with_column(Column::new("extra".into(), [7i64]))
+            .unwrap();
+        new.drop_in_place("value").unwrap();
+        write_partitioned(&new, &dir.0, &table()).unwrap();
+
+        let df = sorted_by_station(read_parquet(partition(&dir.0).join(PART_FILE)).unwrap());
+        assert_eq!(df.height(), 2);
+        let value: Vec<Option<f64>> = df.column("value").unwrap().f64().unwrap().iter().collect();
+        let extra: Vec<Option<i64>> = df.column("extra").unwrap().i64().unwrap().iter().collect();
+        assert_eq!(value, vec![Some(1.0), None]);
+        assert_eq!(extra, vec![None, Some(7)]);
+    }
+
+    #[test]
+    fn merge_rewrites_the_partition_as_one_file_without_leftovers() {
+        let dir = Scratch::new("atomic");
+        let part = partition(&dir.0);
+        std::fs::create_dir_all(&part).unwrap();
+        // A file from an earlier writer is folded into the merged file and removed.
+        write_parquet(&mut frame(&["a"], &[1.0]), part.join("part-0000.parquet")).unwrap();
+        write_partitioned(&frame(&["b"], &[2.0]), &dir.0, &table()).unwrap();
+        assert_eq!(file_names(&part), vec![PART_FILE.to_string()]);
+        assert_eq!(read_parquet(part.join(PART_FILE)).unwrap().height(), 2);
+
+        let target = dir.0.join("single.parquet");
+        write_parquet_atomic(&mut frame(&["a"], &[1.0]), &target).unwrap();
+        write_parquet_atomic(&mut frame(&["a", "b", "c"], &[1.0, 2.0, 3.0]), &target).unwrap();
+        assert_eq!(read_parquet(&target).unwrap().height(), 3);
+        assert!(!file_names(&dir.0).iter().any(|n| n.ends_with(".tmp")));
+    }
+}
@@ -1,2 +1,7 @@
+pub mod asof;
 pub mod io;
+pub mod manifest;
+pub mod quality;
+pub mod scan;
 pub mod schema;
+pub mod time;
@@ -0,0 +1,178 @@
+use crate::io::LakeTable;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "_manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub dtype: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionEntry {
+    pub rows: usize,
+    pub min_timestamp: Option<String>,
+    pub max_timestamp: Option<String>,
+    // SHA-256 of the partition's Parquet file.
+    pub checksum: String,
+    pub source: Option<String>,
+    pub ingested_at: String,
+}
+
+// Catalog of one dataset, kept in `<root>/_manifest.json` and updated by every lake write.
+// Partitions are keyed by their path relative to the dataset root (e.g. `zone=NL/dt=2024-01-01`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub manifest_version: u32,
+    pub dataset: String,
+    pub schema: Vec<ColumnSchema>,
+    pub primary_key: Vec<String>,
+    pub date_column: String,
+    pub partition_keys: Vec<String>,
+    pub layout: String,
+    pub tool_version: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub partitions: BTreeMap<String, PartitionEntry>,
+}
+
+// What the writer knows about a partition it just wrote.
+#[derive(Debug, Clone)]
+pub struct PartitionWrite {
+    pub dir: PathBuf,
+    pub file: PathBuf,
+    pub rows: usize,
+    pub min_timestamp: Option<String>,
+    pub max_timestamp: Option<String>,
+    pub schema: Vec<ColumnSchema>,
+}
+
+impl Manifest {
+    fn new(root: &Path, table: &LakeTable, now: &str) -> Self {
+        Self {
+            manifest_version: MANIFEST_VERSION,
+            dataset: dataset_name(root),
+            schema: Vec::new(),
+            primary_key: table.primary_key.clone(),
+            date_column: table.date_column.clone(),
+            partition_keys: table.partition_keys.clone(),
+            layout: format!("{:?}", table.layout),
+            tool_version: tool_version(),
+            created_at: now.to_string(),
+            updated_at: now.to_string(),
+            partitions: BTreeMap::new(),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.partitions.values().map(|p| p.rows).sum()
+    }
+
+    pub fn min_timestamp(&self) -> Option<&str> {
+        self.partitions
+            .values()
+            .filter_map(|p| p.min_timestamp.as_deref())
+            .min()
+    }
+
+    pub fn max_timestamp(&self) -> Option<&str> {
+        self.partitions
+            .values()
+            .filter_map(|p| p.max_timestamp.as_deref())
+            .max()
+    }
+
+    // Distinct sources recorded across partitions.
+    pub fn sources(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = self
+            .partitions
+            .values()
+            .filter_map(|p| p.source.as_deref())
+            .collect();
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+}
+
+pub fn tool_version() -> String {
+    format!("nexergy {}", env!("CARGO_PKG_VERSION"))
+}
+
+fn dataset_name(root: &Path) -> String {
+    root.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+pub fn manifest_path(root: &Path) -> PathBuf {
+    root.join(MANIFEST_FILE)
+}
+
+pub fn read_manifest(root: &Path) -> Result<Option<Manifest>> {
+    let path = manifest_path(root);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(&path)?;
+    let m = serde_json::from_str(&text)
+        .map_err(|e| anyhow!("invalid manifest {}: {e}", path.display()))?;
+    Ok(Some(m))
+}
+
+fn write_manifest(root: &Path, m: &Manifest) -> Result<()> {
+    let path = manifest_path(root);
+    let tmp = root.join(format!(".{MANIFEST_FILE}.{}.tmp", std::process::id()));
+    std::fs::write(&tmp, serde_json::to_string_pretty(m)?)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+pub fn file_checksum(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+    let digest = Sha256::digest(&bytes);
+    Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+// Record freshly written partitions in the dataset manifest, creating it on first write. The schema
+// is taken from the latest write; the table's primary key and layout replace the recorded ones.
+pub fn record_partitions(root: &Path, table: &LakeTable, writes: &[PartitionWrite]) -> Result<()> {
+    if writes.is_empty() {
+        return Ok(());
+    }
+    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let mut m = read_manifest(root)?.unwrap_or_else(|| Manifest::new(root, table, &now));
+    m.primary_key = table.primary_key.clone();
+    m.date_column = table.date_column.clone();
+    m.partition_keys = table.partition_keys.clone();
+    m.layout = format!("{:?}", table.layout);
+    m.tool_version = tool_version();
+    m.updated_at = now.clone();
+    for w in writes {
+        let rel = w
+            .dir
+            .strip_prefix(root)
+            .map_err(|_| anyhow!("partition {} outside {}", w.dir.display(), root.display()))?;
+        let key = rel.to_string_lossy().replace('\\', "/");
+        m.partitions.insert(
+            key,
+            PartitionEntry {
+                rows: w.rows,
+                min_timestamp: w.min_timestamp.clone(),
+                max_timestamp: w.max_timestamp.clone(),
+                checksum: file_checksum(&w.file)?,
+                source: table.source.clone(),
+                ingested_at: now.clone(),
+            },
+        );
+        m.schema = w.schema.clone();
+    }
+    write_manifest(root, &m)
+}
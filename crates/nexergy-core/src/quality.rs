@@ -0,0 +1,440 @@
+use crate::time::{column_utc, format_ts, utc_to_wall_clock, wall_clock_to_utc, LOCAL_TZ};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone)]
+pub struct QualityOptions {
+    pub timestamp_col: String,
+    pub value_col: String,
+    // Expected spacing of the series.
+    pub step: Duration,
+    // A value is a spike when it is further than `spike_mad` median absolute deviations and at least
+    // `spike_min_abs` away from its local day's median.
+    pub spike_mad: f64,
+    pub spike_min_abs: f64,
+}
+
+impl Default for QualityOptions {
+    fn default() -> Self {
+        Self {
+            timestamp_col: "datetime_utc".to_string(),
+            value_col: "price_eur_mwh".to_string(),
+            step: Duration::hours(1),
+            spike_mad: 10.0,
+            spike_min_abs: 150.0,
+        }
+    }
+}
+
+// Issue counts for one local (Europe/Amsterdam) day. `expected` is the number of steps in the day
+// within the checked range: 23 or 25 hours on DST transition days.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DayQuality {
+    pub date: NaiveDate,
+    pub expected: usize,
+    pub observed: usize,
+    pub missing: usize,
+    pub duplicates: usize,
+    pub out_of_order: usize,
+    pub non_finite: usize,
+    pub spikes: usize,
+    // Day on which clocks change (23 or 25 hours).
+    pub dst_transition: bool,
+    // DST day whose distinct timestamps do not match its length.
+    pub dst_anomaly: bool,
+}
+
+impl DayQuality {
+    pub fn has_issues(&self) -> bool {
+        self.missing + self.duplicates + self.out_of_order + self.non_finite + self.spikes > 0
+            || self.dst_anomaly
+    }
+}
+
+// Run of consecutive missing steps, by UTC timestamp of the first and last missing step.
+#[derive(Debug, Clone, Serialize)]
+pub struct Gap {
+    pub first_missing: String,
+    pub last_missing: String,
+    pub steps: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QualityReport {
+    pub rows: usize,
+    pub first: Option<String>,
+    pub last: Option<String>,
+    pub missing: usize,
+    pub duplicates: usize,
+    pub out_of_order: usize,
+    pub non_finite: usize,
+    // Rows without a parseable timestamp.
+    pub null_timestamps: usize,
+    pub spikes: usize,
+    pub dst_anomalies: usize,
+    pub gaps: Vec<Gap>,
+    pub days: Vec<DayQuality>,
+}
+
+// Upper bounds on issue counts; `None` means unchecked.
+#[derive(Debug, Clone, Default)]
+pub struct QualityThresholds {
+    pub max_missing: Option<usize>,
+    pub max_duplicates: Option<usize>,
+    pub max_out_of_order: Option<usize>,
+    pub max_non_finite: Option<usize>,
+    pub max_null_timestamps: Option<usize>,
+    pub max_spikes: Option<usize>,
+    pub max_dst_anomalies: Option<usize>,
+}
+
+impl QualityThresholds {
+    pub fn is_empty(&self) -> bool {
+        [
+            self.max_missing,
+            self.max_duplicates,
+            self.max_out_of_order,
+            self.max_non_finite,
+            self.max_null_timestamps,
+            self.max_spikes,
+            self.max_dst_anomalies,
+        ]
+        .iter()
+        .all(Option::is_none)
+    }
+}
+
+impl QualityReport {
+    pub fn issue_days(&self) -> impl Iterator<Item = &DayQuality> {
+        self.days.iter().filter(|d| d.has_issues())
+    }
+
+    // Thresholds exceeded by this report, as readable messages.
+    pub fn violations(&self, t: &QualityThresholds) -> Vec<String> {
+        let checks = [
+            ("missing steps", self.missing, t.max_missing),
+            ("duplicates", self.duplicates, t.max_duplicates),
+            ("out-of-order rows", self.out_of_order, t.max_out_of_order),
+            ("non-finite values", self.non_finite, t.max_non_finite),
+            (
+                "rows without a timestamp",
+                self.null_timestamps,
+                t.max_null_timestamps,
+            ),
+            ("spikes", self.spikes, t.max_spikes),
+            ("DST anomalies", self.dst_anomalies, t.max_dst_anomalies),
+        ];
+        checks
+            .iter()
+            .filter_map(|(what, n, max)| match max {
+                Some(max) if n > max => Some(format!("{n} {what} (max {max})")),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn check(&self, t: &QualityThresholds) -> Result<()> {
+        let v = self.violations(t);
+        if v.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "data quality thresholds exceeded: {}",
+                v.join(", ")
+            ))
+        }
+    }
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    Some(if n % 2 == 1 {
+        values[n / 2]
+    } else {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    })
+}
+
+// Number of spikes among the values of one day.
+fn day_spikes(values: &[f64], opts: &QualityOptions) -> usize {
+    let mut v = values.to_vec();
+    let Some(med) = median(&mut v) else {
+        return 0;
+    };
+    let mut dev: Vec<f64> = values.iter().map(|x| (x - med).abs()).collect();
+    let mad = median(&mut dev).unwrap_or(0.0);
+    let limit = (opts.spike_mad * mad).max(opts.spike_min_abs);
+    values.iter().filter(|x| (*x - med).abs() > limit).count()
+}
+
+// Check a time series for missing steps (on the regular grid between its first and last timestamp),
+// duplicate and out-of-order timestamps (in row order), null or non-finite values, spikes relative to
+// the local day, and DST days whose row count does not match their 23 or 25 hours. Days are
+// Europe/Amsterdam calendar days.
+pub fn validate_series(df: &DataFrame, opts: &QualityOptions) -> Result<QualityReport> {
+    let step = opts.step.num_seconds();
+    if step <= 0 {
+        return Err(anyhow!("quality check step must be positive"));
+    }
+    let ts = column_utc(df.column(&opts.timestamp_col)?)?;
+    let values = df
+        .column(&opts.value_col)?
+        .cast(&DataType::Float64)?
+        .f64()?
+        .clone();
+
+    let mut report = QualityReport {
+        rows: df.height(),
+        ..QualityReport::default()
+    };
+    let local_day = |t: NaiveDateTime| utc_to_wall_clock(t, LOCAL_TZ).date();
+    let mut days: BTreeMap<NaiveDate, DayQuality> = BTreeMap::new();
+    let mut seen: BTreeSet<NaiveDateTime> = BTreeSet::new();
+    let mut day_values: BTreeMap<NaiveDate, Vec<f64>> = BTreeMap::new();
+    let mut prev: Option<NaiveDateTime> = None;
+    for (i, t) in ts.iter().enumerate() {
+        let Some(t) = *t else {
+            report.null_timestamps += 1;
+            continue;
+        };
+        let day = days.entry(local_day(t)).or_default();
+        if prev.is_some_and(|p| t < p) {
+            day.out_of_order += 1;
+        }
+        prev = Some(t);
+        if !seen.insert(t) {
+            day.duplicates += 1;
+        }
+        match values.get(i) {
+            Some(v) if v.is_finite() => day_values.entry(local_day(t)).or_default().push(v),
+            _ => day.non_finite += 1,
+        }
+    }
+
+    for (date, vals) in day_values.iter() {
+        let spikes = day_spikes(vals, opts);
+        days.entry(*date).or_default().spikes = spikes;
+    }
+
+    if let (Some(&first), Some(&last)) = (seen.first(), seen.last()) {
+        report.first = Some(format_ts(first));
+        report.last = Some(format_ts(last));
+        let step = Duration::seconds(step);
+        let mut t = first;
+        let mut run: Option<(NaiveDateTime, NaiveDateTime, usize)> = None;
+        while t <= last {
+            let day = days.entry(local_day(t)).or_default();
+            day.expected += 1;
+            if seen.contains(&t) {
+                day.observed += 1;
+                if let Some((a, b, n)) = run.take() {
+                    report.gaps.push(Gap {
+                        first_missing: format_ts(a),
+                        last_missing: format_ts(b),
+                        steps: n,
+                    });
+                }
+            } else {
+                day.missing += 1;
+                run = match run {
+                    Some((a, _, n)) => Some((a, t, n + 1)),
+                    None => Some((t, t, 1)),
+                };
+            }
+            t += step;
+        }
+    }
+
+    let steps_per_day = (86_400 / step) as usize;
+    for (date, day) in days.iter_mut() {
+        day.date = *date;
+        let day_steps = local_day_seconds(*date) / step;
+        day.dst_transition = day_steps as usize != steps_per_day;
+        // Only whole days inside the range are judged; the first and last day may be partial.
+        day.dst_anomaly = day.dst_transition
+            && day.expected == day_steps as usize
+            && day.observed != day.expected;
+    }
+    report.days = days.into_values().collect();
+    for d in report.days.iter() {
+        report.missing += d.missing;
+        report.duplicates += d.duplicates;
+        report.out_of_order += d.out_of_order;
+        report.non_finite += d.non_finite;
+        report.spikes += d.spikes;
+        report.dst_anomalies += usize::from(d.dst_anomaly);
+    }
+    Ok(report)
+}
+
+// Length of a Europe/Amsterdam calendar day in seconds (82800 or 90000 on DST transitions).
+fn local_day_seconds(date: NaiveDate) -> i64 {
+    let start = |d: NaiveDate| {
+        d.and_hms_opt(0, 0, 0)
+            .and_then(|t| wall_clock_to_utc(t, LOCAL_TZ))
+    };
+    match (start(date), date.succ_opt().and_then(start)) {
+        (Some(a), Some(b)) => (b - a).num_seconds(),
+        _ => 86_400,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::utc_column;
+
+    fn utc(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn hours(from: &str, to: &str) -> Vec<NaiveDateTime> {
+        let (mut t, end) = (utc(from), utc(to));
+        let mut out = Vec::new();
+        while t <= end {
+            out.push(t);
+            t += Duration::hours(1);
+        }
+        out
+    }
+
+    fn frame(ts: &[NaiveDateTime], values: &[f64]) -> DataFrame {
+        DataFrame::new(vec![
+            utc_column("datetime_utc", ts).unwrap(),
+            Column::new("price_eur_mwh".into(), values),
+        ])
+        .unwrap()
+    }
+
+    fn day<'a>(report: &'a QualityReport, date: &str) -> &'a DayQuality {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        report.days.iter().find(|d| d.date == date).unwrap()
+    }
+
+    // Local days 2024-03-30 to 2024-04-01 (the middle one has 23 hours) with a two-hour gap, a DST
+    // day missing one hour, a duplicate and two swapped rows.
+    fn one_of_each() -> QualityReport {
+        let mut ts = hours("2024-03-29 23:00", "2024-04-01 21:00");
+        ts.retain(|t| {
+            ![
+                utc("2024-03-30 10:00"),
+                utc("2024-03-30 11:00"),
+                utc("2024-03-31 12:00"),
+            ]
+            .contains(t)
+        });
+        let dup = ts
+            .iter()
+            .position(|t| *t == utc("2024-04-01 05:00"))
+            .unwrap();
+        ts.insert(dup + 1, ts[dup]);
+        let swap = ts
+            .iter()
+            .position(|t| *t == utc("2024-04-01 08:00"))
+            .unwrap();
+        ts.swap(swap, swap + 1);
+        let values: Vec<f64> = (0..ts.len()).map(|i| 50.0 + (i % 5) as f64).collect();
+        validate_series(&frame(&ts, &values), &QualityOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn each_issue_is_counted_once_on_its_local_day() {
+        let report = one_of_each();
+        assert_eq!(report.missing, 3);
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.out_of_order, 1);
+        assert_eq!(report.dst_anomalies, 1);
+        assert_eq!((report.non_finite, report.spikes), (0, 0));
+
+        let gap_day = day(&report, "2024-03-30");
+        assert_eq!((gap_day.expected, gap_day.missing), (24, 2));
+        assert!(!gap_day.dst_transition && !gap_day.dst_anomaly);
+
+        let dst_day = day(&report, "2024-03-31");
+        assert_eq!((dst_day.expected, dst_day.observed), (23, 22));
+        assert!(dst_day.dst_transition && dst_day.dst_anomaly);
+
+        let messy_day = day(&report, "2024-04-01");
+        assert_eq!((messy_day.duplicates, messy_day.out_of_order), (1, 1));
+        assert_eq!(messy_day.missing, 0);
+        assert_eq!(report.issue_days().count(), 3);
+    }
+
+    #[test]
+    fn gaps_are_runs_of_missing_steps() {
+        let gaps = one_of_each().gaps;
+        let runs: Vec<(&str, &str, usize)> = gaps
+            .iter()
+            .map(|g| (g.first_missing.as_str(), g.last_missing.as_str(), g.steps))
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                ("2024-03-30 10:00:00", "2024-03-30 11:00:00", 2),
+                ("2024-03-31 12:00:00", "2024-03-31 12:00:00", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn complete_dst_days_are_transitions_but_not_anomalies() {
+        // Local 2024-10-27 has 25 hours: 2024-10-26 22:00 to 2024-10-27 22:00 UTC.
+        let ts = hours("2024-10-26 22:00", "2024-10-27 22:00");
+        let mut values = vec![60.0; ts.len()];
+        values[3] = 2000.0;
+        values[7] = f64::NAN;
+        let report = validate_series(&frame(&ts, &values), &QualityOptions::default()).unwrap();
+        let d = day(&report, "2024-10-27");
+        assert_eq!((d.expected, d.observed), (25, 25));
+        assert!(d.dst_transition && !d.dst_anomaly);
+        assert_eq!((report.spikes, report.non_finite), (1, 1));
+        assert_eq!(report.dst_anomalies, 0);
+    }
+
+    #[test]
+    fn violations_name_every_exceeded_threshold() {
+        let report = one_of_each();
+        let thresholds = QualityThresholds {
+            max_missing: Some(2),
+            max_duplicates: Some(1),
+            max_dst_anomalies: Some(0),
+            ..QualityThresholds::default()
+        };
+        assert_eq!(
+            report.violations(&thresholds),
+            vec!["3 missing steps (max 2)", "1 DST anomalies (max 0)"]
+        );
+        assert!(report.check(&thresholds).is_err());
+        assert!(report.check(&QualityThresholds::default()).is_ok());
+    }
+
+    #[test]
+    fn null_timestamps_are_not_counted_as_non_finite_values() {
+        let report = QualityReport {
+            non_finite: 1,
+            null_timestamps: 2,
+            ..QualityReport::default()
+        };
+        let thresholds = |non_finite, null_timestamps| QualityThresholds {
+            max_non_finite: Some(non_finite),
+            max_null_timestamps: Some(null_timestamps),
+            ..QualityThresholds::default()
+        };
+        assert!(report.violations(&thresholds(1, 2)).is_empty());
+        assert_eq!(
+            report.violations(&thresholds(0, 1)),
+            vec![
+                "1 non-finite values (max 0)",
+                "2 rows without a timestamp (max 1)"
+            ]
+        );
+        assert!(!thresholds(0, 0).is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+use crate::time::align_timestamps;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use polars::functions::concat_df_diagonal;
+use polars::io::HiveOptions;
+use polars::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// What to read from a partitioned dataset. Date bounds prune `dt=` and `year=/month=/day=`
+// directories, `partitions` prunes other `key=value` directories (e.g. zone=NL), and `columns` is
+// pushed into the Parquet reader.
+#[derive(Debug, Clone, Default)]
+pub struct LakeScan {
+    // First date to read (inclusive).
+    pub from: Option<NaiveDate>,
+    // Last date to read (exclusive).
+    pub to: Option<NaiveDate>,
+    pub columns: Option<Vec<String>>,
+    pub partitions: BTreeMap<String, Vec<String>>,
+}
+
+impl LakeScan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn between(mut self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    pub fn columns(mut self, cols: &[&str]) -> Self {
+        self.columns = Some(cols.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    // Only read `key=<value>` directories whose value is one of `values`.
+    pub fn partition_in(mut self, key: &str, values: &[&str]) -> Self {
+        self.partitions.insert(
+            key.to_string(),
+            values.iter().map(|v| v.to_string()).collect(),
+        );
+        self
+    }
+
+    // Whether any date in [first, last] lies within the requested range.
+    fn overlaps(&self, first: NaiveDate, last: NaiveDate) -> bool {
+        self.from.is_none_or(|f| last >= f) && self.to.is_none_or(|t| first < t)
+    }
+
+    // Directory-level pruning. `ym` carries year (and month) from enclosing year=/month= directories.
+    fn keep_dir(&self, key: &str, value: &str, ym: &mut (Option<i32>, Option<u32>)) -> bool {
+        match key {
+            "dt" => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                Ok(d) => self.overlaps(d, d),
+                Err(_) => true,
+            },
+            "year" => {
+                let Ok(y) = value.parse::<i32>() else {
+                    return true;
+                };
+                ym.0 = Some(y);
+                match (
+                    NaiveDate::from_ymd_opt(y, 1, 1),
+                    NaiveDate::from_ymd_opt(y, 12, 31),
+                ) {
+                    (Some(a), Some(b)) => self.overlaps(a, b),
+                    _ => true,
+                }
+            }
+            "month" => {
+                let (Some(y), Ok(m)) = (ym.0, value.parse::<u32>()) else {
+                    return true;
+                };
+                ym.1 = Some(m);
+                match month_bounds(y, m) {
+                    Some((a, b)) => self.overlaps(a, b),
+                    None => true,
+                }
+            }
+            "day" => {
+                let (Some(y), Some(m), Ok(d)) = (ym.0, ym.1, value.parse::<u32>()) else {
+                    return true;
+                };
+                match NaiveDate::from_ymd_opt(y, m, d) {
+                    Some(date) => self.overlaps(date, date),
+                    None => true,
+                }
+            }
+            other => self
+                .partitions
+                .get(other)
+                .is_none_or(|allowed| allowed.iter().any(|v| v == value)),
+        }
+    }
+}
+
+fn month_bounds(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((first, next.pred_opt()?))
+}
+
+fn is_data_file(path: &Path) -> bool {
+    path.extension().and_then(|x| x.to_str()) == Some("parquet")
+        && !path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'))
+}
+
+fn collect_files(
+    dir: &Path,
+    scan: &LakeScan,
+    ym: (Option<i32>, Option<u32>),
+    out: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            let mut ym = ym;
+            if let Some((k, v)) = name.split_once('=') {
+                if !scan.keep_dir(k, v, &mut ym) {
+                    continue;
+                }
+            }
+            collect_files(&path, scan, ym, out)?;
+        } else if is_data_file(&path) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Parquet files under `root` that survive partition pruning, in path order.
+pub fn partition_files(root: &Path, scan: &LakeScan) -> Result<Vec<PathBuf>> {
+    if !root.is_dir() {
+        return Err(anyhow!("dataset directory {} not found", root.display()));
+    }
+    let mut out = Vec::new();
+    collect_files(root, scan, (None, None), &mut out)?;
+    Ok(out)
+}
+
+// Column names and dtypes of a file, used to group files that can share one scan.
+type SchemaKey = Vec<(String, String)>;
+
+type FileSchema = Vec<(String, DataType)>;
+
+fn file_schema(path: &Path) -> Result<FileSchema> {
+    let mut reader = ParquetReader::new(std::fs::File::open(path)?);
+    let schema = reader.schema()?;
+    Ok(schema
+        .iter_values()
+        .map(|f| (f.name.to_string(), DataType::from_arrow_field(f)))
+        .collect())
+}
+
+// Eagerly read files whose timestamp columns are still strings, converted to the typed schema.
+fn read_aligned(paths: &[PathBuf], names: &[&str], temporal: &Schema) -> Result<DataFrame> {
+    let mut frames = Vec::with_capacity(paths.len());
+    for p in paths {
+        let columns: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        let mut df = ParquetReader::new(std::fs::File::open(p)?)
+            .with_columns(Some(columns))
+            .finish()?;
+        align_timestamps(&mut df, temporal)?;
+        frames.push(df);
+    }
+    Ok(concat_df_diagonal(&frames)?)
+}
+
+// Lazily scan a partitioned dataset, keeping path (partition) order. Consecutive files with the same
+// schema are grouped; each group becomes one Parquet scan
+// with the requested columns pushed down, and the groups are concatenated diagonally so partitions
+// written before a column was added read back with nulls in it. Timestamp columns written as strings by
+// older versions are converted to the Datetime type of newer partitions.
+pub fn scan_lake(root: &Path, scan: &LakeScan) -> Result<LazyFrame> {
+    let files = partition_files(root, scan)?;
+    if files.is_empty() {
+        return Err(anyhow!(
+            "no parquet files found under {} for the requested range",
+            root.display()
+        ));
+    }
+    let mut groups: Vec<(SchemaKey, FileSchema, Vec<PathBuf>)> = Vec::new();
+    for f in files.iter() {
+        let schema = file_schema(f)?;
+        let key: SchemaKey = schema
+            .iter()
+            .map(|(n, t)| (n.clone(), t.to_string()))
+            .collect();
+        match groups.last_mut() {
+            Some((k, _, paths)) if *k == key => paths.push(f.clone()),
+            _ => groups.push((key, schema, vec![f.clone()])),
+        }
+    }
+    // Timestamp types of the newest writes; partitions that still hold these columns as strings are
+    // converted when read.
+    let mut temporal = Schema::default();
+    for (_, schema, _) in groups.iter() {
+        for (name, dtype) in schema.iter() {
+            if matches!(dtype, DataType::Datetime(_, _) | DataType::Date) {
+                temporal.with_column(name.as_str().into(), dtype.clone());
+            }
+        }
+    }
+
+    let wanted: Option<BTreeSet<&str>> = scan
+        .columns
+        .as_ref()
+        .map(|c| c.iter().map(String::as_str).collect());
+    let mut frames = Vec::with_capacity(groups.len());
+    // Partition columns are stored in the files themselves; directory pruning is done above.
+    let args = ScanArgsParquet {
+        hive_options: HiveOptions::new_disabled(),
+        ..ScanArgsParquet::default()
+    };
+    for (_, schema, paths) in groups.into_iter() {
+        let names: Vec<&str> = schema
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .filter(|n| wanted.as_ref().is_none_or(|w| w.contains(n)))
+            .collect();
+        let legacy = schema.iter().any(|(n, t)| {
+            t == &DataType::String && temporal.contains(n) && names.contains(&n.as_str())
+        });
+        if legacy {
+            frames.push(read_aligned(&paths, &names, &temporal)?.lazy());
+            continue;
+        }
+        let paths: Vec<PlPath> = paths
+            .iter()
+            .map(|p| PlPath::Local(Arc::from(p.as_path())))
+            .collect();
+        let lf = LazyFrame::scan_parquet_files(paths.into(), args.clone())?;
+        let lf = match &wanted {
+            Some(_) => lf.select(names.iter().map(|n| col(*n)).collect::<Vec<_>>()),
+            None => lf,
+        };
+        frames.push(lf);
+    }
+    let lf = if frames.len() == 1 {
+        frames.remove(0)
+    } else {
+        concat_lf_diagonal(frames, UnionArgs::default())?
+    };
+    // Keep the requested column order.
+    Ok(match &scan.columns {
+        Some(cols) => {
+            let present = lf.clone().collect_schema()?;
+            let exprs: Vec<Expr> = cols
+                .iter()
+                .filter(|c| present.contains(c.as_str()))
+                .map(|c| col(c.as_str()))
+                .collect();
+            lf.select(exprs)
+        }
+        None => lf,
+    })
+}
+
+pub fn read_lake(root: &Path, scan: &LakeScan) -> Result<DataFrame> {
+    Ok(scan_lake(root, scan)?.collect()?)
+}
@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Offset, TimeZone as _};
+use chrono_tz::Tz;
+use polars::prelude::*;
+
+// Timestamps are stored as Datetime(µs) columns holding UTC instants: `*_utc` columns carry the UTC
+// time zone and `*_local` columns the market time zone. The time zone only changes how an instant is
+// displayed and which calendar day it falls on.
+pub const LOCAL_TZ: Tz = chrono_tz::Europe::Amsterdam;
+pub const LOCAL_TZ_NAME: &str = "Europe/Amsterdam";
+// Text form of a timestamp in manifests and of string timestamps in partitions written before
+// timestamps were typed.
+pub const TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub fn datetime_dtype(tz: &str) -> Result<DataType> {
+    Ok(DataType::Datetime(
+        TimeUnit::Microseconds,
+        TimeZone::opt_try_new(Some(tz))?,
+    ))
+}
+
+pub fn utc_dtype() -> DataType {
+    DataType::Datetime(TimeUnit::Microseconds, Some(TimeZone::UTC))
+}
+
+pub fn local_dtype() -> Result<DataType> {
+    datetime_dtype(LOCAL_TZ_NAME)
+}
+
+// Datetime(µs) column of UTC instants, displayed in `tz`.
+pub fn datetime_column<I>(name: &str, values: I, tz: &str) -> Result<Column>
+where
+    I: IntoIterator<Item = Option<NaiveDateTime>>,
+{
+    let DataType::Datetime(unit, tz) = datetime_dtype(tz)? else {
+        unreachable!()
+    };
+    let micros: Int64Chunked = values
+        .into_iter()
+        .map(|v| v.map(|t| t.and_utc().timestamp_micros()))
+        .collect();
+    Ok(micros
+        .with_name(name.into())
+        .into_datetime(unit, tz)
+        .into_column())
+}
+
+pub fn utc_column(name: &str, values: &[NaiveDateTime]) -> Result<Column> {
+    datetime_column(name, values.iter().copied().map(Some), "UTC")
+}
+
+// The same UTC instants as `utc_column`, displayed in Europe/Amsterdam.
+pub fn local_column(name: &str, values: &[NaiveDateTime]) -> Result<Column> {
+    datetime_column(name, values.iter().copied().map(Some), LOCAL_TZ_NAME)
+}
+
+// Parse a timestamp written as `YYYY-MM-DD HH:MM[:SS]`, with `T` as separator or a trailing `Z`, or a
+// bare date (midnight).
+pub fn parse_timestamp(s: &str) -> Option<NaiveDateTime> {
+    let s = s.trim().trim_end_matches('Z');
+    for fmt in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, fmt) {
+            return Some(t);
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+}
+
+// UTC instant of a wall-clock time in `tz`. Ambiguous times (the repeated hour when DST ends) resolve
+// to the first occurrence; times skipped when DST starts have no instant.
+pub fn wall_clock_to_utc(t: NaiveDateTime, tz: Tz) -> Option<NaiveDateTime> {
+    tz.from_local_datetime(&t).earliest().map(|d| d.naive_utc())
+}
+
+pub fn utc_to_wall_clock(t: NaiveDateTime, tz: Tz) -> NaiveDateTime {
+    t + chrono::Duration::seconds(i64::from(
+        tz.offset_from_utc_datetime(&t).fix().local_minus_utc(),
+    ))
+}
+
+fn parse_tz(tz: Option<&TimeZone>) -> Result<Tz> {
+    match tz {
+        None => Ok(Tz::UTC),
+        Some(tz) => tz
+            .as_str()
+            .parse::<Tz>()
+            .map_err(|e| anyhow!("unknown time zone {tz}: {e}")),
+    }
+}
+
+// Time zone a column's values are displayed in: its Datetime time zone, UTC otherwise.
+pub fn column_tz(col: &Column) -> Result<Tz> {
+    match col.dtype() {
+        DataType::Datetime(_, tz) => parse_tz(tz.as_ref()),
+        _ => Ok(Tz::UTC),
+    }
+}
+
+fn from_epoch(v: i64, unit: TimeUnit) -> Option<NaiveDateTime> {
+    let t = match unit {
+        TimeUnit::Nanoseconds => Some(DateTime::from_timestamp_nanos(v)),
+        TimeUnit::Microseconds => DateTime::from_timestamp_micros(v),
+        TimeUnit::Milliseconds => DateTime::from_timestamp_millis(v),
+    };
+    t.map(|t| t.naive_utc())
+}
+
+// UTC instants of a timestamp column. Datetime columns are read as stored, Date columns as midnight
+// UTC, and string columns are parsed as wall-clock times in `string_tz`.
+pub fn column_utc_with(col: &Column, string_tz: Tz) -> Result<Vec<Option<NaiveDateTime>>> {
+    Ok(match col.dtype() {
+        DataType::Datetime(unit, _) => {
+            let unit = *unit;
+            col.datetime()?
+                .physical()
+                .into_iter()
+                .map(|v| v.and_then(|v| from_epoch(v, unit)))
+                .collect()
+        }
+        DataType::Date => col
+            .date()?
+            .physical()
+            .into_iter()
+            .map(|v| {
+                v.and_then(|d| {
+                    DateTime::from_timestamp(i64::from(d) * 86_400, 0).map(|t| t.naive_utc())
+                })
+            })
+            .collect(),
+        DataType::String => col
+            .str()?
+            .into_iter()
+            .map(|v| {
+                v.and_then(parse_timestamp)
+                    .and_then(|t| wall_clock_to_utc(t, string_tz))
+            })
+            .collect(),
+        other => return Err(anyhow!("column {} is {other}, not a timestamp", col.name())),
+    })
+}
+
+// UTC instants of a timestamp column; string values are taken to be UTC.
+pub fn column_utc(col: &Column) -> Result<Vec<Option<NaiveDateTime>>> {
+    column_utc_with(col, Tz::UTC)
+}
+
+// Calendar date of every value in the column's own time zone (a local column's rows fall on local
+// days). String values are dated by their text.
+pub fn column_dates(col: &Column) -> Result<Vec<Option<NaiveDate>>> {
+    let tz = column_tz(col)?;
+    Ok(column_utc(col)?
+        .into_iter()
+        .map(|t| t.map(|t| utc_to_wall_clock(t, tz).date()))
+        .collect())
+}
+
+// Convert a string timestamp column to `dtype` (Datetime or Date), reading the strings as wall-clock
+// times in the target time zone. Other columns are returned unchanged.
+pub fn to_timestamp_column(col: &Column, dtype: &DataType) -> Result<Column> {
+    if col.dtype() != &DataType::String {
+        return Ok(col.clone());
+    }
+    match dtype {
+        DataType::Datetime(_, tz) => {
+            let tz = parse_tz(tz.as_ref())?;
+            let values = column_utc_with(col, tz)?;
+            let out = datetime_column(col.name(), values, tz.name())?;
+            Ok(out.cast(dtype)?)
+        }
+        DataType::Date => Ok(col.cast(&DataType::Date)?),
+        _ => Ok(col.clone()),
+    }
+}
+
+// Bring string timestamp columns of `df` (partitions written before timestamps were typed) to the
+// Datetime/Date types `schema` has for them, so the two can be concatenated.
+pub fn align_timestamps(df: &mut DataFrame, schema: &Schema) -> Result<()> {
+    let mut replaced = Vec::new();
+    for col in df.get_columns() {
+        if let Some(dtype) = schema.get(col.name()) {
+            if col.dtype() == &DataType::String
+                && matches!(dtype, DataType::Datetime(_, _) | DataType::Date)
+            {
+                replaced.push(to_timestamp_column(col, dtype)?);
+            }
+        }
+    }
+    for col in replaced {
+        df.with_column(col)?;
+    }
+    Ok(())
+}
+
+pub fn format_ts(t: NaiveDateTime) -> String {
+    t.format(TS_FORMAT).to_string()
+}
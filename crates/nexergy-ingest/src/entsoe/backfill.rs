@@ -0,0 +1,416 @@
+use super::response::get_documents;
+use super::{build_client, timeseries_lake_table, EntsoeError, EntsoeQuery};
+use anyhow::Result;
+use chrono::{Months, NaiveDate, NaiveDateTime, NaiveTime};
+use nexergy_core::io::{partition_run_end, write_partitioned, DateLayout, LakeTable};
+use polars::prelude::DataFrame;
+use reqwest::blocking::Client;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Exponential backoff with equal jitter: half the capped delay plus a random share of the other half.
+    fn delay(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(1u32 << attempt.min(16));
+        let half = exp.min(self.max_delay) / 2;
+        half + half.mul_f64(jitter())
+    }
+}
+
+// Cheap randomness in [0, 1) from the clock; enough to de-synchronise retries.
+fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let mut x = u64::from(nanos) ^ 0x9E37_79B9_7F4A_7C15;
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    x ^= x >> 33;
+    (x % 1_000_000) as f64 / 1_000_000.0
+}
+
+// Called with the error and the wait before every retry, e.g. to log it.
+type OnRetry = Box<dyn FnMut(&EntsoeError, Duration)>;
+
+// ENTSO-E client with retries and a request budget (per minute and, optionally, per run).
+pub struct EntsoeClient {
+    client: Client,
+    base_url: String,
+    security_token: String,
+    retry: RetryPolicy,
+    requests_per_minute: u32,
+    max_requests: Option<usize>,
+    sent: VecDeque<Instant>,
+    requests: usize,
+    retries: usize,
+    on_retry: Option<OnRetry>,
+}
+
+impl EntsoeClient {
+    pub fn new(base_url: &str, security_token: &str) -> std::result::Result<Self, EntsoeError> {
+        Ok(Self {
+            client: build_client()?,
+            base_url: base_url.to_string(),
+            security_token: security_token.to_string(),
+            retry: RetryPolicy::default(),
+            // ENTSO-E throttles at roughly 400 requests per minute; stay well below.
+            requests_per_minute: 300,
+            max_requests: None,
+            sent: VecDeque::new(),
+            requests: 0,
+            retries: 0,
+            on_retry: None,
+        })
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_requests_per_minute(mut self, n: u32) -> Self {
+        self.requests_per_minute = n.max(1);
+        self
+    }
+
+    // Cap on the total number of requests this client may issue (checked between windows).
+    pub fn with_max_requests(mut self, n: Option<usize>) -> Self {
+        self.max_requests = n;
+        self
+    }
+
+    pub fn with_on_retry(mut self, f: impl FnMut(&EntsoeError, Duration) + 'static) -> Self {
+        self.on_retry = Some(Box::new(f));
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn requests_made(&self) -> usize {
+        self.requests
+    }
+
+    pub fn retries_made(&self) -> usize {
+        self.retries
+    }
+
+    pub fn budget_exhausted(&self) -> bool {
+        self.max_requests.is_some_and(|m| self.requests >= m)
+    }
+
+    // Block until another request fits in the per-minute budget.
+    fn throttle(&mut self) {
+        let window = Duration::from_secs(60);
+        loop {
+            let now = Instant::now();
+            while self
+                .sent
+                .front()
+                .is_some_and(|t| now.duration_since(*t) >= window)
+            {
+                self.sent.pop_front();
+            }
+            match self.sent.front() {
+                Some(oldest) if self.sent.len() as u32 >= self.requests_per_minute => {
+                    std::thread::sleep(window - now.duration_since(*oldest));
+                }
+                _ => break,
+            }
+        }
+        self.sent.push_back(Instant::now());
+        self.requests += 1;
+    }
+
+    // Fetch the documents of a query (several when ENTSO-E returns a ZIP archive), retrying
+    // rate-limited and server errors with exponential backoff and jitter.
+    pub fn fetch(&mut self, query: &EntsoeQuery) -> std::result::Result<Vec<String>, EntsoeError> {
+        let mut params = query.to_params();
+        params.insert("securityToken", self.security_token.clone());
+        let mut attempt = 0;
+        loop {
+            self.throttle();
+            match get_documents(&self.client, &self.base_url, &params) {
+                Err(e) if e.is_retryable() && attempt < self.retry.max_retries => {
+                    let wait = self.retry.delay(attempt);
+                    if let Some(on_retry) = self.on_retry.as_mut() {
+                        on_retry(&e, wait);
+                    }
+                    std::thread::sleep(wait);
+                    attempt += 1;
+                    self.retries += 1;
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+// Split [start, end) into consecutive windows of at most `months` calendar months.
+pub fn split_windows(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    months: u32,
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut out = Vec::new();
+    let mut cur = start;
+    while cur < end {
+        let next = cur
+            .checked_add_months(Months::new(months.max(1)))
+            .unwrap_or(end)
+            .min(end);
+        out.push((cur, next));
+        cur = next;
+    }
+    out
+}
+
+// Last day of the partitions under `out_dir` written without a gap from `from`, the first day of a
+// query period: the windows before it completed (its own may be partial). Partitions after a gap,
+// e.g. from another query into the same dataset, say nothing about the days before them.
+pub fn last_written_date(
+    out_dir: &Path,
+    layout: DateLayout,
+    from: NaiveDate,
+) -> Result<Option<NaiveDate>> {
+    partition_run_end(out_dir, layout, from)
+}
+
+// Day to resume a query period [start, end) from: the last day written without a gap from its
+// start, when that lies after the start and before the end.
+fn resume_date(
+    dir: &Path,
+    layout: DateLayout,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<Option<NaiveDate>> {
+    Ok(last_written_date(dir, layout, start.date())?.filter(|d| {
+        let t = d.and_time(NaiveTime::MIN);
+        t > start && t < end
+    }))
+}
+
+#[derive(Debug, Clone)]
+pub struct BackfillOptions {
+    // Maximum window per request; ENTSO-E allows at most one year for most document types.
+    pub window_months: u32,
+    // Restart from the last partition written without a gap from the query start (see
+    // last_written_date) instead of the query start.
+    pub resume: bool,
+    // Directory scanned for written partitions, when several queries share one dataset (e.g.
+    // `<out_dir>/zone=NL`); defaults to the output directory.
+    pub resume_dir: Option<PathBuf>,
+    // Layout and primary key of the output dataset; partitions are merged, not overwritten.
+    pub table: LakeTable,
+}
+
+impl Default for BackfillOptions {
+    fn default() -> Self {
+        Self {
+            window_months: 12,
+            resume: true,
+            resume_dir: None,
+            table: timeseries_lake_table(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BackfillReport {
+    pub resumed_from: Option<NaiveDate>,
+    pub windows: usize,
+    pub no_data_windows: usize,
+    pub rows: usize,
+    pub requests: usize,
+    // Requests retried after a rate limit or server error.
+    pub retries: usize,
+    pub budget_exhausted: bool,
+}
+
+fn parse_all<F>(docs: &[String], parse: &mut F) -> Result<DataFrame>
+where
+    F: FnMut(&str) -> Result<DataFrame>,
+{
+    let mut acc: Option<DataFrame> = None;
+    for xml in docs {
+        let df = parse(xml)?;
+        match acc.as_mut() {
+            Some(a) => {
+                a.vstack_mut(&df)?;
+            }
+            None => acc = Some(df),
+        }
+    }
+    Ok(acc.unwrap_or_default())
+}
+
+// Pull `query` over its full period window by window, writing each window's rows as soon as it is
+// parsed so an interrupted run can resume from the last partition written without a gap from the
+// period start (otherwise the whole period is pulled). Windows without data are skipped; windows
+// rejected for having too many points are halved and retried. `parse` is applied to every document
+// of a response.
+pub fn backfill<F>(
+    client: &mut EntsoeClient,
+    query: &EntsoeQuery,
+    out_dir: &Path,
+    opts: &BackfillOptions,
+    mut parse: F,
+) -> Result<BackfillReport>
+where
+    F: FnMut(&str) -> Result<DataFrame>,
+{
+    let mut report = BackfillReport::default();
+    let mut start = query.period_start;
+    if opts.resume {
+        let resume_dir = opts.resume_dir.as_deref().unwrap_or(out_dir);
+        let resume = resume_date(resume_dir, opts.table.layout, start, query.period_end)?;
+        if let Some(d) = resume {
+            start = d.and_time(NaiveTime::MIN);
+            report.resumed_from = Some(d);
+        }
+    }
+
+    let mut table = opts.table.clone();
+    if table.source.is_none() {
+        table.source = Some(query.source_url(client.base_url()));
+    }
+
+    let mut pending: VecDeque<(NaiveDateTime, NaiveDateTime)> =
+        split_windows(start, query.period_end, opts.window_months).into();
+    while let Some((ws, we)) = pending.pop_front() {
+        if client.budget_exhausted() {
+            report.budget_exhausted = true;
+            break;
+        }
+        match client.fetch(&query.with_period(ws, we)) {
+            Ok(docs) => {
+                let df = parse_all(&docs, &mut parse)?;
+                write_partitioned(&df, out_dir, &table)?;
+                report.rows += df.height();
+                report.windows += 1;
+            }
+            Err(e) if e.is_no_data() => report.no_data_windows += 1,
+            Err(EntsoeError::TooManyPoints { .. }) if we - ws > chrono::Duration::days(1) => {
+                // Split on a midnight so no day partition is written from two windows.
+                let mid = (ws + (we - ws) / 2)
+                    .date()
+                    .and_hms_opt(0, 0, 0)
+                    .filter(|m| *m > ws)
+                    .unwrap_or(ws + chrono::Duration::days(1));
+                pending.push_front((mid, we));
+                pending.push_front((ws, mid));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    report.requests = client.requests_made();
+    report.retries = client.retries_made();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Scratch lake with an (empty) data file in the dt= partition of every given day.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn with_days(name: &str, days: &[&str]) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("nexergy-backfill-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            for day in days {
+                let part = dir.join(format!("dt={day}"));
+                std::fs::create_dir_all(&part).unwrap();
+                std::fs::write(part.join("part-0001.parquet"), b"").unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn at(day: &str) -> NaiveDateTime {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .unwrap()
+            .and_time(NaiveTime::MIN)
+    }
+
+    #[test]
+    fn resumes_from_the_end_of_the_run_written_from_the_period_start() {
+        // An interrupted pull of January, and later data written by another period's backfill.
+        let lake = Scratch::with_days(
+            "run",
+            &[
+                "2024-01-01",
+                "2024-01-02",
+                "2024-01-03",
+                "2024-06-01",
+                "2024-06-02",
+            ],
+        );
+        let resume = |from, to| resume_date(&lake.0, DateLayout::Dt, at(from), at(to)).unwrap();
+        assert_eq!(
+            resume("2024-01-01", "2025-01-01"),
+            Some(at("2024-01-03").date())
+        );
+        // Nothing written at the start of February: pull it all, despite the June partitions.
+        assert_eq!(resume("2024-02-01", "2025-01-01"), None);
+        assert_eq!(
+            resume("2024-06-01", "2024-07-01"),
+            Some(at("2024-06-02").date())
+        );
+        // A run reaching the period end or ending on its first day leaves nothing to skip.
+        assert_eq!(resume("2024-01-01", "2024-01-03"), None);
+        assert_eq!(resume("2024-01-03", "2024-02-01"), None);
+    }
+
+    #[test]
+    fn the_written_run_starts_mid_day_and_ignores_empty_partitions() {
+        let lake = Scratch::with_days("mid-day", &["2023-12-31", "2024-01-01", "2024-01-03"]);
+        std::fs::create_dir_all(lake.0.join("dt=2024-01-02")).unwrap();
+        // A period starting at 23:00 UTC writes its first rows into that day's partition.
+        let start = at("2023-12-31") + chrono::Duration::hours(23);
+        let resumed = resume_date(&lake.0, DateLayout::Dt, start, at("2024-02-01")).unwrap();
+        assert_eq!(resumed, Some(at("2024-01-01").date()));
+    }
+
+    #[test]
+    fn windows_cover_the_period_in_calendar_months() {
+        let windows = split_windows(at("2024-01-31"), at("2024-04-15"), 1);
+        assert_eq!(
+            windows,
+            vec![
+                (at("2024-01-31"), at("2024-02-29")),
+                (at("2024-02-29"), at("2024-03-29")),
+                (at("2024-03-29"), at("2024-04-15")),
+            ]
+        );
+        assert!(split_windows(at("2024-01-01"), at("2024-01-01"), 12).is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use thiserror::Error;
+
+// Errors returned by the ENTSO-E Transparency Platform, classified from the HTTP status and,
+// when present, the Reason of an Acknowledgement_MarketDocument.
+#[derive(Debug, Error)]
+pub enum EntsoeError {
+    #[error("ENTSO-E: no data for the requested window (code {code}): {text}")]
+    NoData { code: String, text: String },
+    #[error("ENTSO-E: invalid query parameter (code {code}): {text}")]
+    InvalidParameter { code: String, text: String },
+    #[error(
+        "ENTSO-E: requested window exceeds the allowed number of points (code {code}): {text}"
+    )]
+    TooManyPoints { code: String, text: String },
+    #[error("ENTSO-E: rate limited (HTTP {status})")]
+    RateLimited { status: u16 },
+    #[error("ENTSO-E: authentication failed (HTTP {status}): {text}")]
+    Unauthorized { status: u16, text: String },
+    #[error("ENTSO-E: query rejected, check the security token (code {code}): {text}")]
+    AuthRejected { code: String, text: String },
+    #[error("ENTSO-E HTTP {status}: {text}")]
+    Http { status: u16, text: String },
+    #[error("ENTSO-E request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("ENTSO-E response could not be read: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("ENTSO-E response is not valid UTF-8: {0}")]
+    Encoding(#[from] std::string::FromUtf8Error),
+    #[error("ENTSO-E ZIP response could not be unpacked: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("ENTSO-E returned an archive of {documents} documents where one was expected")]
+    UnexpectedArchive { documents: usize },
+}
+
+impl EntsoeError {
+    // True when the window simply has no published data and can be skipped.
+    pub fn is_no_data(&self) -> bool {
+        matches!(self, EntsoeError::NoData { .. })
+    }
+
+    // True when retrying the same request later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            EntsoeError::RateLimited { .. } => true,
+            EntsoeError::Http { status, .. } => *status >= 500,
+            EntsoeError::Transport(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+// Classify an acknowledgement by its Reason code. Most query errors carry the catch-all code 999
+// (errors not specifically identified); for those and unknown codes the text decides.
+fn classify_reason(code: String, text: String) -> EntsoeError {
+    match code.as_str() {
+        // Data not yet available.
+        "B08" => EntsoeError::NoData { code, text },
+        // Time interval incorrect.
+        "A04" => EntsoeError::InvalidParameter { code, text },
+        // Sender without valid contract.
+        "A05" => EntsoeError::AuthRejected { code, text },
+        _ => classify_reason_text(code, text),
+    }
+}
+
+fn classify_reason_text(code: String, text: String) -> EntsoeError {
+    let lower = text.to_lowercase();
+    if lower.contains("no matching data") || lower.contains("no data") {
+        EntsoeError::NoData { code, text }
+    } else if lower.contains("exceeds the allowed limit")
+        || lower.contains("maximum allowed")
+        || lower.contains("max allowed")
+        || lower.contains("too many")
+    {
+        EntsoeError::TooManyPoints { code, text }
+    } else if lower.contains("unauthorized") || lower.contains("security token") {
+        EntsoeError::AuthRejected { code, text }
+    } else {
+        EntsoeError::InvalidParameter { code, text }
+    }
+}
+
+// Extract Reason/code and Reason/text from an Acknowledgement_MarketDocument.
+// Returns None when the document is not an acknowledgement.
+pub fn parse_acknowledgement(xml: &str) -> Option<EntsoeError> {
+    if !xml.contains("Acknowledgement_MarketDocument") {
+        return None;
+    }
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut path: Vec<String> = Vec::new();
+    let mut code = String::new();
+    let mut texts: Vec<String> = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                path.push(String::from_utf8_lossy(e.local_name().as_ref()).into_owned())
+            }
+            Ok(Event::Text(t)) => {
+                let in_reason = path.len() >= 2 && path[path.len() - 2] == "Reason";
+                let text = t
+                    .unescape()
+                    .map(|c| c.trim().to_string())
+                    .unwrap_or_default();
+                match path.last().map(String::as_str) {
+                    Some("code") if in_reason && code.is_empty() => code = text,
+                    Some("text") if in_reason => texts.push(text),
+                    _ => {}
+                }
+            }
+            Ok(Event::End(_)) => {
+                path.pop();
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    Some(classify_reason(code, texts.join("; ")))
+}
+
+// Map a non-success HTTP response to an error, preferring the acknowledgement reason if the body has one.
+pub(crate) fn from_http_status(status: u16, body: &str) -> EntsoeError {
+    match status {
+        401 | 403 => EntsoeError::Unauthorized {
+            status,
+            text: body.trim().to_string(),
+        },
+        429 => EntsoeError::RateLimited { status },
+        _ => parse_acknowledgement(body).unwrap_or_else(|| EntsoeError::Http {
+            status,
+            text: body.trim().to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ack(code: &str, text: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Acknowledgement_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-1:acknowledgementdocument:7:0">
+  <mRID>ack-1</mRID>
+  <Reason>
+    <code>{code}</code>
+    <text>{text}</text>
+  </Reason>
+</Acknowledgement_MarketDocument>"#
+        )
+    }
+
+    #[test]
+    fn reason_codes_decide_before_the_text() {
+        let classify = |code, text| parse_acknowledgement(&ack(code, text)).unwrap();
+        assert!(classify("B08", "Data not yet available").is_no_data());
+        // The code wins over text that reads like another error.
+        assert!(matches!(
+            classify("A04", "No matching data found"),
+            EntsoeError::InvalidParameter { .. }
+        ));
+        match classify("A05", "Sender without valid contract") {
+            EntsoeError::AuthRejected { code, text } => {
+                assert_eq!(
+                    (code.as_str(), text.as_str()),
+                    ("A05", "Sender without valid contract")
+                )
+            }
+            other => panic!("expected an auth rejection, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn catch_all_reasons_are_told_apart_by_their_text() {
+        let classify = |text| parse_acknowledgement(&ack("999", text)).unwrap();
+        let no_data = classify("No matching data found for Data item Day-ahead Prices [12.1.D]");
+        assert!(no_data.is_no_data());
+        assert!(!no_data.is_retryable());
+        assert!(matches!(
+            classify("The amount of requested data exceeds the allowed limit of 100 documents"),
+            EntsoeError::TooManyPoints { .. }
+        ));
+        assert!(matches!(
+            classify("Invalid security token"),
+            EntsoeError::AuthRejected { .. }
+        ));
+        match classify("Mandatory parameter in_Domain is missing") {
+            EntsoeError::InvalidParameter { code, .. } => assert_eq!(code, "999"),
+            other => panic!("expected an invalid parameter, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn acknowledgements_join_every_reason_text() {
+        let xml = ack("999", "first").replace(
+            "</Reason>",
+            "</Reason><Reason><code>B08</code><text>second</text></Reason>",
+        );
+        match parse_acknowledgement(&xml).unwrap() {
+            EntsoeError::InvalidParameter { code, text } => {
+                assert_eq!((code.as_str(), text.as_str()), ("999", "first; second"))
+            }
+            other => panic!("expected an invalid parameter, got {other:?}"),
+        }
+        assert!(parse_acknowledgement("<Publication_MarketDocument/>").is_none());
+    }
+
+    #[test]
+    fn http_statuses_map_to_retryable_and_final_errors() {
+        for status in [401, 403] {
+            let err = from_http_status(status, " denied ");
+            assert!(!err.is_retryable());
+            match err {
+                EntsoeError::Unauthorized { status: s, text } => {
+                    assert_eq!((s, text.as_str()), (status, "denied"))
+                }
+                other => panic!("expected an auth failure, got {other:?}"),
+            }
+        }
+        let limited = from_http_status(429, "");
+        assert!(matches!(limited, EntsoeError::RateLimited { status: 429 }));
+        assert!(limited.is_retryable());
+        for status in [500, 502, 503] {
+            assert!(from_http_status(status, "busy").is_retryable());
+        }
+        let not_found = from_http_status(404, "not found");
+        assert!(matches!(not_found, EntsoeError::Http { status: 404, .. }));
+        assert!(!not_found.is_retryable());
+        // A 400 with an acknowledgement body is classified by its reason.
+        assert!(from_http_status(400, &ack("999", "No matching data found")).is_no_data());
+    }
+}
@@ -0,0 +1,263 @@
+use super::query::{BiddingZone, DocumentType};
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDateTime, Timelike};
+use nexergy_core::io::LakeTable;
+use nexergy_core::time::{column_utc, utc_column};
+use polars::prelude::*;
+use std::collections::BTreeMap;
+
+// Bidding zones with an interconnector to NL.
+pub const NL_NEIGHBOURS: [&str; 5] = ["BE", "DE-LU", "GB", "NO2", "DK1"];
+
+// Both directions of every border between `zone` and `neighbours`, as (from, to) pairs.
+pub fn border_pairs(
+    zone: BiddingZone,
+    neighbours: &[BiddingZone],
+) -> Vec<(BiddingZone, BiddingZone)> {
+    neighbours
+        .iter()
+        .filter(|n| n.eic != zone.eic)
+        .flat_map(|&n| [(zone, n), (n, zone)])
+        .collect()
+}
+
+// Friendly zone name for an EIC code, falling back to the code itself.
+fn zone_name(eic: Option<&str>) -> Option<String> {
+    eic.map(|e| match BiddingZone::lookup(e) {
+        Some(z) => z.name.to_string(),
+        None => e.to_string(),
+    })
+}
+
+// Border table from the long table of parse_timeseries_xml (optionally resampled) for A11 physical
+// flows, A09 scheduled exchanges or A61 forecasted transfer capacity. One row per direction and
+// interval, keyed by (from_zone, to_zone, start): document_type, from_zone, to_zone, start, end,
+// resolution, quantity_mw. `from_zone` is the out_Domain, `to_zone` the in_Domain.
+pub fn border_flows(df: &DataFrame) -> Result<DataFrame> {
+    let out_domain = df.column("out_domain")?.str()?;
+    let in_domain = df.column("in_domain")?.str()?;
+    let from_zone: Vec<Option<String>> = out_domain.into_iter().map(zone_name).collect();
+    let to_zone: Vec<Option<String>> = in_domain.into_iter().map(zone_name).collect();
+    let out = df!(
+        "document_type" => df.column("document_type")?.as_materialized_series().clone(),
+        "from_zone" => from_zone,
+        "to_zone" => to_zone,
+        "start" => df.column("start")?.as_materialized_series().clone(),
+        "end" => df.column("end")?.as_materialized_series().clone(),
+        "resolution" => df.column("resolution")?.as_materialized_series().clone(),
+        "quantity_mw" => df.column("quantity")?.as_materialized_series().clone(),
+    )?;
+    Ok(out)
+}
+
+// Border tables, partitioned by direction: one row per direction, interval start and resolution.
+pub fn flows_lake_table() -> LakeTable {
+    LakeTable::new("start")
+        .partition_by(&["from_zone", "to_zone"])
+        .primary_key(&[
+            "document_type",
+            "from_zone",
+            "to_zone",
+            "start",
+            "resolution",
+        ])
+}
+
+fn hour_start(t: NaiveDateTime) -> Result<NaiveDateTime> {
+    t.with_minute(0)
+        .and_then(|t| t.with_second(0))
+        .ok_or_else(|| anyhow!("invalid timestamp '{t}'"))
+}
+
+// Net import position of `zone` per UTC hour from the rows of one document type of a border table
+// (see border_flows), e.g. A11 physical flows or A09 scheduled exchanges, which may share a dataset
+// and must not be added up: imports are flows with to_zone == zone, exports flows with from_zone ==
+// zone. Sub-hourly intervals are averaged per border direction first, so PT15M and PT60M borders
+// can be mixed. Columns: datetime_utc, import_mw, export_mw, net_import_mw.
+pub fn net_import_position(
+    flows: &DataFrame,
+    zone: &str,
+    document_type: DocumentType,
+) -> Result<DataFrame> {
+    let doc = flows.column("document_type")?.str()?;
+    let from = flows.column("from_zone")?.str()?;
+    let to = flows.column("to_zone")?.str()?;
+    let start = column_utc(flows.column("start")?)?;
+    let qty = flows.column("quantity_mw")?.f64()?;
+
+    // (hour, import?, counterpart) -> (sum, count)
+    let mut per_border: BTreeMap<(NaiveDateTime, bool, String), (f64, usize)> = BTreeMap::new();
+    for (i, s) in start.into_iter().enumerate() {
+        let (Some(f), Some(t), Some(s), Some(v)) = (from.get(i), to.get(i), s, qty.get(i)) else {
+            continue;
+        };
+        if doc.get(i) != Some(document_type.code()) {
+            continue;
+        }
+        let key = if t == zone {
+            (true, f)
+        } else if f == zone {
+            (false, t)
+        } else {
+            continue;
+        };
+        let e = per_border
+            .entry((hour_start(s)?, key.0, key.1.to_string()))
+            .or_insert((0.0, 0));
+        e.0 += v;
+        e.1 += 1;
+    }
+
+    // hour -> (import, export)
+    let mut hourly: BTreeMap<NaiveDateTime, (f64, f64)> = BTreeMap::new();
+    for ((hour, is_import, _), (sum, n)) in per_border {
+        let e = hourly.entry(hour).or_insert((0.0, 0.0));
+        let mean = sum / n as f64;
+        if is_import {
+            e.0 += mean;
+        } else {
+            e.1 += mean;
+        }
+    }
+
+    let hours: Vec<NaiveDateTime> = hourly.keys().copied().collect();
+    let import_mw: Vec<f64> = hourly.values().map(|v| v.0).collect();
+    let export_mw: Vec<f64> = hourly.values().map(|v| v.1).collect();
+    let net_import_mw: Vec<f64> = hourly.values().map(|v| v.0 - v.1).collect();
+    let df = DataFrame::new(vec![
+        utc_column("datetime_utc", &hours)?,
+        Column::new("import_mw".into(), import_mw),
+        Column::new("export_mw".into(), export_mw),
+        Column::new("net_import_mw".into(), net_import_mw),
+    ])?;
+    Ok(df)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::parse_timeseries_xml;
+
+    const NL: &str = "10YNL----------L";
+    const BE: &str = "10YBE----------2";
+    const DE_LU: &str = "10Y1001A1001A82H";
+
+    // One border direction over 2024-01-01 00:00-01:00 UTC.
+    fn series(out_domain: &str, in_domain: &str, resolution: &str, values: &[f64]) -> String {
+        let points: String = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                format!(
+                    "<Point><position>{}</position><quantity>{v}</quantity></Point>",
+                    i + 1
+                )
+            })
+            .collect();
+        format!(
+            r#"<TimeSeries>
+    <mRID>{out_domain}-{in_domain}</mRID>
+    <in_Domain.mRID codingScheme="A01">{in_domain}</in_Domain.mRID>
+    <out_Domain.mRID codingScheme="A01">{out_domain}</out_Domain.mRID>
+    <quantity_Measure_Unit.name>MAW</quantity_Measure_Unit.name>
+    <Period>
+      <timeInterval><start>2024-01-01T00:00Z</start><end>2024-01-01T01:00Z</end></timeInterval>
+      <resolution>{resolution}</resolution>
+      {points}
+    </Period>
+  </TimeSeries>"#
+        )
+    }
+
+    fn flows(document_type: &str, series: &[String]) -> DataFrame {
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Publication_MarketDocument>
+  <mRID>1</mRID>
+  <type>{document_type}</type>
+  {}
+</Publication_MarketDocument>"#,
+            series.concat()
+        );
+        border_flows(&parse_timeseries_xml(&xml).unwrap()).unwrap()
+    }
+
+    fn column(df: &DataFrame, name: &str) -> Vec<f64> {
+        df.column(name)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect()
+    }
+
+    #[test]
+    fn border_flows_run_from_the_out_domain_to_the_in_domain() {
+        let df = flows(
+            "A11",
+            &[
+                series(NL, BE, "PT60M", &[300.0]),
+                series(BE, NL, "PT60M", &[100.0]),
+                series("10Y-UNKNOWN", NL, "PT60M", &[5.0]),
+            ],
+        );
+        let zones = |name: &str| -> Vec<String> {
+            df.column(name)
+                .unwrap()
+                .str()
+                .unwrap()
+                .into_no_null_iter()
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(zones("from_zone"), vec!["NL", "BE", "10Y-UNKNOWN"]);
+        assert_eq!(zones("to_zone"), vec!["BE", "NL", "NL"]);
+        assert_eq!(column(&df, "quantity_mw"), vec![300.0, 100.0, 5.0]);
+    }
+
+    #[test]
+    fn net_import_is_imports_minus_exports_with_quarter_hours_averaged() {
+        let df = flows(
+            "A11",
+            &[
+                series(NL, BE, "PT60M", &[300.0]),
+                series(BE, NL, "PT60M", &[100.0]),
+                series(DE_LU, NL, "PT15M", &[100.0, 200.0, 300.0, 400.0]),
+                // Transit between neighbours does not touch NL.
+                series(BE, DE_LU, "PT60M", &[1000.0]),
+            ],
+        );
+        let nl = net_import_position(&df, "NL", DocumentType::PhysicalFlows).unwrap();
+        assert_eq!(nl.height(), 1);
+        assert_eq!(column(&nl, "import_mw"), vec![350.0]);
+        assert_eq!(column(&nl, "export_mw"), vec![300.0]);
+        assert_eq!(column(&nl, "net_import_mw"), vec![50.0]);
+
+        let be = net_import_position(&df, "BE", DocumentType::PhysicalFlows).unwrap();
+        assert_eq!(column(&be, "net_import_mw"), vec![300.0 - 100.0 - 1000.0]);
+    }
+
+    #[test]
+    fn net_import_only_nets_flows_of_the_requested_document_type() {
+        let mut df = flows("A11", &[series(BE, NL, "PT60M", &[100.0])]);
+        df.vstack_mut(&flows("A09", &[series(BE, NL, "PT60M", &[400.0])]))
+            .unwrap();
+        let physical = net_import_position(&df, "NL", DocumentType::PhysicalFlows).unwrap();
+        assert_eq!(column(&physical, "net_import_mw"), vec![100.0]);
+        let scheduled = net_import_position(&df, "NL", DocumentType::FinalisedSchedule).unwrap();
+        assert_eq!(column(&scheduled, "net_import_mw"), vec![400.0]);
+        let none = net_import_position(&df, "NL", DocumentType::NetTransferCapacity).unwrap();
+        assert_eq!(none.height(), 0);
+    }
+
+    #[test]
+    fn border_pairs_cover_both_directions_and_skip_the_zone_itself() {
+        let nl = BiddingZone::lookup("NL").unwrap();
+        let be = BiddingZone::lookup("BE").unwrap();
+        let pairs: Vec<(&str, &str)> = border_pairs(nl, &[be, nl])
+            .iter()
+            .map(|(f, t)| (f.name, t.name))
+            .collect();
+        assert_eq!(pairs, vec![("NL", "BE"), ("BE", "NL")]);
+    }
+}
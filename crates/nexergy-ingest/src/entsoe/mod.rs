@@ -0,0 +1,73 @@
+use reqwest::blocking::Client;
+use std::collections::HashMap;
+
+// Minimal ENTSO-E client for day-ahead load/RES/price-like time series (Generic TimeSeries)
+// Caller provides endpoint path, security token, and query params.
+
+mod backfill;
+mod error;
+mod flows;
+mod outages;
+mod parse;
+mod query;
+mod resample;
+mod response;
+mod zone_prices;
+
+pub use backfill::{
+    backfill, last_written_date, split_windows, BackfillOptions, BackfillReport, EntsoeClient,
+    RetryPolicy,
+};
+pub use error::{parse_acknowledgement, EntsoeError};
+pub use flows::{border_flows, border_pairs, flows_lake_table, net_import_position, NL_NEIGHBOURS};
+pub use outages::{outage_lake_table, parse_unavailability_xml};
+pub use parse::{
+    document_created, parse_timeseries_xml, timeseries_bitemporal_lake_table, timeseries_lake_table,
+};
+pub use query::{BiddingZone, DocumentType, EntsoeQuery, ProcessType, BIDDING_ZONES};
+pub use resample::{format_resolution, resample_timeseries, Aggregation};
+pub use response::unzip_documents;
+pub use zone_prices::{zone_prices, zone_prices_lake_table, NL_PRICE_ZONES};
+
+pub const DEFAULT_BASE_URL: &str = "https://web-api.tp.entsoe.eu/api";
+
+pub(crate) fn build_client() -> std::result::Result<Client, EntsoeError> {
+    Ok(Client::builder()
+        .gzip(true)
+        .brotli(true)
+        .deflate(true)
+        .build()?)
+}
+
+// Fetch every XML document of a response; ZIP archives (outage queries) are unpacked.
+// Acknowledgement_MarketDocument responses (no data, bad parameters, ...) are returned as typed errors,
+// whether ENTSO-E sent them with HTTP 200 or 4xx.
+pub fn fetch_documents(
+    base_url: &str,
+    params: &HashMap<&str, String>,
+) -> std::result::Result<Vec<String>, EntsoeError> {
+    response::get_documents(&build_client()?, base_url, params)
+}
+
+// Fetch a single document as text; use fetch_documents for queries that may return archives.
+pub fn fetch_xml_to_string(
+    base_url: &str,
+    params: &HashMap<&str, String>,
+) -> std::result::Result<String, EntsoeError> {
+    let mut docs = fetch_documents(base_url, params)?;
+    match docs.len() {
+        1 => Ok(docs.remove(0)),
+        n => Err(EntsoeError::UnexpectedArchive { documents: n }),
+    }
+}
+
+// Fetch a typed query, adding the security token.
+pub fn fetch_query(
+    base_url: &str,
+    security_token: &str,
+    query: &EntsoeQuery,
+) -> std::result::Result<String, EntsoeError> {
+    let mut params = query.to_params();
+    params.insert("securityToken", security_token.to_string());
+    fetch_xml_to_string(base_url, &params)
+}
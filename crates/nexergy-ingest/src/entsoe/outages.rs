@@ -0,0 +1,410 @@
+use super::parse::{parse_entsoe_datetime, parse_resolution};
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use nexergy_core::io::LakeTable;
+use nexergy_core::time::utc_column;
+use polars::prelude::*;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+#[derive(Debug, Default, Clone)]
+struct OutageHeader {
+    mrid: String,
+    revision: Option<i64>,
+    doc_type: Option<String>,
+    process_type: Option<String>,
+    // docStatus/value: A05 active, A09 cancelled, A13 withdrawn (absent means active)
+    status: Option<String>,
+    reason_code: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct OutageSeries {
+    business_type: Option<String>,
+    area: Option<String>,
+    unit_mrid: Option<String>,
+    unit_name: Option<String>,
+    resource_name: Option<String>,
+    psr_type: Option<String>,
+    nominal_mw: Option<f64>,
+    periods: Vec<OutagePeriod>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct OutagePeriod {
+    start: String,
+    end: String,
+    resolution: String,
+    points: Vec<(i64, f64)>,
+}
+
+#[derive(Default)]
+struct OutageDoc {
+    header: OutageHeader,
+    series: Vec<OutageSeries>,
+}
+
+fn parse_num<T: std::str::FromStr>(text: &str, what: &str) -> Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    text.parse()
+        .map_err(|e| anyhow!("parse {what} '{text}': {e}"))
+}
+
+fn apply_header(leaf: &str, parent: &str, depth: usize, text: &str, h: &mut OutageHeader) {
+    match (parent, leaf) {
+        (_, "mRID") if depth == 2 => h.mrid = text.to_string(),
+        (_, "revisionNumber") if depth == 2 => h.revision = text.parse().ok(),
+        (_, "type") if depth == 2 => h.doc_type = Some(text.to_string()),
+        (_, "process.processType") if depth == 2 => h.process_type = Some(text.to_string()),
+        ("docStatus", "value") => h.status = Some(text.to_string()),
+        ("Reason", "code") if depth == 3 && h.reason_code.is_none() => {
+            h.reason_code = Some(text.to_string())
+        }
+        _ => {}
+    }
+}
+
+// TimeSeries-level resource fields. Generation outages use production_RegisteredResource.*,
+// transmission outages use Asset_RegisteredResource.*; match on the suffix to cover both.
+fn apply_series(leaf: &str, text: &str, ts: &mut OutageSeries) -> Result<()> {
+    let t = Some(text.to_string());
+    match leaf {
+        "businessType" => ts.business_type = t,
+        "biddingZone_Domain.mRID" | "in_Domain.mRID" if ts.area.is_none() => ts.area = t,
+        l if l.ends_with("powerSystemResources.mRID") => ts.unit_mrid = t,
+        l if l.ends_with("powerSystemResources.name") => ts.unit_name = t,
+        l if l.ends_with("powerSystemResources.nominalP") => {
+            ts.nominal_mw = Some(parse_num(text, "nominalP")?)
+        }
+        l if l.ends_with("RegisteredResource.mRID") && ts.unit_mrid.is_none() => ts.unit_mrid = t,
+        l if l.ends_with("RegisteredResource.name") => ts.resource_name = t,
+        l if l.ends_with("pSRType.psrType") => ts.psr_type = t,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn apply_period(
+    leaf: &str,
+    parent: &str,
+    text: &str,
+    period: &mut OutagePeriod,
+    point: &mut (Option<i64>, Option<f64>),
+) -> Result<()> {
+    match (parent, leaf) {
+        ("timeInterval", "start") => period.start = text.to_string(),
+        ("timeInterval", "end") => period.end = text.to_string(),
+        ("Available_Period", "resolution") => period.resolution = text.to_string(),
+        ("Point", "position") => point.0 = Some(parse_num(text, "position")?),
+        ("Point", "quantity") => point.1 = Some(parse_num(text, "quantity")?),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn read_outage_doc(xml: &str) -> Result<OutageDoc> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut path: Vec<String> = Vec::new();
+    let mut doc = OutageDoc::default();
+    let mut ts = OutageSeries::default();
+    let mut period = OutagePeriod::default();
+    let mut point: (Option<i64>, Option<f64>) = (None, None);
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match name.as_str() {
+                    "TimeSeries" => ts = OutageSeries::default(),
+                    "Available_Period" => period = OutagePeriod::default(),
+                    "Point" => point = (None, None),
+                    _ => {}
+                }
+                path.push(name);
+            }
+            Event::Text(t) => {
+                let text = t.unescape()?;
+                let text = text.trim();
+                let n = path.len();
+                if n < 2 {
+                    continue;
+                }
+                let (leaf, parent) = (path[n - 1].as_str(), path[n - 2].as_str());
+                if path.iter().any(|p| p == "Available_Period") {
+                    apply_period(leaf, parent, text, &mut period, &mut point)?;
+                } else if parent == "TimeSeries" {
+                    apply_series(leaf, text, &mut ts)?;
+                } else {
+                    apply_header(leaf, parent, n, text, &mut doc.header);
+                }
+            }
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                match name.as_str() {
+                    "Point" => {
+                        if let (Some(pos), Some(v)) = point {
+                            period.points.push((pos, v));
+                        }
+                    }
+                    "Available_Period" => ts.periods.push(std::mem::take(&mut period)),
+                    "TimeSeries" => doc.series.push(std::mem::take(&mut ts)),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(doc)
+}
+
+// Intervals of an Available_Period. Points hold until the next point's position or the period end,
+// so sparse (curveType A03) and dense (A01) curves are both handled.
+fn period_intervals(p: &OutagePeriod) -> Result<Vec<(NaiveDateTime, NaiveDateTime, f64)>> {
+    let start = parse_entsoe_datetime(&p.start)?;
+    let end = parse_entsoe_datetime(&p.end)?;
+    let step = parse_resolution(&p.resolution)?;
+    let mut points = p.points.clone();
+    points.sort_by_key(|(pos, _)| *pos);
+    let mut out = Vec::with_capacity(points.len());
+    for (i, &(pos, v)) in points.iter().enumerate() {
+        let t0 = start + step * ((pos - 1) as i32);
+        let t1 = match points.get(i + 1) {
+            Some(&(next, _)) => start + step * ((next - 1) as i32),
+            None => end,
+        };
+        out.push((t0, t1.min(end), v));
+    }
+    Ok(out)
+}
+
+#[derive(Default)]
+struct OutageColumns {
+    mrid: Vec<String>,
+    revision: Vec<Option<i64>>,
+    document_type: Vec<Option<String>>,
+    process_type: Vec<Option<String>>,
+    status: Vec<String>,
+    reason_code: Vec<Option<String>>,
+    business_type: Vec<Option<String>>,
+    area: Vec<Option<String>>,
+    unit_mrid: Vec<Option<String>>,
+    unit_name: Vec<Option<String>>,
+    psr_type: Vec<Option<String>>,
+    nominal_power_mw: Vec<Option<f64>>,
+    start: Vec<NaiveDateTime>,
+    end: Vec<NaiveDateTime>,
+    available_mw: Vec<f64>,
+}
+
+impl OutageColumns {
+    fn push_doc(&mut self, doc: &OutageDoc) -> Result<()> {
+        let h = &doc.header;
+        for ts in doc.series.iter() {
+            for p in ts.periods.iter() {
+                for (t0, t1, v) in period_intervals(p)? {
+                    self.mrid.push(h.mrid.clone());
+                    self.revision.push(h.revision);
+                    self.document_type.push(h.doc_type.clone());
+                    self.process_type.push(h.process_type.clone());
+                    self.status
+                        .push(h.status.clone().unwrap_or_else(|| "A05".to_string()));
+                    self.reason_code.push(h.reason_code.clone());
+                    self.business_type.push(ts.business_type.clone());
+                    self.area.push(ts.area.clone());
+                    self.unit_mrid.push(ts.unit_mrid.clone());
+                    self.unit_name
+                        .push(ts.unit_name.clone().or_else(|| ts.resource_name.clone()));
+                    self.psr_type.push(ts.psr_type.clone());
+                    self.nominal_power_mw.push(ts.nominal_mw);
+                    self.start.push(t0);
+                    self.end.push(t1);
+                    self.available_mw.push(v);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn into_df(self) -> Result<DataFrame> {
+        let df = DataFrame::new(vec![
+            Column::new("mrid".into(), self.mrid),
+            Column::new("revision".into(), self.revision),
+            Column::new("document_type".into(), self.document_type),
+            Column::new("process_type".into(), self.process_type),
+            Column::new("status".into(), self.status),
+            Column::new("reason_code".into(), self.reason_code),
+            Column::new("business_type".into(), self.business_type),
+            Column::new("area".into(), self.area),
+            Column::new("unit_mrid".into(), self.unit_mrid),
+            Column::new("unit_name".into(), self.unit_name),
+            Column::new("psr_type".into(), self.psr_type),
+            Column::new("nominal_power_mw".into(), self.nominal_power_mw),
+            utc_column("start", &self.start)?,
+            utc_column("end", &self.end)?,
+            Column::new("available_mw".into(), self.available_mw),
+        ])?;
+        Ok(df)
+    }
+}
+
+// Parse an Unavailability_MarketDocument (A77/A78/A80 outages) into an interval table with one row per
+// available-capacity interval: mrid, revision, document_type, process_type, status, reason_code,
+// business_type, area, unit_mrid, unit_name, psr_type, nominal_power_mw, start, end, available_mw.
+// `start`/`end` are Datetime (UTC); `status` is the docStatus value (A05 active, A09 cancelled, A13 withdrawn).
+pub fn parse_unavailability_xml(xml: &str) -> Result<DataFrame> {
+    let doc = read_outage_doc(xml)?;
+    let mut cols = OutageColumns::default();
+    cols.push_doc(&doc)?;
+    cols.into_df()
+}
+
+// Outage intervals: one row per document revision, resource and interval start.
+pub fn outage_lake_table() -> LakeTable {
+    LakeTable::new("start").primary_key(&[
+        "mrid",
+        "revision",
+        "business_type",
+        "unit_mrid",
+        "start",
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nexergy_core::time::column_utc;
+
+    // A cancelled generation outage with two units: a sparse (A03) curve of two points over six
+    // hours, and a dense curve of two hourly points.
+    const A80: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Unavailability_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-6:outagedocument:3:0">
+  <mRID>outage-1</mRID>
+  <revisionNumber>3</revisionNumber>
+  <type>A80</type>
+  <process.processType>A26</process.processType>
+  <docStatus><value>A09</value></docStatus>
+  <TimeSeries>
+    <mRID>1</mRID>
+    <businessType>A53</businessType>
+    <biddingZone_Domain.mRID codingScheme="A01">10YNL----------L</biddingZone_Domain.mRID>
+    <production_RegisteredResource.mRID codingScheme="A01">unit-a</production_RegisteredResource.mRID>
+    <production_RegisteredResource.name>Plant A</production_RegisteredResource.name>
+    <production_RegisteredResource.pSRType.psrType>B04</production_RegisteredResource.pSRType.psrType>
+    <production_RegisteredResource.pSRType.powerSystemResources.nominalP unit="MAW">400</production_RegisteredResource.pSRType.powerSystemResources.nominalP>
+    <curveType>A03</curveType>
+    <Available_Period>
+      <timeInterval><start>2024-03-01T00:00Z</start><end>2024-03-01T06:00Z</end></timeInterval>
+      <resolution>PT60M</resolution>
+      <Point><position>1</position><quantity>0</quantity></Point>
+      <Point><position>3</position><quantity>200</quantity></Point>
+    </Available_Period>
+  </TimeSeries>
+  <TimeSeries>
+    <mRID>2</mRID>
+    <businessType>A54</businessType>
+    <biddingZone_Domain.mRID codingScheme="A01">10YNL----------L</biddingZone_Domain.mRID>
+    <production_RegisteredResource.mRID codingScheme="A01">unit-b</production_RegisteredResource.mRID>
+    <production_RegisteredResource.pSRType.psrType>B14</production_RegisteredResource.pSRType.psrType>
+    <Available_Period>
+      <timeInterval><start>2024-03-01T04:00Z</start><end>2024-03-01T06:00Z</end></timeInterval>
+      <resolution>PT60M</resolution>
+      <Point><position>1</position><quantity>100</quantity></Point>
+      <Point><position>2</position><quantity>150</quantity></Point>
+    </Available_Period>
+  </TimeSeries>
+  <Reason><code>B18</code></Reason>
+</Unavailability_MarketDocument>"#;
+
+    fn ts(s: &str) -> NaiveDateTime {
+        parse_entsoe_datetime(s).unwrap()
+    }
+
+    fn intervals(df: &DataFrame) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        let start = column_utc(df.column("start").unwrap()).unwrap();
+        let end = column_utc(df.column("end").unwrap()).unwrap();
+        start
+            .into_iter()
+            .zip(end)
+            .map(|(s, e)| (s.unwrap(), e.unwrap()))
+            .collect()
+    }
+
+    fn strings(df: &DataFrame, name: &str) -> Vec<Option<String>> {
+        df.column(name)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|v| v.map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn points_hold_until_the_next_position_or_the_period_end() {
+        let df = parse_unavailability_xml(A80).unwrap();
+        assert_eq!(
+            intervals(&df),
+            vec![
+                (ts("2024-03-01T00:00Z"), ts("2024-03-01T02:00Z")),
+                (ts("2024-03-01T02:00Z"), ts("2024-03-01T06:00Z")),
+                (ts("2024-03-01T04:00Z"), ts("2024-03-01T05:00Z")),
+                (ts("2024-03-01T05:00Z"), ts("2024-03-01T06:00Z")),
+            ]
+        );
+        let available: Vec<f64> = df
+            .column("available_mw")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        assert_eq!(available, vec![0.0, 200.0, 100.0, 150.0]);
+    }
+
+    #[test]
+    fn every_series_carries_the_header_and_its_own_resource() {
+        let df = parse_unavailability_xml(A80).unwrap();
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(strings(&df, "mrid"), vec![some("outage-1"); 4]);
+        assert_eq!(strings(&df, "status"), vec![some("A09"); 4]);
+        assert_eq!(strings(&df, "reason_code"), vec![some("B18"); 4]);
+        assert_eq!(
+            strings(&df, "unit_mrid"),
+            vec![
+                some("unit-a"),
+                some("unit-a"),
+                some("unit-b"),
+                some("unit-b")
+            ]
+        );
+        assert_eq!(
+            strings(&df, "unit_name"),
+            vec![some("Plant A"), some("Plant A"), None, None]
+        );
+        assert_eq!(strings(&df, "area"), vec![some("10YNL----------L"); 4]);
+        let revision = df.column("revision").unwrap().i64().unwrap().get(0);
+        assert_eq!(revision, Some(3));
+        let nominal: Vec<Option<f64>> = df
+            .column("nominal_power_mw")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .iter()
+            .collect();
+        assert_eq!(nominal, vec![Some(400.0), Some(400.0), None, None]);
+    }
+
+    #[test]
+    fn missing_status_means_active() {
+        let xml = A80.replace("<docStatus><value>A09</value></docStatus>", "");
+        let df = parse_unavailability_xml(&xml).unwrap();
+        assert_eq!(
+            strings(&df, "status"),
+            vec![Some("A05".to_string()); df.height()]
+        );
+    }
+}
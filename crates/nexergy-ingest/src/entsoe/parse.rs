@@ -0,0 +1,550 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDateTime};
+use nexergy_core::io::LakeTable;
+use nexergy_core::time::utc_column;
+use polars::prelude::*;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RawPeriod {
+    pub start: String,
+    pub end: String,
+    pub resolution: String,
+    pub points: Vec<(i64, f64)>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RawTimeSeries {
+    pub mrid: String,
+    pub business_type: Option<String>,
+    pub psr_type: Option<String>,
+    // EICs found in the domain elements, indexed like AREA_ELEMENTS.
+    pub domains: [Option<String>; 4],
+    // quantity_Measure_Unit.name (MAW, MWH) or price_Measure_Unit.name (MWH for EUR/MWh).
+    pub unit: Option<String>,
+    // A01 fixed-size blocks (every position present) or A03 variable-sized blocks.
+    pub curve_type: Option<String>,
+    pub periods: Vec<RawPeriod>,
+}
+
+// curveType A03: a point holds until the next listed position, omitted positions repeat it.
+pub(crate) const CURVE_VARIABLE_BLOCKS: &str = "A03";
+
+impl RawPeriod {
+    // Points of the period, sorted by position. Variable-sized blocks (A03) are expanded to one point
+    // per resolution step up to the period end. Positions outside the period's steps are an error, and
+    // so are variable-sized blocks without position 1 (there is no value to hold before the first).
+    pub fn dense_points(&self, variable_blocks: bool) -> Result<Vec<(i64, f64)>> {
+        let mut points = self.points.clone();
+        points.sort_by_key(|(pos, _)| *pos);
+        let (Some(&(first, _)), Some(&(last, _))) = (points.first(), points.last()) else {
+            return Ok(points);
+        };
+        let start = parse_entsoe_datetime(&self.start)?;
+        let end = parse_entsoe_datetime(&self.end)?;
+        let step = parse_resolution(&self.resolution)?;
+        let slots = ((end - start).num_seconds() / step.num_seconds().max(1)).max(1);
+        if let Some(pos) = [first, last].into_iter().find(|p| !(1..=slots).contains(p)) {
+            return Err(anyhow!(
+                "position {pos} outside the {slots} steps of the period {} to {}",
+                self.start,
+                self.end
+            ));
+        }
+        if !variable_blocks {
+            return Ok(points);
+        }
+        if first != 1 {
+            return Err(anyhow!(
+                "variable-sized blocks of the period from {} start at position {first}, not 1",
+                self.start
+            ));
+        }
+        let mut dense: Vec<(i64, f64)> = Vec::with_capacity(slots as usize);
+        let mut next = points.iter().peekable();
+        let mut value = points[0].1;
+        for pos in 1..=slots {
+            while let Some(&&(p, v)) = next.peek() {
+                if p > pos {
+                    break;
+                }
+                value = v;
+                next.next();
+            }
+            dense.push((pos, value));
+        }
+        Ok(dense)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RawDocument {
+    // Document type code (A44, A65, A69, ...)
+    pub doc_type: Option<String>,
+    pub series: Vec<RawTimeSeries>,
+}
+
+// Domain elements that identify the area of a TimeSeries, in order of preference.
+const AREA_ELEMENTS: [&str; 4] = [
+    "inBiddingZone_Domain.mRID",
+    "outBiddingZone_Domain.mRID",
+    "in_Domain.mRID",
+    "out_Domain.mRID",
+];
+
+fn area_index(elem: &str) -> Option<usize> {
+    AREA_ELEMENTS.iter().position(|a| *a == elem)
+}
+
+impl RawTimeSeries {
+    // EIC of the bidding zone/area the series describes (first domain element present).
+    pub fn area(&self) -> Option<String> {
+        self.domains.iter().flatten().next().cloned()
+    }
+
+    // EIC the energy flows into (in_Domain, or inBiddingZone_Domain).
+    pub fn in_domain(&self) -> Option<String> {
+        self.domains[2].clone().or_else(|| self.domains[0].clone())
+    }
+
+    // EIC the energy flows out of (out_Domain, or outBiddingZone_Domain).
+    pub fn out_domain(&self) -> Option<String> {
+        self.domains[3].clone().or_else(|| self.domains[1].clone())
+    }
+}
+
+// Parse ENTSO-E timestamps such as `2024-01-01T00:00Z` or `2024-01-01T00:00:00Z` (always UTC).
+pub(crate) fn parse_entsoe_datetime(s: &str) -> Result<NaiveDateTime> {
+    let t = s.trim();
+    NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%MZ")
+        .or_else(|_| NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M:%SZ"))
+        .map_err(|e| anyhow!("parse ENTSO-E datetime '{t}': {e}"))
+}
+
+// Parse the ISO 8601 durations ENTSO-E uses for resolutions (PT15M, PT30M, PT60M, PT1H, P1D, P7D).
+pub(crate) fn parse_resolution(s: &str) -> Result<Duration> {
+    let t = s.trim();
+    let bad = || anyhow!("unsupported ENTSO-E resolution '{t}'");
+    if let Some(rest) = t.strip_prefix("PT") {
+        let (num, unit) = rest.split_at(rest.len().saturating_sub(1));
+        let n: i64 = num.parse().map_err(|_| bad())?;
+        return match unit {
+            "M" => Ok(Duration::minutes(n)),
+            "H" => Ok(Duration::hours(n)),
+            "S" => Ok(Duration::seconds(n)),
+            _ => Err(bad()),
+        };
+    }
+    if let Some(rest) = t.strip_prefix('P') {
+        let (num, unit) = rest.split_at(rest.len().saturating_sub(1));
+        let n: i64 = num.parse().map_err(|_| bad())?;
+        return match unit {
+            "D" => Ok(Duration::days(n)),
+            "W" => Ok(Duration::weeks(n)),
+            _ => Err(bad()),
+        };
+    }
+    Err(bad())
+}
+
+// Assign the text of a leaf element to the series/period/point currently being built.
+// `path` holds the local names of all open elements, innermost last.
+fn apply_text(
+    path: &[String],
+    text: &str,
+    doc: &mut RawDocument,
+    ts: &mut RawTimeSeries,
+    period: &mut RawPeriod,
+    point: &mut (Option<i64>, Option<f64>),
+) -> Result<()> {
+    let n = path.len();
+    if n < 2 {
+        return Ok(());
+    }
+    let leaf = path[n - 1].as_str();
+    let parent = path[n - 2].as_str();
+    match (parent, leaf) {
+        (_, "type") if n == 2 => doc.doc_type = Some(text.to_string()),
+        ("TimeSeries", "mRID") => ts.mrid = text.to_string(),
+        ("TimeSeries", "businessType") => ts.business_type = Some(text.to_string()),
+        ("TimeSeries", "curveType") => ts.curve_type = Some(text.to_string()),
+        ("TimeSeries", "quantity_Measure_Unit.name" | "price_Measure_Unit.name") => {
+            ts.unit = Some(text.to_string())
+        }
+        ("TimeSeries", elem) if area_index(elem).is_some() => {
+            if let Some(i) = area_index(elem) {
+                ts.domains[i] = Some(text.to_string());
+            }
+        }
+        ("MktPSRType", "psrType") => ts.psr_type = Some(text.to_string()),
+        ("timeInterval", "start") if path.iter().any(|p| p == "Period") => {
+            period.start = text.to_string()
+        }
+        ("timeInterval", "end") if path.iter().any(|p| p == "Period") => {
+            period.end = text.to_string()
+        }
+        ("Period", "resolution") => period.resolution = text.to_string(),
+        ("Point", "position") => {
+            point.0 = Some(
+                text.parse()
+                    .map_err(|e| anyhow!("parse position '{text}': {e}"))?,
+            )
+        }
+        ("Point", "price.amount") | ("Point", "quantity") => {
+            point.1 = Some(
+                text.parse()
+                    .map_err(|e| anyhow!("parse value '{text}': {e}"))?,
+            )
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Read every TimeSeries/Period/Point of a Publication_MarketDocument or GL_MarketDocument.
+pub(crate) fn read_document(xml: &str) -> Result<RawDocument> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut path: Vec<String> = Vec::new();
+    let mut doc = RawDocument::default();
+    let mut ts = RawTimeSeries::default();
+    let mut period = RawPeriod::default();
+    let mut point: (Option<i64>, Option<f64>) = (None, None);
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match name.as_str() {
+                    "TimeSeries" => ts = RawTimeSeries::default(),
+                    "Period" => period = RawPeriod::default(),
+                    "Point" => point = (None, None),
+                    _ => {}
+                }
+                path.push(name);
+            }
+            Event::Text(t) => {
+                let text = t.unescape()?;
+                apply_text(
+                    &path,
+                    text.trim(),
+                    &mut doc,
+                    &mut ts,
+                    &mut period,
+                    &mut point,
+                )?;
+            }
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                match name.as_str() {
+                    "Point" => {
+                        if let (Some(pos), Some(v)) = point {
+                            period.points.push((pos, v));
+                        }
+                    }
+                    "Period" => ts.periods.push(std::mem::take(&mut period)),
+                    "TimeSeries" => doc.series.push(std::mem::take(&mut ts)),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(doc)
+}
+
+#[derive(Default)]
+struct LongColumns {
+    document_type: Vec<Option<String>>,
+    ts_id: Vec<String>,
+    business_type: Vec<Option<String>>,
+    area: Vec<Option<String>>,
+    in_domain: Vec<Option<String>>,
+    out_domain: Vec<Option<String>>,
+    psr_type: Vec<Option<String>>,
+    unit: Vec<Option<String>>,
+    start: Vec<NaiveDateTime>,
+    end: Vec<NaiveDateTime>,
+    resolution: Vec<String>,
+    position: Vec<i64>,
+    quantity: Vec<f64>,
+}
+
+impl LongColumns {
+    fn push_series(&mut self, doc_type: &Option<String>, ts: &RawTimeSeries) -> Result<()> {
+        let (area, in_domain, out_domain) = (ts.area(), ts.in_domain(), ts.out_domain());
+        let variable_blocks = ts.curve_type.as_deref() == Some(CURVE_VARIABLE_BLOCKS);
+        for p in ts.periods.iter() {
+            let p_start = parse_entsoe_datetime(&p.start)?;
+            let step = parse_resolution(&p.resolution)?;
+            for (pos, v) in p.dense_points(variable_blocks)? {
+                let t0 = p_start + step * ((pos - 1) as i32);
+                self.document_type.push(doc_type.clone());
+                self.ts_id.push(ts.mrid.clone());
+                self.business_type.push(ts.business_type.clone());
+                self.area.push(area.clone());
+                self.in_domain.push(in_domain.clone());
+                self.out_domain.push(out_domain.clone());
+                self.psr_type.push(ts.psr_type.clone());
+                self.unit.push(ts.unit.clone());
+                self.start.push(t0);
+                self.end.push(t0 + step);
+                self.resolution.push(p.resolution.clone());
+                self.position.push(pos);
+                self.quantity.push(v);
+            }
+        }
+        Ok(())
+    }
+
+    fn into_df(self) -> Result<DataFrame> {
+        let df = DataFrame::new(vec![
+            Column::new("document_type".into(), self.document_type),
+            Column::new("ts_id".into(), self.ts_id),
+            Column::new("business_type".into(), self.business_type),
+            Column::new("area".into(), self.area),
+            Column::new("in_domain".into(), self.in_domain),
+            Column::new("out_domain".into(), self.out_domain),
+            Column::new("psr_type".into(), self.psr_type),
+            Column::new("unit".into(), self.unit),
+            utc_column("start", &self.start)?,
+            utc_column("end", &self.end)?,
+            Column::new("resolution".into(), self.resolution),
+            Column::new("position".into(), self.position),
+            Column::new("quantity".into(), self.quantity),
+        ])?;
+        Ok(df)
+    }
+}
+
+// Long time series tables: one row per series and interval. ts_id is document-local, so it is not
+// part of the key.
+pub fn timeseries_lake_table() -> LakeTable {
+    LakeTable::new("start").primary_key(&[
+        "document_type",
+        "business_type",
+        "area",
+        "in_domain",
+        "out_domain",
+        "psr_type",
+        "unit",
+        "start",
+        "resolution",
+    ])
+}
+
+// Time series tables with valid_time (the interval start) and knowledge_time (see
+// nexergy_core::asof); every published version of an interval is kept.
+pub fn timeseries_bitemporal_lake_table() -> LakeTable {
+    LakeTable::bitemporal(&[
+        "document_type",
+        "business_type",
+        "area",
+        "in_domain",
+        "out_domain",
+        "psr_type",
+        "unit",
+        "resolution",
+    ])
+}
+
+// createdDateTime of a market document (when ENTSO-E created this version of it), if it has one.
+pub fn document_created(xml: &str) -> Result<Option<NaiveDateTime>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut depth = 0usize;
+    let mut in_created = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                depth += 1;
+                in_created = depth == 2 && e.local_name().as_ref() == b"createdDateTime";
+            }
+            Event::Text(t) if in_created => {
+                return parse_entsoe_datetime(&t.unescape()?).map(Some);
+            }
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                in_created = false;
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+// Parse a Publication_MarketDocument (e.g. A44 day-ahead prices) or GL_MarketDocument (A65 load,
+// A69 wind/solar forecasts) into one tidy long table with columns:
+// document_type, ts_id, business_type, area, in_domain, out_domain, psr_type, unit, start, end, resolution,
+// position, quantity. `area` is the EIC of the in/out (bidding zone) domain, with in_domain/out_domain
+// kept separately for border documents (flows, exchanges, capacities); `psr_type` is null for series without MktPSRType,
+// and `start`/`end` are the absolute UTC bounds of each point (Datetime, UTC):
+// timeInterval.start + resolution * (position - 1).
+// Each Period keeps its own resolution (series may mix PT15M and PT60M); A03 curves are expanded densely.
+pub fn parse_timeseries_xml(xml: &str) -> Result<DataFrame> {
+    let doc = read_document(xml)?;
+    let mut cols = LongColumns::default();
+    for ts in doc.series.iter() {
+        cols.push_series(&doc.doc_type, ts)?;
+    }
+    cols.into_df()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nexergy_core::time::column_utc;
+
+    // Day-ahead prices in one series: an hourly period missing position 3, then a quarter-hourly
+    // period listing positions 1 and 3 only.
+    fn a44(curve_type: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Publication_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-3:publicationdocument:7:3">
+  <mRID>1</mRID>
+  <type>A44</type>
+  <createdDateTime>2024-01-01T11:05:12Z</createdDateTime>
+  <TimeSeries>
+    <mRID>1</mRID>
+    <businessType>A62</businessType>
+    <in_Domain.mRID codingScheme="A01">10YNL----------L</in_Domain.mRID>
+    <out_Domain.mRID codingScheme="A01">10YNL----------L</out_Domain.mRID>
+    <price_Measure_Unit.name>MWH</price_Measure_Unit.name>
+    <curveType>{curve_type}</curveType>
+    <Period>
+      <timeInterval><start>2024-01-01T23:00Z</start><end>2024-01-02T03:00Z</end></timeInterval>
+      <resolution>PT60M</resolution>
+      <Point><position>1</position><price.amount>50.5</price.amount></Point>
+      <Point><position>2</position><price.amount>48</price.amount></Point>
+      <Point><position>4</position><price.amount>45.25</price.amount></Point>
+    </Period>
+    <Period>
+      <timeInterval><start>2024-01-02T03:00Z</start><end>2024-01-02T04:00Z</end></timeInterval>
+      <resolution>PT15M</resolution>
+      <Point><position>1</position><price.amount>40</price.amount></Point>
+      <Point><position>3</position><price.amount>42</price.amount></Point>
+    </Period>
+  </TimeSeries>
+</Publication_MarketDocument>"#
+        )
+    }
+
+    fn ts(s: &str) -> NaiveDateTime {
+        parse_entsoe_datetime(s).unwrap()
+    }
+
+    fn bounds(df: &DataFrame) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        let start = column_utc(df.column("start").unwrap()).unwrap();
+        let end = column_utc(df.column("end").unwrap()).unwrap();
+        start
+            .into_iter()
+            .zip(end)
+            .map(|(s, e)| (s.unwrap(), e.unwrap()))
+            .collect()
+    }
+
+    fn quantities(df: &DataFrame) -> Vec<f64> {
+        df.column("quantity")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect()
+    }
+
+    #[test]
+    fn fixed_blocks_keep_listed_positions_with_their_own_resolution() {
+        let df = parse_timeseries_xml(&a44("A01")).unwrap();
+        assert_eq!(
+            bounds(&df),
+            vec![
+                (ts("2024-01-01T23:00Z"), ts("2024-01-02T00:00Z")),
+                (ts("2024-01-02T00:00Z"), ts("2024-01-02T01:00Z")),
+                (ts("2024-01-02T02:00Z"), ts("2024-01-02T03:00Z")),
+                (ts("2024-01-02T03:00Z"), ts("2024-01-02T03:15Z")),
+                (ts("2024-01-02T03:30Z"), ts("2024-01-02T03:45Z")),
+            ]
+        );
+        assert_eq!(quantities(&df), vec![50.5, 48.0, 45.25, 40.0, 42.0]);
+        let resolution: Vec<&str> = df
+            .column("resolution")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        assert_eq!(
+            resolution,
+            vec!["PT60M", "PT60M", "PT60M", "PT15M", "PT15M"]
+        );
+        let area = df.column("area").unwrap().str().unwrap().get(0);
+        assert_eq!(area, Some("10YNL----------L"));
+    }
+
+    #[test]
+    fn document_created_reads_the_header_only() {
+        let xml = a44("A01");
+        assert_eq!(
+            document_created(&xml).unwrap(),
+            Some(ts("2024-01-01T11:05:12Z"))
+        );
+        let without = xml.replace(
+            "<createdDateTime>2024-01-01T11:05:12Z</createdDateTime>",
+            "",
+        );
+        assert_eq!(document_created(&without).unwrap(), None);
+    }
+
+    #[test]
+    fn variable_blocks_repeat_the_previous_point_over_gaps() {
+        let df = parse_timeseries_xml(&a44(CURVE_VARIABLE_BLOCKS)).unwrap();
+        let b = bounds(&df);
+        assert_eq!(b.len(), 8);
+        assert_eq!(b[0].0, ts("2024-01-01T23:00Z"));
+        assert_eq!(b[2], (ts("2024-01-02T01:00Z"), ts("2024-01-02T02:00Z")));
+        assert_eq!(b[7], (ts("2024-01-02T03:45Z"), ts("2024-01-02T04:00Z")));
+        assert!(b.windows(2).all(|w| w[0].1 == w[1].0));
+        assert_eq!(
+            quantities(&df),
+            vec![50.5, 48.0, 48.0, 45.25, 40.0, 40.0, 42.0, 42.0]
+        );
+    }
+
+    #[test]
+    fn positions_outside_the_period_are_errors() {
+        // Eight quarter-hours.
+        let period = |points: Vec<(i64, f64)>| RawPeriod {
+            start: "2024-01-01T23:00Z".to_string(),
+            end: "2024-01-02T01:00Z".to_string(),
+            resolution: "PT15M".to_string(),
+            points,
+        };
+        let fixed = period(vec![(3, 2.0), (1, 1.0)])
+            .dense_points(false)
+            .unwrap();
+        assert_eq!(fixed, vec![(1, 1.0), (3, 2.0)]);
+        let dense = period(vec![(1, 1.0), (7, 2.0)]).dense_points(true).unwrap();
+        assert_eq!(dense.last(), Some(&(8, 2.0)));
+        for variable_blocks in [false, true] {
+            let err = period(vec![(1, 1.0), (9, 2.0)])
+                .dense_points(variable_blocks)
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("position 9 outside the 8 steps"),
+                "{err}"
+            );
+            assert!(period(vec![(0, 1.0)])
+                .dense_points(variable_blocks)
+                .is_err());
+        }
+        // Nothing to hold over the first steps.
+        let err = period(vec![(2, 1.0), (5, 2.0)])
+            .dense_points(true)
+            .unwrap_err();
+        assert!(err.to_string().contains("start at position 2"), "{err}");
+        assert!(period(Vec::new()).dense_points(true).unwrap().is_empty());
+    }
+}
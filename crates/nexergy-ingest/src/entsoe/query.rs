@@ -0,0 +1,357 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Format of periodStart/periodEnd query parameters (UTC).
+const PERIOD_FORMAT: &str = "%Y%m%d%H%M";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DocumentType {
+    // A09 Finalised schedule (scheduled commercial exchanges)
+    FinalisedSchedule,
+    // A11 Aggregated energy data report (cross-border physical flows)
+    PhysicalFlows,
+    // A44 Price document (day-ahead prices)
+    Prices,
+    // A61 Estimated net transfer capacity
+    NetTransferCapacity,
+    // A65 System total load
+    SystemTotalLoad,
+    // A69 Wind and solar forecast
+    WindSolarForecast,
+    // A71 Generation forecast
+    GenerationForecast,
+    // A75 Actual generation per type
+    ActualGenerationPerType,
+    // A77 Production unavailability
+    ProductionUnavailability,
+    // A78 Transmission unavailability
+    TransmissionUnavailability,
+    // A80 Generation unavailability
+    GenerationUnavailability,
+}
+
+impl DocumentType {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DocumentType::FinalisedSchedule => "A09",
+            DocumentType::PhysicalFlows => "A11",
+            DocumentType::Prices => "A44",
+            DocumentType::NetTransferCapacity => "A61",
+            DocumentType::SystemTotalLoad => "A65",
+            DocumentType::WindSolarForecast => "A69",
+            DocumentType::GenerationForecast => "A71",
+            DocumentType::ActualGenerationPerType => "A75",
+            DocumentType::ProductionUnavailability => "A77",
+            DocumentType::TransmissionUnavailability => "A78",
+            DocumentType::GenerationUnavailability => "A80",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProcessType {
+    // A01 Day ahead
+    DayAhead,
+    // A16 Realised
+    Realised,
+    // A18 Intraday total
+    IntradayTotal,
+    // A31 Week ahead
+    WeekAhead,
+    // A32 Month ahead
+    MonthAhead,
+    // A33 Year ahead
+    YearAhead,
+    // A53 Planned maintenance
+    PlannedMaintenance,
+    // A54 Unplanned outage
+    UnplannedOutage,
+}
+
+impl ProcessType {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProcessType::DayAhead => "A01",
+            ProcessType::Realised => "A16",
+            ProcessType::IntradayTotal => "A18",
+            ProcessType::WeekAhead => "A31",
+            ProcessType::MonthAhead => "A32",
+            ProcessType::YearAhead => "A33",
+            ProcessType::PlannedMaintenance => "A53",
+            ProcessType::UnplannedOutage => "A54",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BiddingZone {
+    pub name: &'static str,
+    pub eic: &'static str,
+}
+
+// Bidding zones of NL and its coupled neighbours, friendly name -> EIC code.
+pub const BIDDING_ZONES: &[BiddingZone] = &[
+    BiddingZone {
+        name: "NL",
+        eic: "10YNL----------L",
+    },
+    BiddingZone {
+        name: "DE-LU",
+        eic: "10Y1001A1001A82H",
+    },
+    BiddingZone {
+        name: "BE",
+        eic: "10YBE----------2",
+    },
+    BiddingZone {
+        name: "FR",
+        eic: "10YFR-RTE------C",
+    },
+    BiddingZone {
+        name: "GB",
+        eic: "10YGB----------A",
+    },
+    BiddingZone {
+        name: "NO2",
+        eic: "10YNO-2--------T",
+    },
+    BiddingZone {
+        name: "DK1",
+        eic: "10YDK-1--------W",
+    },
+];
+
+impl BiddingZone {
+    // Look up a zone by friendly name (case-insensitive) or EIC code.
+    pub fn lookup(s: &str) -> Option<BiddingZone> {
+        let t = s.trim();
+        BIDDING_ZONES
+            .iter()
+            .find(|z| z.name.eq_ignore_ascii_case(t) || z.eic == t)
+            .copied()
+    }
+}
+
+impl FromStr for BiddingZone {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        BiddingZone::lookup(s).ok_or_else(|| {
+            let known: Vec<&str> = BIDDING_ZONES.iter().map(|z| z.name).collect();
+            anyhow!(
+                "unknown bidding zone '{s}'; known zones: {}",
+                known.join(", ")
+            )
+        })
+    }
+}
+
+impl fmt::Display for BiddingZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+// A typed ENTSO-E API query. The security token is added by the client, not stored here.
+#[derive(Debug, Clone)]
+pub struct EntsoeQuery {
+    pub document_type: DocumentType,
+    pub process_type: Option<ProcessType>,
+    pub business_type: Option<String>,
+    pub psr_type: Option<String>,
+    // contract_MarketAgreement.Type: A01 day ahead, A05 total, ...
+    pub contract_type: Option<String>,
+    // Domain parameters such as in_Domain/outBiddingZone_Domain with their EIC codes.
+    pub domains: Vec<(&'static str, &'static str)>,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+}
+
+impl EntsoeQuery {
+    pub fn new(document_type: DocumentType, start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        Self {
+            document_type,
+            process_type: None,
+            business_type: None,
+            psr_type: None,
+            contract_type: None,
+            domains: Vec::new(),
+            period_start: start,
+            period_end: end,
+        }
+    }
+
+    // A44 day-ahead prices for one bidding zone.
+    pub fn day_ahead_prices(zone: BiddingZone, start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        Self::new(DocumentType::Prices, start, end)
+            .domain("in_Domain", zone)
+            .domain("out_Domain", zone)
+    }
+
+    // A65 day-ahead total load forecast for one bidding zone.
+    pub fn load_forecast(zone: BiddingZone, start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        Self::new(DocumentType::SystemTotalLoad, start, end)
+            .process_type(ProcessType::DayAhead)
+            .domain("outBiddingZone_Domain", zone)
+    }
+
+    // A69 day-ahead wind and solar generation forecast for one bidding zone.
+    pub fn wind_solar_forecast(
+        zone: BiddingZone,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Self {
+        Self::new(DocumentType::WindSolarForecast, start, end)
+            .process_type(ProcessType::DayAhead)
+            .domain("in_Domain", zone)
+    }
+
+    // A80 generation unit unavailability in a bidding zone (returned as a ZIP archive).
+    pub fn generation_unavailability(
+        zone: BiddingZone,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Self {
+        Self::new(DocumentType::GenerationUnavailability, start, end)
+            .domain("biddingZone_Domain", zone)
+    }
+
+    // A77 production unit unavailability in a bidding zone (returned as a ZIP archive).
+    pub fn production_unavailability(
+        zone: BiddingZone,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Self {
+        Self::new(DocumentType::ProductionUnavailability, start, end)
+            .domain("biddingZone_Domain", zone)
+    }
+
+    // A78 transmission infrastructure unavailability on the border `from` -> `to` (ZIP archive).
+    pub fn transmission_unavailability(
+        from: BiddingZone,
+        to: BiddingZone,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Self {
+        Self::new(DocumentType::TransmissionUnavailability, start, end)
+            .domain("out_Domain", from)
+            .domain("in_Domain", to)
+    }
+
+    // A11 cross-border physical flows from `from` into `to`.
+    pub fn physical_flows(
+        from: BiddingZone,
+        to: BiddingZone,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Self {
+        Self::new(DocumentType::PhysicalFlows, start, end)
+            .domain("out_Domain", from)
+            .domain("in_Domain", to)
+    }
+
+    // A09 total scheduled commercial exchanges from `from` into `to`.
+    pub fn scheduled_exchanges(
+        from: BiddingZone,
+        to: BiddingZone,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Self {
+        Self::new(DocumentType::FinalisedSchedule, start, end)
+            .contract_type("A05")
+            .domain("out_Domain", from)
+            .domain("in_Domain", to)
+    }
+
+    // A61 day-ahead forecasted transfer capacity from `from` into `to`.
+    pub fn forecasted_transfer_capacity(
+        from: BiddingZone,
+        to: BiddingZone,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Self {
+        Self::new(DocumentType::NetTransferCapacity, start, end)
+            .contract_type("A01")
+            .domain("out_Domain", from)
+            .domain("in_Domain", to)
+    }
+
+    pub fn process_type(mut self, process_type: ProcessType) -> Self {
+        self.process_type = Some(process_type);
+        self
+    }
+
+    pub fn business_type(mut self, business_type: &str) -> Self {
+        self.business_type = Some(business_type.to_string());
+        self
+    }
+
+    pub fn psr_type(mut self, psr_type: &str) -> Self {
+        self.psr_type = Some(psr_type.to_string());
+        self
+    }
+
+    pub fn contract_type(mut self, contract_type: &str) -> Self {
+        self.contract_type = Some(contract_type.to_string());
+        self
+    }
+
+    pub fn domain(mut self, param: &'static str, zone: BiddingZone) -> Self {
+        self.domains.retain(|(p, _)| *p != param);
+        self.domains.push((param, zone.eic));
+        self
+    }
+
+    // Same query over a different period, used when splitting backfills into windows.
+    pub fn with_period(&self, start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        Self {
+            period_start: start,
+            period_end: end,
+            ..self.clone()
+        }
+    }
+
+    pub fn to_params(&self) -> HashMap<&'static str, String> {
+        let mut q: HashMap<&'static str, String> = HashMap::new();
+        q.insert("documentType", self.document_type.code().to_string());
+        if let Some(p) = self.process_type {
+            q.insert("processType", p.code().to_string());
+        }
+        if let Some(b) = &self.business_type {
+            q.insert("businessType", b.clone());
+        }
+        if let Some(p) = &self.psr_type {
+            q.insert("psrType", p.clone());
+        }
+        if let Some(c) = &self.contract_type {
+            q.insert("contract_MarketAgreement.Type", c.clone());
+        }
+        for (param, eic) in self.domains.iter() {
+            q.insert(param, eic.to_string());
+        }
+        q.insert(
+            "periodStart",
+            self.period_start.format(PERIOD_FORMAT).to_string(),
+        );
+        q.insert(
+            "periodEnd",
+            self.period_end.format(PERIOD_FORMAT).to_string(),
+        );
+        q
+    }
+
+    // Request URL without token and period, recorded as the source of ingested data.
+    pub fn source_url(&self, base_url: &str) -> String {
+        let mut params: Vec<(&str, String)> = self
+            .to_params()
+            .into_iter()
+            .filter(|(k, _)| *k != "periodStart" && *k != "periodEnd")
+            .collect();
+        params.sort();
+        let query: Vec<String> = params.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        format!("{base_url}?{}", query.join("&"))
+    }
+}
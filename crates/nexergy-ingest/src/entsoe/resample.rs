@@ -0,0 +1,290 @@
+use super::parse::parse_resolution;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDateTime};
+use nexergy_core::time::{column_utc, utc_column};
+use polars::prelude::*;
+use std::collections::BTreeMap;
+
+// Columns identifying one series in the long table produced by parse_timeseries_xml.
+const KEY_COLUMNS: [&str; 8] = [
+    "document_type",
+    "ts_id",
+    "business_type",
+    "area",
+    "in_domain",
+    "out_domain",
+    "psr_type",
+    "unit",
+];
+const KEY_DOCUMENT_TYPE: usize = 0;
+const KEY_UNIT: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    // Time-weighted mean: power (MW) and prices (EUR/MWh).
+    Mean,
+    // Energy (MWh): amounts add up when coarsening and are split pro rata when refining.
+    Energy,
+}
+
+impl Aggregation {
+    // A44 prices are per MWh but must be averaged; other MWH quantities are energy.
+    pub fn for_series(document_type: Option<&str>, unit: Option<&str>) -> Self {
+        match (document_type, unit) {
+            (Some("A44"), _) => Aggregation::Mean,
+            (_, Some("MWH")) => Aggregation::Energy,
+            _ => Aggregation::Mean,
+        }
+    }
+}
+
+// ISO 8601 duration for a resolution step, as ENTSO-E writes it (PT15M, PT60M, P1D).
+pub fn format_resolution(step: Duration) -> String {
+    let minutes = step.num_minutes();
+    if minutes > 0 && minutes % (24 * 60) == 0 {
+        format!("P{}D", minutes / (24 * 60))
+    } else {
+        format!("PT{minutes}M")
+    }
+}
+
+fn str_values(df: &DataFrame, name: &str) -> Result<Vec<Option<String>>> {
+    Ok(df
+        .column(name)?
+        .str()?
+        .into_iter()
+        .map(|v| v.map(str::to_string))
+        .collect())
+}
+
+fn unix_seconds(t: Option<NaiveDateTime>) -> Result<i64> {
+    let t = t.ok_or_else(|| anyhow!("null timestamp in ENTSO-E table"))?;
+    Ok(t.and_utc().timestamp())
+}
+
+// Bucket start (unix seconds) -> (accumulated value, covered seconds)
+type Buckets = BTreeMap<i64, (f64, f64)>;
+
+fn accumulate(buckets: &mut Buckets, t0: i64, t1: i64, v: f64, step: i64, agg: Aggregation) {
+    if t1 <= t0 {
+        return;
+    }
+    let mut b = t0.div_euclid(step) * step;
+    while b < t1 {
+        let overlap = (t1.min(b + step) - t0.max(b)) as f64;
+        let e = buckets.entry(b).or_insert((0.0, 0.0));
+        match agg {
+            Aggregation::Mean => e.0 += v * overlap,
+            Aggregation::Energy => e.0 += v * overlap / (t1 - t0) as f64,
+        }
+        e.1 += overlap;
+        b += step;
+    }
+}
+
+#[derive(Default)]
+struct Output {
+    keys: [Vec<Option<String>>; 8],
+    start: Vec<NaiveDateTime>,
+    end: Vec<NaiveDateTime>,
+    resolution: Vec<String>,
+    position: Vec<i64>,
+    quantity: Vec<f64>,
+}
+
+impl Output {
+    fn push_group(
+        &mut self,
+        key: &[Option<String>],
+        buckets: &Buckets,
+        step: i64,
+        agg: Aggregation,
+    ) {
+        let res = format_resolution(Duration::seconds(step));
+        for (&b, &(acc, covered)) in buckets.iter() {
+            let value = match agg {
+                Aggregation::Mean => acc / covered,
+                Aggregation::Energy => acc,
+            };
+            let t0 = DateTime::from_timestamp(b, 0)
+                .unwrap_or_default()
+                .naive_utc();
+            let t1 = t0 + Duration::seconds(step);
+            for (col, k) in self.keys.iter_mut().zip(key.iter()) {
+                col.push(k.clone());
+            }
+            self.start.push(t0);
+            self.end.push(t1);
+            self.resolution.push(res.clone());
+            self.position.push(b.rem_euclid(86_400) / step + 1);
+            self.quantity.push(value);
+        }
+    }
+
+    fn into_df(self) -> Result<DataFrame> {
+        let mut cols: Vec<Column> = Vec::with_capacity(KEY_COLUMNS.len() + 5);
+        for (name, values) in KEY_COLUMNS.iter().zip(self.keys) {
+            cols.push(Column::new((*name).into(), values));
+        }
+        cols.push(utc_column("start", &self.start)?);
+        cols.push(utc_column("end", &self.end)?);
+        cols.push(Column::new("resolution".into(), self.resolution));
+        cols.push(Column::new("position".into(), self.position));
+        cols.push(Column::new("quantity".into(), self.quantity));
+        Ok(DataFrame::new(cols)?)
+    }
+}
+
+// Resample the long table of parse_timeseries_xml to one resolution (e.g. "PT60M" or "PT15M").
+// Each series is aggregated per Aggregation::for_series: MW and prices by time-weighted mean, MWh by
+// energy. `position` becomes the 1-based index of the step within its UTC day.
+pub fn resample_timeseries(df: &DataFrame, resolution: &str) -> Result<DataFrame> {
+    let step = parse_resolution(resolution)?.num_seconds();
+    if step <= 0 {
+        return Err(anyhow!("invalid resample resolution '{resolution}'"));
+    }
+    let mut keys: Vec<Vec<Option<String>>> = Vec::with_capacity(KEY_COLUMNS.len());
+    for name in KEY_COLUMNS {
+        keys.push(str_values(df, name)?);
+    }
+    let start = column_utc(df.column("start")?)?;
+    let end = column_utc(df.column("end")?)?;
+    let quantity = df.column("quantity")?.f64()?.clone();
+
+    let mut groups: BTreeMap<Vec<Option<String>>, Buckets> = BTreeMap::new();
+    for i in 0..df.height() {
+        let Some(v) = quantity.get(i) else { continue };
+        let key: Vec<Option<String>> = keys.iter().map(|k| k[i].clone()).collect();
+        let agg =
+            Aggregation::for_series(key[KEY_DOCUMENT_TYPE].as_deref(), key[KEY_UNIT].as_deref());
+        let (t0, t1) = (unix_seconds(start[i])?, unix_seconds(end[i])?);
+        accumulate(groups.entry(key).or_default(), t0, t1, v, step, agg);
+    }
+
+    let mut out = Output::default();
+    for (key, buckets) in groups.iter() {
+        let agg =
+            Aggregation::for_series(key[KEY_DOCUMENT_TYPE].as_deref(), key[KEY_UNIT].as_deref());
+        out.push_group(key, buckets, step, agg);
+    }
+    out.into_df()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::parse_timeseries_xml;
+
+    fn series(id: &str, unit: &str, resolution: &str, end: &str, values: &[f64]) -> String {
+        let points: String = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                format!(
+                    "<Point><position>{}</position><quantity>{v}</quantity></Point>",
+                    i + 1
+                )
+            })
+            .collect();
+        format!(
+            r#"<TimeSeries>
+    <mRID>{id}</mRID>
+    <quantity_Measure_Unit.name>{unit}</quantity_Measure_Unit.name>
+    <Period>
+      <timeInterval><start>2024-01-01T00:00Z</start><end>{end}</end></timeInterval>
+      <resolution>{resolution}</resolution>
+      {points}
+    </Period>
+  </TimeSeries>"#
+        )
+    }
+
+    fn document(doc_type: &str, series: &[String]) -> DataFrame {
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<GL_MarketDocument>
+  <mRID>1</mRID>
+  <type>{doc_type}</type>
+  {}
+</GL_MarketDocument>"#,
+            series.concat()
+        );
+        parse_timeseries_xml(&xml).unwrap()
+    }
+
+    // (position, quantity) of one series in a resampled table.
+    fn values(df: &DataFrame, ts_id: &str) -> Vec<(i64, f64)> {
+        let ids = df.column("ts_id").unwrap().str().unwrap();
+        let position = df.column("position").unwrap().i64().unwrap();
+        let quantity = df.column("quantity").unwrap().f64().unwrap();
+        (0..df.height())
+            .filter(|&i| ids.get(i) == Some(ts_id))
+            .map(|i| (position.get(i).unwrap(), quantity.get(i).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn quarter_hours_are_averaged_for_power_and_summed_for_energy() {
+        let quarters = [100.0, 200.0, 300.0, 400.0, 10.0, 10.0, 10.0, 10.0];
+        let df = document(
+            "A75",
+            &[
+                series("mw", "MAW", "PT15M", "2024-01-01T02:00Z", &quarters),
+                series("mwh", "MWH", "PT15M", "2024-01-01T02:00Z", &quarters),
+            ],
+        );
+        let hourly = resample_timeseries(&df, "PT60M").unwrap();
+        assert_eq!(values(&hourly, "mw"), vec![(1, 250.0), (2, 10.0)]);
+        assert_eq!(values(&hourly, "mwh"), vec![(1, 1000.0), (2, 40.0)]);
+        let resolution = hourly.column("resolution").unwrap().str().unwrap();
+        assert!(resolution.into_iter().all(|r| r == Some("PT60M")));
+    }
+
+    #[test]
+    fn refining_repeats_power_and_splits_energy() {
+        let df = document(
+            "A75",
+            &[
+                series("mw", "MAW", "PT60M", "2024-01-01T01:00Z", &[100.0]),
+                series("mwh", "MWH", "PT60M", "2024-01-01T01:00Z", &[100.0]),
+            ],
+        );
+        let quarters = resample_timeseries(&df, "PT15M").unwrap();
+        assert_eq!(
+            values(&quarters, "mw"),
+            vec![(1, 100.0), (2, 100.0), (3, 100.0), (4, 100.0)]
+        );
+        assert_eq!(
+            values(&quarters, "mwh"),
+            vec![(1, 25.0), (2, 25.0), (3, 25.0), (4, 25.0)]
+        );
+    }
+
+    #[test]
+    fn prices_per_mwh_are_averaged() {
+        let df = document(
+            "A44",
+            &[series(
+                "price",
+                "MWH",
+                "PT15M",
+                "2024-01-01T01:00Z",
+                &[40.0, 60.0, 40.0, 60.0],
+            )],
+        );
+        let hourly = resample_timeseries(&df, "PT60M").unwrap();
+        assert_eq!(values(&hourly, "price"), vec![(1, 50.0)]);
+        assert_eq!(
+            Aggregation::for_series(Some("A44"), Some("MWH")),
+            Aggregation::Mean
+        );
+    }
+
+    #[test]
+    fn resolutions_are_written_the_way_entsoe_does() {
+        assert_eq!(format_resolution(Duration::minutes(15)), "PT15M");
+        assert_eq!(format_resolution(Duration::hours(1)), "PT60M");
+        assert_eq!(format_resolution(Duration::days(1)), "P1D");
+        assert!(resample_timeseries(&DataFrame::empty(), "PT0M").is_err());
+    }
+}
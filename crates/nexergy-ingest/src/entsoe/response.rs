@@ -0,0 +1,119 @@
+use super::error::{from_http_status, parse_acknowledgement, EntsoeError};
+use reqwest::blocking::Client;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+// Local file header signature of a ZIP archive.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+pub(crate) fn is_zip(body: &[u8]) -> bool {
+    body.starts_with(ZIP_MAGIC)
+}
+
+// Unpack every XML document of a ZIP archive (outage queries return one file per document).
+pub fn unzip_documents(body: &[u8]) -> Result<Vec<String>, EntsoeError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(body))?;
+    let mut docs: Vec<String> = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() || !file.name().to_ascii_lowercase().ends_with(".xml") {
+            continue;
+        }
+        let mut xml = String::new();
+        file.read_to_string(&mut xml)?;
+        docs.push(xml);
+    }
+    Ok(docs)
+}
+
+// Split a successful response body into XML documents, surfacing acknowledgements as errors.
+pub(crate) fn documents_from_body(body: Vec<u8>) -> Result<Vec<String>, EntsoeError> {
+    let docs = if is_zip(&body) {
+        unzip_documents(&body)?
+    } else {
+        vec![String::from_utf8(body)?]
+    };
+    for doc in docs.iter() {
+        if let Some(err) = parse_acknowledgement(doc) {
+            return Err(err);
+        }
+    }
+    Ok(docs)
+}
+
+pub(crate) fn get_documents(
+    client: &Client,
+    base_url: &str,
+    params: &HashMap<&str, String>,
+) -> Result<Vec<String>, EntsoeError> {
+    let mut resp = client.get(base_url).query(params).send()?;
+    let status = resp.status();
+    let mut body: Vec<u8> = Vec::new();
+    resp.read_to_end(&mut body)?;
+    if !status.is_success() {
+        return Err(from_http_status(
+            status.as_u16(),
+            &String::from_utf8_lossy(&body),
+        ));
+    }
+    documents_from_body(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("docs/", SimpleFileOptions::default())
+            .unwrap();
+        for (name, body) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(body.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    const NO_DATA: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Acknowledgement_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-1:acknowledgementdocument:7:0">
+  <mRID>ack-1</mRID>
+  <Reason>
+    <code>999</code>
+    <text>No matching data found for Data item Unavailability of Generation Units.</text>
+  </Reason>
+</Acknowledgement_MarketDocument>"#;
+
+    #[test]
+    fn unzip_returns_the_xml_documents_in_archive_order() {
+        let body = archive(&[
+            ("docs/first.xml", "<a/>"),
+            ("readme.txt", "not a document"),
+            ("docs/second.XML", "<b/>"),
+        ]);
+        assert!(is_zip(&body));
+        assert_eq!(unzip_documents(&body).unwrap(), vec!["<a/>", "<b/>"]);
+    }
+
+    #[test]
+    fn plain_bodies_are_one_document() {
+        let docs = documents_from_body(b"<a/>".to_vec()).unwrap();
+        assert_eq!(docs, vec!["<a/>"]);
+        assert!(!is_zip(b"<a/>"));
+    }
+
+    #[test]
+    fn acknowledgements_inside_an_archive_are_errors() {
+        let body = archive(&[("docs/first.xml", "<a/>"), ("docs/ack.xml", NO_DATA)]);
+        let err = documents_from_body(body).unwrap_err();
+        assert!(err.is_no_data(), "{err}");
+    }
+
+    #[test]
+    fn truncated_archives_are_zip_errors() {
+        let mut body = archive(&[("docs/first.xml", "<a/>")]);
+        body.truncate(body.len() / 2);
+        assert!(matches!(unzip_documents(&body), Err(EntsoeError::Zip(_))));
+    }
+}
@@ -0,0 +1,56 @@
+use super::query::BiddingZone;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use nexergy_core::io::LakeTable;
+use nexergy_core::time::{column_utc, utc_column};
+use polars::prelude::*;
+use std::collections::BTreeMap;
+
+// NL and the neighbouring zones whose day-ahead prices feed the price model.
+pub const NL_PRICE_ZONES: [&str; 5] = ["NL", "DE-LU", "BE", "FR", "NO2"];
+
+// Day-ahead prices of one zone from the long A44 table of parse_timeseries_xml (optionally resampled):
+// zone, datetime_utc, price_eur_mwh, resolution, one row per interval start and resolution.
+// `datetime_utc` has the type of the curated NL prices (Datetime, UTC) so the two join directly. A44 documents may
+// repeat an interval across TimeSeries (classification sequences); repeated values are averaged.
+pub fn zone_prices(df: &DataFrame, zone: BiddingZone) -> Result<DataFrame> {
+    let start = column_utc(df.column("start")?)?;
+    let resolution = df.column("resolution")?.str()?;
+    let quantity = df.column("quantity")?.f64()?;
+
+    // (start, resolution) -> (sum, count)
+    let mut by_start: BTreeMap<(NaiveDateTime, &str), (f64, usize)> = BTreeMap::new();
+    for (i, s) in start.into_iter().enumerate() {
+        let (Some(s), Some(r), Some(v)) = (s, resolution.get(i), quantity.get(i)) else {
+            continue;
+        };
+        let e = by_start.entry((s, r)).or_insert((0.0, 0));
+        e.0 += v;
+        e.1 += 1;
+    }
+
+    let n = by_start.len();
+    let starts: Vec<NaiveDateTime> = by_start.keys().map(|k| k.0).collect();
+    let out = DataFrame::new(vec![
+        Column::new("zone".into(), vec![zone.name; n]),
+        utc_column("datetime_utc", &starts)?,
+        Column::new(
+            "price_eur_mwh".into(),
+            by_start
+                .values()
+                .map(|v| v.0 / v.1 as f64)
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "resolution".into(),
+            by_start.keys().map(|k| k.1).collect::<Vec<_>>(),
+        ),
+    ])?;
+    Ok(out)
+}
+
+pub fn zone_prices_lake_table() -> LakeTable {
+    LakeTable::new("datetime_utc")
+        .partition_by(&["zone"])
+        .primary_key(&["zone", "datetime_utc", "resolution"])
+}
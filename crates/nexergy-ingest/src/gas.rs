@@ -1,4 +1,7 @@
 use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use nexergy_core::io::LakeTable;
+use nexergy_core::time::{local_column, parse_timestamp, utc_column, wall_clock_to_utc, LOCAL_TZ};
 use polars::prelude::*;
 use serde::Deserialize;
 use std::{fs, path::Path};
@@ -19,52 +22,33 @@ pub fn read_gas_json_array_to_df<P: AsRef<Path>>(path: P) -> Result<DataFrame> {
     let data = fs::read_to_string(path.as_ref())?;
     let rows: Vec<GasRowRaw> = serde_json::from_str(&data)?;
 
-    let mut datetime_local: Vec<String> = Vec::with_capacity(rows.len());
+    let mut instants: Vec<NaiveDateTime> = Vec::with_capacity(rows.len());
     let mut price_eur_mwh: Vec<f64> = Vec::with_capacity(rows.len());
 
     for r in rows.into_iter() {
-        datetime_local.push(r.datum);
+        // `datum` is Dutch wall-clock time.
+        let t = parse_timestamp(&r.datum)
+            .and_then(|t| wall_clock_to_utc(t, LOCAL_TZ))
+            .ok_or_else(|| anyhow!("parse datum '{}'", r.datum))?;
+        instants.push(t);
         price_eur_mwh.push(parse_price_eur_mwh(&r.prijs_excl_belastingen)?);
     }
 
-    let df = df!(
-        "datetime_local" => datetime_local,
-        "price_eur_mwh" => price_eur_mwh,
-    )?;
+    let df = DataFrame::new(vec![
+        local_column("datetime_local", &instants)?,
+        utc_column("datetime_utc", &instants)?,
+        Column::new("price_eur_mwh".into(), price_eur_mwh),
+    ])?;
     Ok(df)
 }
 
-pub fn write_partitioned_by_date<P: AsRef<Path>>(df: &DataFrame, out_dir: P) -> Result<()> {
-    let out_dir = out_dir.as_ref();
-    std::fs::create_dir_all(out_dir)?;
-
-    let s = df.column("datetime_local")?;
-    let mut by_date: std::collections::BTreeMap<String, Vec<usize>> = Default::default();
-    for idx in 0..df.height() {
-        let av = s.get(idx)?;
-        let val = match av {
-            AnyValue::String(v) => v,
-            AnyValue::StringOwned(ref v) => v.as_str(),
-            _ => continue,
-        };
-        if val.len() >= 10 {
-            let date = &val[0..10];
-            by_date.entry(date.to_string()).or_default().push(idx);
-        }
-    }
+// Curated gas prices, one row per delivery timestamp, partitioned by local day.
+pub fn lake_table() -> LakeTable {
+    LakeTable::new("datetime_local").primary_key(&["datetime_local"])
+}
 
-    for (date, indices) in by_date.into_iter() {
-        let idx_vec: Vec<u32> = indices.into_iter().map(|i| i as u32).collect();
-        let take_idx = UInt32Chunked::from_vec("idx".into(), idx_vec);
-        let mut part = df.take(&take_idx)?;
-        let path = out_dir.join(format!("dt={}/part-0001.parquet", date));
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let mut file = std::fs::File::create(&path)?;
-        ParquetWriter::new(&mut file)
-            .with_compression(ParquetCompression::Zstd(None))
-            .finish(&mut part)?;
-    }
-    Ok(())
+// Gas prices with valid_time/knowledge_time (see nexergy_core::asof), one row per delivery timestamp
+// and version.
+pub fn bitemporal_lake_table() -> LakeTable {
+    LakeTable::bitemporal(&[])
 }
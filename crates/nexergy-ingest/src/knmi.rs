@@ -1,12 +1,12 @@
 use anyhow::{anyhow, Result};
+use nexergy_core::io::LakeTable;
 use polars::prelude::*;
 use reqwest::blocking::Client;
 use std::collections::HashMap;
 use std::io::Read;
-use std::path::Path;
 
-const KNMI_HOURLY_URL: &str = "https://www.daggegevens.knmi.nl/klimatologie/uurgegevens";
-const KNMI_DAILY_URL: &str = "https://www.daggegevens.knmi.nl/klimatologie/daggegevens";
+pub const KNMI_HOURLY_URL: &str = "https://www.daggegevens.knmi.nl/klimatologie/uurgegevens";
+pub const KNMI_DAILY_URL: &str = "https://www.daggegevens.knmi.nl/klimatologie/daggegevens";
 
 fn post_csv(url: &str, form: &HashMap<&str, String>) -> Result<String> {
     let client = Client::builder()
@@ -20,6 +20,19 @@ fn post_csv(url: &str, form: &HashMap<&str, String>) -> Result<String> {
     Ok(body)
 }
 
+// KNMI pads the header with spaces (e.g. `   HH`); names are trimmed so keys can refer to them.
+fn read_csv(csv: &str) -> Result<DataFrame> {
+    let csv = csv_without_comments(csv);
+    let mut df = CsvReader::new(std::io::Cursor::new(csv)).finish()?;
+    let names: Vec<String> = df
+        .get_column_names()
+        .iter()
+        .map(|n| n.trim().to_string())
+        .collect();
+    df.set_column_names(names)?;
+    Ok(df)
+}
+
 fn csv_without_comments(s: &str) -> String {
     s.lines()
         .filter(|l| !l.trim_start().starts_with('#'))
@@ -41,9 +54,7 @@ pub fn fetch_knmi_hourly_to_df(
     form.insert("fmt", "csv".to_string());
 
     let csv = post_csv(KNMI_HOURLY_URL, &form)?;
-    let csv = csv_without_comments(&csv);
-    let df = CsvReader::new(std::io::Cursor::new(csv)).finish()?;
-    Ok(df)
+    read_csv(&csv)
 }
 
 pub fn fetch_knmi_daily_to_df(start: &str, end: &str, stns: &str, vars: &str) -> Result<DataFrame> {
@@ -55,9 +66,7 @@ pub fn fetch_knmi_daily_to_df(start: &str, end: &str, stns: &str, vars: &str) ->
     form.insert("fmt", "csv".to_string());
 
     let csv = post_csv(KNMI_DAILY_URL, &form)?;
-    let csv = csv_without_comments(&csv);
-    let df = CsvReader::new(std::io::Cursor::new(csv)).finish()?;
-    Ok(df)
+    read_csv(&csv)
 }
 
 fn normalize_date_str(df: &DataFrame) -> Result<(DataFrame, String)> {
@@ -110,49 +119,20 @@ fn normalize_date_str(df: &DataFrame) -> Result<(DataFrame, String)> {
     Ok((df2, "date_str".to_string()))
 }
 
-pub fn write_partitioned_by_date<P: AsRef<Path>>(
-    df: &DataFrame,
-    date_col: &str,
-    out_dir: P,
-) -> Result<()> {
-    let out_dir = out_dir.as_ref();
-    std::fs::create_dir_all(out_dir)?;
-
-    // Normalize/ensure a YYYY-MM-DD string column exists
-    let (df2, date_col_norm) =
-        if date_col == "date_str" && df.get_column_names().iter().any(|n| n == &"date_str") {
-            (df.clone(), date_col.to_string())
-        } else {
-            normalize_date_str(df)?
-        };
-
-    let s = df2.column(&date_col_norm)?;
-    let mut by_date: std::collections::BTreeMap<String, Vec<usize>> = Default::default();
-    for idx in 0..df2.height() {
-        let av = s.get(idx)?;
-        let v = match av {
-            AnyValue::String(v) => v,
-            AnyValue::StringOwned(ref v) => v.as_str(),
-            _ => continue,
-        };
-        if v.len() >= 10 {
-            let date = &v[0..10];
-            by_date.entry(date.to_string()).or_default().push(idx);
-        }
+// Add a YYYY-MM-DD `date_str` column (detected from the YYYYMMDD column) unless it exists.
+pub fn with_date_column(df: &DataFrame) -> Result<DataFrame> {
+    if df.get_column_names().iter().any(|n| n == &"date_str") {
+        return Ok(df.clone());
     }
+    Ok(normalize_date_str(df)?.0)
+}
 
-    for (date, indices) in by_date.into_iter() {
-        let idx_vec: Vec<u32> = indices.into_iter().map(|i| i as u32).collect();
-        let take_idx = UInt32Chunked::from_vec("idx".into(), idx_vec);
-        let mut part = df2.take(&take_idx)?;
-        let path = out_dir.join(format!("dt={}/part-0001.parquet", date));
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let mut file = std::fs::File::create(&path)?;
-        ParquetWriter::new(&mut file)
-            .with_compression(ParquetCompression::Zstd(None))
-            .finish(&mut part)?;
-    }
-    Ok(())
+// KNMI hourly observations partitioned on `date_str`; a record is one station and hour (HH 1-24).
+pub fn hourly_lake_table() -> LakeTable {
+    LakeTable::new("date_str").primary_key(&["STN", "date_str", "HH"])
+}
+
+// KNMI daily observations partitioned on `date_str`; a record is one station and day.
+pub fn daily_lake_table() -> LakeTable {
+    LakeTable::new("date_str").primary_key(&["STN", "date_str"])
 }
@@ -0,0 +1,289 @@
+use crate::day_ahead::BASELINE_DAYS;
+use crate::{
+    build_features_until, target_series, BaselineScore, DayAheadModel, ForecastMode, Regressor,
+    TrainEvalOptions, TrainEvalResult,
+};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use nexergy_core::manifest::tool_version;
+use nexergy_core::time::{column_utc, utc_to_wall_clock, wall_clock_to_utc, LOCAL_TZ};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Bumped whenever a saved model can no longer be read the same way.
+pub const ARTIFACT_VERSION: u32 = 1;
+
+// JSON has no NaN: non-finite floats are written as null and read back as NaN.
+pub(crate) mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &f64, s: S) -> Result<S::Ok, S::Error> {
+        if v.is_finite() {
+            s.serialize_f64(*v)
+        } else {
+            s.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<f64, D::Error> {
+        Ok(Option::<f64>::deserialize(d)?.unwrap_or(f64::NAN))
+    }
+}
+
+// First and last delivery time (UTC) of the training rows.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TrainingWindow {
+    pub first: NaiveDateTime,
+    pub last: NaiveDateTime,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SavedModel {
+    OneStep(Regressor),
+    DayAhead(DayAheadModel),
+}
+
+// Test-set metrics of the saved model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactMetrics {
+    #[serde(with = "nan_as_null")]
+    pub mae: f64,
+    #[serde(with = "nan_as_null")]
+    pub rmse: f64,
+    pub test_n: usize,
+    pub baselines: Vec<BaselineScore>,
+}
+
+// A fitted model with everything needed to forecast with it again: the options that built its
+// features (feature names and standardization statistics are in the model), the training window
+// and its test metrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelArtifact {
+    pub artifact_version: u32,
+    pub tool_version: String,
+    pub created_at: String,
+    pub options: TrainEvalOptions,
+    pub training: TrainingWindow,
+    pub metrics: Option<ArtifactMetrics>,
+    pub model: SavedModel,
+}
+
+impl ModelArtifact {
+    // Artifact of the model fitted by train_eval with `opts`; fails when no model was fitted. Without
+    // test rows the artifact has no metrics.
+    pub fn new(opts: &TrainEvalOptions, result: &TrainEvalResult) -> Result<Self> {
+        let model = match (&result.model, &result.day_ahead_model) {
+            (Some(m), _) => SavedModel::OneStep(m.clone()),
+            (None, Some(m)) => SavedModel::DayAhead(m.clone()),
+            (None, None) => return Err(anyhow!("no model was fitted (no training rows)")),
+        };
+        let training = result
+            .training_window
+            .ok_or_else(|| anyhow!("no training rows"))?;
+        Ok(Self {
+            artifact_version: ARTIFACT_VERSION,
+            tool_version: tool_version(),
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            options: opts.clone(),
+            training,
+            metrics: (result.test_n > 0).then(|| ArtifactMetrics {
+                mae: result.mae,
+                rmse: result.rmse,
+                test_n: result.test_n,
+                baselines: result.baselines.clone(),
+            }),
+            model,
+        })
+    }
+
+    pub fn feature_names(&self) -> &[String] {
+        match &self.model {
+            SavedModel::OneStep(m) => m.feature_names(),
+            SavedModel::DayAhead(m) => &m.feature_names,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let version = serde_json::from_str::<serde_json::Value>(&text)
+            .map_err(|e| anyhow!("invalid model artifact {}: {e}", path.display()))?
+            .get("artifact_version")
+            .and_then(|v| v.as_u64());
+        if version != Some(u64::from(ARTIFACT_VERSION)) {
+            return Err(anyhow!(
+                "model artifact {} has version {version:?}, expected {ARTIFACT_VERSION}",
+                path.display()
+            ));
+        }
+        serde_json::from_str(&text)
+            .map_err(|e| anyhow!("invalid model artifact {}: {e}", path.display()))
+    }
+}
+
+// Days of prices before a delivery day that its features look back on.
+fn lookback_days(opts: &TrainEvalOptions) -> i64 {
+    let steps = opts
+        .neighbours
+        .iter()
+        .flat_map(|nb| nb.lags.iter())
+        .chain([&opts.lags])
+        .max()
+        .copied()
+        .unwrap_or(0);
+    let step_days = (opts.step * steps as i32).num_days() + 1;
+    let days = match opts.mode {
+        ForecastMode::OneStep => 0,
+        ForecastMode::DayAhead => opts
+            .day_lags
+            .iter()
+            .chain(BASELINE_DAYS.iter())
+            .max()
+            .copied()
+            .unwrap_or(0) as i64,
+    };
+    step_days.max(days) + 1
+}
+
+// Forecast every period of local delivery day `date` with a saved model. Features are rebuilt from
+// `prices_dir` (and the artifact's neighbour and as-of sources) with the options the model was
+// trained with, so steps without a price yet are forecast too as long as their inputs are known; in
+// one-step mode that needs the previous step's price. Returns the delivery time, `forecast` and
+// `actual` (null where not known yet).
+pub fn predict(prices_dir: &Path, artifact: &ModelArtifact, date: NaiveDate) -> Result<DataFrame> {
+    let day_start = |d: NaiveDate| {
+        wall_clock_to_utc(d.and_time(NaiveTime::MIN), LOCAL_TZ)
+            .ok_or_else(|| anyhow!("no local midnight on {d}"))
+    };
+    let next = date + Duration::days(1);
+    let opts = TrainEvalOptions {
+        from: Some(date - Duration::days(lookback_days(&artifact.options))),
+        to: Some(next),
+        ..artifact.options.clone()
+    };
+    let features = build_features_until(prices_dir, &opts, Some(day_start(next)?))?;
+    if features.feature_cols != artifact.feature_names() {
+        return Err(anyhow!(
+            "features built from the data ({}) do not match the model's ({})",
+            features.feature_cols.join(","),
+            artifact.feature_names().join(",")
+        ));
+    }
+    let times = column_utc(features.df.column(&opts.date_col)?)?;
+    let mask = BooleanChunked::from_iter_values(
+        "mask".into(),
+        times
+            .iter()
+            .map(|t| t.is_some_and(|t| utc_to_wall_clock(t, LOCAL_TZ).date() == date)),
+    );
+    let day = features.df.filter(&mask)?;
+    let forecast = match &artifact.model {
+        SavedModel::OneStep(m) => m.predict_df(&day)?,
+        SavedModel::DayAhead(m) => m.predict_df(&day)?,
+    };
+    Ok(DataFrame::new(vec![
+        day.column(&opts.date_col)?.clone(),
+        forecast.with_name("forecast".into()).into(),
+        target_series(&day, &opts.target_col)?
+            .with_name("actual".into())
+            .into(),
+    ])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::tests::{date, write_prices, Scratch};
+    use crate::train_eval;
+
+    // Hourly prices of December 2023 to train on and the first days of 2024 to test on.
+    fn fitted(name: &str) -> (Scratch, TrainEvalOptions, TrainEvalResult) {
+        fitted_until(name, 2023)
+    }
+
+    fn fitted_until(name: &str, cutoff_year: i32) -> (Scratch, TrainEvalOptions, TrainEvalResult) {
+        let scratch = Scratch::new(name);
+        write_prices(&scratch.0, "2023-11-30 23:00", "2024-01-10 00:00");
+        let opts = TrainEvalOptions {
+            cutoff_year,
+            ..TrainEvalOptions::default()
+        };
+        let result = train_eval(&scratch.0, &opts).unwrap();
+        (scratch, opts, result)
+    }
+
+    #[test]
+    fn saved_models_forecast_exactly_as_before() {
+        let (scratch, opts, result) = fitted("artifact-roundtrip");
+        assert!(result.train_n > 0 && result.test_n > 0);
+        let artifact = ModelArtifact::new(&opts, &result).unwrap();
+        let path = scratch.0.join("model.json");
+        artifact.save(&path).unwrap();
+        let loaded = ModelArtifact::load(&path).unwrap();
+        assert_eq!(loaded.feature_names(), artifact.feature_names());
+        assert_eq!(loaded.training.rows, result.train_n);
+        assert_eq!(loaded.metrics.as_ref().unwrap().test_n, result.test_n);
+
+        let day = date("2024-01-08");
+        let before = predict(&scratch.0, &artifact, day).unwrap();
+        let after = predict(&scratch.0, &loaded, day).unwrap();
+        assert_eq!(before.height(), 24);
+        assert_eq!(before.column("forecast").unwrap().null_count(), 0);
+        assert!(before.equals_missing(&after));
+    }
+
+    #[test]
+    fn artifacts_of_another_version_are_rejected() {
+        let (scratch, opts, result) = fitted("artifact-version");
+        let path = scratch.0.join("model.json");
+        ModelArtifact::new(&opts, &result)
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        let mut json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        json["artifact_version"] = serde_json::json!(ARTIFACT_VERSION + 1);
+        std::fs::write(&path, json.to_string()).unwrap();
+        let err = ModelArtifact::load(&path).unwrap_err().to_string();
+        assert!(err.contains("expected 1"), "{err}");
+    }
+
+    #[test]
+    fn non_finite_metrics_round_trip_as_null() {
+        let metrics = ArtifactMetrics {
+            mae: f64::NAN,
+            rmse: 2.5,
+            test_n: 0,
+            baselines: Vec::new(),
+        };
+        let json = serde_json::to_string(&metrics).unwrap();
+        assert!(json.contains(r#""mae":null"#), "{json}");
+        let back: ArtifactMetrics = serde_json::from_str(&json).unwrap();
+        assert!(back.mae.is_nan());
+        assert_eq!(back.rmse, 2.5);
+    }
+
+    #[test]
+    fn final_fits_without_test_rows_save_without_metrics() {
+        // Every row is a training row.
+        let (scratch, opts, result) = fitted_until("artifact-final", 2024);
+        assert_eq!(result.test_n, 0);
+        assert!(result.mae.is_nan() && result.baselines.is_empty());
+        let artifact = ModelArtifact::new(&opts, &result).unwrap();
+        assert!(artifact.metrics.is_none());
+        assert_eq!(artifact.training.rows, result.train_n);
+
+        let path = scratch.0.join("model.json");
+        artifact.save(&path).unwrap();
+        let loaded = ModelArtifact::load(&path).unwrap();
+        assert!(loaded.metrics.is_none());
+        let forecast = predict(&scratch.0, &loaded, date("2024-01-08")).unwrap();
+        assert_eq!(forecast.column("forecast").unwrap().null_count(), 0);
+    }
+}
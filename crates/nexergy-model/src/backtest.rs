@@ -0,0 +1,391 @@
+use crate::{
+    build_features, fit_model, mean_absolute_error, root_mean_squared_error, score_baselines,
+    target_series, BaselineScore, Features, ForecastMode, TrainEvalOptions,
+};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use nexergy_core::asof::gate_closure;
+use nexergy_core::io::LakeTable;
+use nexergy_core::time::{column_utc, datetime_column, utc_to_wall_clock, LOCAL_TZ};
+use polars::prelude::*;
+use rayon::prelude::*;
+use std::path::Path;
+
+// Columns of the forecast table besides the delivery time, forecast and actual.
+pub const ISSUE_TIME: &str = "issue_time";
+pub const FIT_DAY: &str = "fit_day";
+
+// Training rows used at each re-fit, by local delivery day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrainWindow {
+    // Every day before the fit day.
+    Expanding,
+    // The given number of days before the fit day.
+    Rolling(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestOptions {
+    // Features and model (cutoff_year is ignored).
+    pub model: TrainEvalOptions,
+    // Local delivery days forecast, from inclusive, to exclusive.
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    // Days between re-fits; each fit forecasts the days up to the next one.
+    pub refit_days: usize,
+    pub window: TrainWindow,
+}
+
+pub struct BacktestResult {
+    // One row per forecast: delivery time, ISSUE_TIME, FIT_DAY, `forecast`, `actual` and one column
+    // per baseline.
+    pub forecasts: DataFrame,
+    pub fits: usize,
+    pub mae: f64,
+    pub rmse: f64,
+    pub baselines: Vec<BaselineScore>,
+    pub missing_steps: usize,
+}
+
+// Lake layout of the forecast table: by delivery day, one row per delivery time and issue time.
+pub fn forecast_table(date_col: &str) -> LakeTable {
+    LakeTable::new(date_col).primary_key(&[date_col, ISSUE_TIME])
+}
+
+// When a forecast for delivery time `t` is made: the gate closure on the day before (day-ahead) or
+// one step before (one-step).
+fn issue_time(t: NaiveDateTime, opts: &TrainEvalOptions) -> Option<NaiveDateTime> {
+    match opts.mode {
+        ForecastMode::OneStep => Some(t - opts.step),
+        ForecastMode::DayAhead => gate_closure(t, opts.gate),
+    }
+}
+
+// Rows of one re-fit: the model fitted on `train` forecasts `test`.
+struct FitBlock {
+    fit_day: NaiveDate,
+    train: Vec<IdxSize>,
+    test: Vec<IdxSize>,
+}
+
+// Re-fit schedule over rows with local delivery days `days`: one block per fit day start,
+// start + refit_days, ... before end, training on the days before the fit day (all, or the last
+// `window` days) and forecasting the days up to the next fit day.
+fn fit_blocks(days: &[Option<NaiveDate>], opts: &BacktestOptions) -> Vec<FitBlock> {
+    let rows_in = |from: Option<NaiveDate>, to: NaiveDate| -> Vec<IdxSize> {
+        days.iter()
+            .enumerate()
+            .filter(
+                |(_, day)| matches!(day, Some(day) if from.is_none_or(|f| *day >= f) && *day < to),
+            )
+            .map(|(i, _)| i as IdxSize)
+            .collect()
+    };
+    let mut blocks = Vec::new();
+    let mut fit_day = opts.start;
+    while fit_day < opts.end {
+        let block_end = (fit_day + Duration::days(opts.refit_days as i64)).min(opts.end);
+        let train_from = match opts.window {
+            TrainWindow::Expanding => None,
+            TrainWindow::Rolling(n) => Some(fit_day - Duration::days(n as i64)),
+        };
+        blocks.push(FitBlock {
+            fit_day,
+            train: rows_in(train_from, fit_day),
+            test: rows_in(Some(fit_day), block_end),
+        });
+        fit_day = block_end;
+    }
+    blocks
+}
+
+// Walk forward through [start, end) in blocks of `refit_days` local days. At the start of every block
+// the model is re-fitted on the days before it (all, or the last `window` days) and forecasts the
+// block, so no forecast uses a price delivered on or after its fit day. Fits run in parallel.
+pub fn backtest(prices_dir: &Path, opts: &BacktestOptions) -> Result<BacktestResult> {
+    if opts.refit_days == 0 {
+        return Err(anyhow!("refit interval must be at least one day"));
+    }
+    if opts.window == TrainWindow::Rolling(0) {
+        return Err(anyhow!("rolling window must be at least one day"));
+    }
+    let model_opts = &opts.model;
+    let (target_col, date_col) = (model_opts.target_col.as_str(), model_opts.date_col.as_str());
+    let Features {
+        df,
+        feature_cols,
+        baselines,
+        missing_steps,
+    } = build_features(prices_dir, model_opts)?;
+    let ts = column_utc(df.column(date_col)?)?;
+    let days: Vec<Option<NaiveDate>> = ts
+        .iter()
+        .map(|t| t.map(|t| utc_to_wall_clock(t, LOCAL_TZ).date()))
+        .collect();
+
+    // (row, forecast, fit day) per forecast; blocks without training or test rows are skipped.
+    let blocks: Vec<Vec<(IdxSize, Option<f64>, NaiveDate)>> = fit_blocks(&days, opts)
+        .into_par_iter()
+        .map(|block| {
+            if block.test.is_empty() || block.train.is_empty() {
+                return Ok(Vec::new());
+            }
+            let train = df.take(&IdxCa::from_vec("idx".into(), block.train))?;
+            let test = df.take(&IdxCa::from_vec("idx".into(), block.test.clone()))?;
+            let model = fit_model(&train, &feature_cols, model_opts)?;
+            let yhat = model.predict_df(&test)?;
+            Ok(block
+                .test
+                .into_iter()
+                .zip(yhat.f64()?)
+                .map(|(i, y)| (i, y, block.fit_day))
+                .collect())
+        })
+        .collect::<Result<_>>()?;
+    let fits = blocks.iter().filter(|b| !b.is_empty()).count();
+    let rows: Vec<(IdxSize, Option<f64>, NaiveDate)> = blocks.into_iter().flatten().collect();
+
+    let idx: Vec<IdxSize> = rows.iter().map(|r| r.0).collect();
+    let picked = df.take(&IdxCa::from_vec("idx".into(), idx))?;
+    let issue: Vec<Option<NaiveDateTime>> = rows
+        .iter()
+        .map(|r| ts[r.0 as usize].and_then(|t| issue_time(t, model_opts)))
+        .collect();
+    let fit_day: Vec<NaiveDate> = rows.iter().map(|r| r.2).collect();
+    let forecast: Vec<Option<f64>> = rows.iter().map(|r| r.1).collect();
+    let mut columns = vec![
+        picked.column(date_col)?.clone(),
+        datetime_column(ISSUE_TIME, issue, "UTC")?,
+        Series::new(FIT_DAY.into(), fit_day).into_column(),
+        Column::new("forecast".into(), forecast),
+        target_series(&picked, target_col)?
+            .with_name("actual".into())
+            .into_column(),
+    ];
+    for (name, col) in baselines.iter() {
+        columns.push(
+            target_series(&picked, col)?
+                .with_name(name.as_str().into())
+                .into_column(),
+        );
+    }
+    let forecasts = DataFrame::new(columns)?;
+
+    let (mae, rmse, baselines) = if forecasts.height() > 0 {
+        let y_true = target_series(&forecasts, "actual")?;
+        let y_pred = target_series(&forecasts, "forecast")?;
+        let named: Vec<(String, String)> = baselines
+            .iter()
+            .map(|(name, _)| (name.clone(), name.clone()))
+            .collect();
+        (
+            mean_absolute_error(&y_true, &y_pred)?,
+            root_mean_squared_error(&y_true, &y_pred)?,
+            score_baselines(&forecasts, "actual", &named)?,
+        )
+    } else {
+        (f64::NAN, f64::NAN, Vec::new())
+    };
+    Ok(BacktestResult {
+        forecasts,
+        fits,
+        mae,
+        rmse,
+        baselines,
+        missing_steps,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::{Datelike, Timelike};
+    use nexergy_core::io::write_partitioned;
+    use nexergy_core::time::utc_column;
+    use std::path::PathBuf;
+
+    // Fresh scratch directory per test, removed when dropped.
+    pub(crate) struct Scratch(pub PathBuf);
+
+    impl Scratch {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("nexergy-model-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    pub(crate) fn utc(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    pub(crate) fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    pub(crate) fn hours(from: &str, to: &str) -> Vec<NaiveDateTime> {
+        let (mut t, end) = (utc(from), utc(to));
+        let mut out = Vec::new();
+        while t < end {
+            out.push(t);
+            t += Duration::hours(1);
+        }
+        out
+    }
+
+    // A daily price shape with cheaper weekends and a little deterministic noise.
+    fn price(t: NaiveDateTime) -> f64 {
+        let local = utc_to_wall_clock(t, LOCAL_TZ);
+        let hour = f64::from(local.hour());
+        let weekend = if local.weekday().num_days_from_monday() >= 5 {
+            -15.0
+        } else {
+            0.0
+        };
+        let noise = ((t.and_utc().timestamp() / 3600 * 7919) % 11) as f64 - 5.0;
+        60.0 + 20.0 * (std::f64::consts::TAU * hour / 24.0).sin() + weekend + noise
+    }
+
+    // Hourly prices over [from, to) UTC written to a prices lake under `dir`.
+    pub(crate) fn write_prices(dir: &Path, from: &str, to: &str) {
+        let ts = hours(from, to);
+        let values: Vec<f64> = ts.iter().map(|t| price(*t)).collect();
+        let df = DataFrame::new(vec![
+            utc_column("datetime_utc", &ts).unwrap(),
+            Column::new("price_eur_mwh".into(), values),
+        ])
+        .unwrap();
+        let table = LakeTable::new("datetime_utc").primary_key(&["datetime_utc"]);
+        write_partitioned(&df, dir, &table).unwrap();
+    }
+
+    fn options(mode: ForecastMode, window: TrainWindow) -> BacktestOptions {
+        BacktestOptions {
+            model: TrainEvalOptions {
+                mode,
+                day_lags: vec![1, 7],
+                ..TrainEvalOptions::default()
+            },
+            start: date("2024-03-20"),
+            end: date("2024-04-10"),
+            refit_days: 5,
+            window,
+        }
+    }
+
+    #[test]
+    fn training_rows_are_known_before_every_forecast_of_their_block() {
+        // Spans the 2024-03-31 DST change.
+        let ts = hours("2024-02-29 23:00", "2024-04-15 00:00");
+        let days: Vec<Option<NaiveDate>> = ts
+            .iter()
+            .map(|t| Some(utc_to_wall_clock(*t, LOCAL_TZ).date()))
+            .collect();
+        for mode in [ForecastMode::DayAhead, ForecastMode::OneStep] {
+            for window in [TrainWindow::Expanding, TrainWindow::Rolling(7)] {
+                let opts = options(mode, window);
+                let blocks = fit_blocks(&days, &opts);
+                let fit_days: Vec<NaiveDate> = blocks.iter().map(|b| b.fit_day).collect();
+                assert_eq!(
+                    fit_days,
+                    [
+                        "2024-03-20",
+                        "2024-03-25",
+                        "2024-03-30",
+                        "2024-04-04",
+                        "2024-04-09"
+                    ]
+                    .map(date)
+                );
+                let mut tested = Vec::new();
+                for b in blocks.iter() {
+                    // A price is known from the day-ahead gate closure of its own delivery day.
+                    let known = b
+                        .train
+                        .iter()
+                        .map(|&r| gate_closure(ts[r as usize], opts.model.gate).unwrap())
+                        .max()
+                        .unwrap();
+                    let last_delivered = b.train.iter().map(|&r| ts[r as usize]).max().unwrap();
+                    for &s in b.test.iter() {
+                        let t = ts[s as usize];
+                        let issued = issue_time(t, &opts.model).unwrap();
+                        assert!(
+                            known < issued,
+                            "{mode:?} {t}: trained on prices known at {known}"
+                        );
+                        assert!(last_delivered < t);
+                        let day = days[s as usize].unwrap();
+                        assert!(day >= b.fit_day && day < b.fit_day + Duration::days(5));
+                    }
+                    let first_train = days[b.train[0] as usize].unwrap();
+                    match window {
+                        TrainWindow::Expanding => assert_eq!(first_train, date("2024-03-01")),
+                        TrainWindow::Rolling(n) => {
+                            assert_eq!(first_train, b.fit_day - Duration::days(n as i64))
+                        }
+                    }
+                    tested.extend(b.test.iter().copied());
+                }
+                // Every delivery hour in [start, end) is forecast exactly once.
+                let expected: Vec<IdxSize> = days
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, d)| matches!(d, Some(d) if *d >= opts.start && *d < opts.end))
+                    .map(|(i, _)| i as IdxSize)
+                    .collect();
+                assert_eq!(tested, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn backtest_forecasts_each_day_once_from_an_earlier_fit() {
+        let dir = Scratch::new("backtest");
+        write_prices(&dir.0, "2024-02-29 23:00", "2024-04-15 00:00");
+        let opts = options(ForecastMode::DayAhead, TrainWindow::Rolling(14));
+        let result = backtest(&dir.0, &opts).unwrap();
+        assert_eq!(result.fits, 5);
+        // 21 local days, one of them 23 hours long; 02:00 on 2024-04-01 and 2024-04-07 has no day lag
+        // (2024-03-31 had no 02:00).
+        assert_eq!(result.forecasts.height(), 21 * 24 - 1 - 2);
+        assert!(result.mae.is_finite() && result.rmse >= result.mae);
+
+        let delivery = column_utc(result.forecasts.column("datetime_utc").unwrap()).unwrap();
+        let issued = column_utc(result.forecasts.column(ISSUE_TIME).unwrap()).unwrap();
+        let fit_day = result
+            .forecasts
+            .column(FIT_DAY)
+            .unwrap()
+            .date()
+            .unwrap()
+            .clone();
+        for i in 0..result.forecasts.height() {
+            let t = delivery[i].unwrap();
+            assert!(issued[i].unwrap() < t);
+            let day = utc_to_wall_clock(t, LOCAL_TZ).date();
+            let fit = fit_day.as_date_iter().nth(i).flatten().unwrap();
+            assert!(fit <= day && day < fit + Duration::days(5));
+        }
+    }
+
+    #[test]
+    fn rejects_empty_refit_intervals_and_windows() {
+        let dir = Scratch::new("backtest-options");
+        let opts = BacktestOptions {
+            refit_days: 0,
+            ..options(ForecastMode::DayAhead, TrainWindow::Expanding)
+        };
+        assert!(backtest(&dir.0, &opts).is_err());
+        let opts = options(ForecastMode::DayAhead, TrainWindow::Rolling(0));
+        assert!(backtest(&dir.0, &opts).is_err());
+    }
+}
@@ -0,0 +1,421 @@
+use crate::day_ahead::period_of_day;
+use crate::{fit_model, target_series, ForecastMode, TrainEvalOptions};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use nexergy_core::time::{column_utc, utc_to_wall_clock, wall_clock_to_utc, LOCAL_TZ};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// How conformal intervals around a point forecast are calibrated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConformalOptions {
+    // Nominal coverages, e.g. 0.9 for an interval that should hold 90% of actuals.
+    pub coverages: Vec<f64>,
+    // The last days of the training period are held out from the point model and its residuals on
+    // them calibrate the intervals; with `rolling` this is also the length of the residual window.
+    pub calibration_days: usize,
+    // Calibrate each forecast only on residuals of the same local hour of day.
+    pub by_hour: bool,
+    // Rolling: every forecast uses the residuals of the latest `calibration_days` before it that are
+    // known when it is made, test residuals included. Split: the held-out residuals only.
+    pub rolling: bool,
+}
+
+impl Default for ConformalOptions {
+    fn default() -> Self {
+        Self {
+            coverages: vec![0.8, 0.9],
+            calibration_days: 90,
+            by_hour: false,
+            rolling: true,
+        }
+    }
+}
+
+// Test-set score of the conformal intervals at one nominal coverage.
+#[derive(Debug, Clone)]
+pub struct ConformalScore {
+    pub nominal: f64,
+    // Share of actuals inside the interval.
+    pub coverage: f64,
+    pub mean_width: f64,
+}
+
+// Columns holding the bounds of the interval with nominal coverage `c`, e.g. lo90 and hi90.
+pub fn conformal_cols(c: f64) -> (String, String) {
+    let pct = (c * 100.0 * 1e6).round() / 1e6;
+    (format!("lo{pct}"), format!("hi{pct}"))
+}
+
+// Residuals of deliveries before this instant may calibrate the forecast for delivery `t`: the
+// prices its features may use (up to the step before in one-step mode, up to the end of the previous
+// local day in day-ahead mode).
+pub(crate) fn known_before(t: NaiveDateTime, opts: &TrainEvalOptions) -> Option<NaiveDateTime> {
+    match opts.mode {
+        ForecastMode::OneStep => Some(t),
+        ForecastMode::DayAhead => wall_clock_to_utc(
+            utc_to_wall_clock(t, LOCAL_TZ)
+                .date()
+                .and_time(NaiveTime::MIN),
+            LOCAL_TZ,
+        ),
+    }
+}
+
+// A residual (actual - forecast) of a past forecast with its delivery time.
+#[derive(Debug, Clone, Copy)]
+pub struct Residual {
+    pub time: NaiveDateTime,
+    pub value: f64,
+}
+
+// Half-widths w of the symmetric intervals forecast ± w for forecasts of the delivery times `targets`,
+// one vector per coverage in `opts.coverages`. `calibration` and `later` are residuals in time
+// order, all of `calibration` before `later`; `cutoffs[i]` is the instant before which residuals
+// are known for target i (see known_before). For coverage c over m absolute residuals, w is the
+// ⌈(m+1)·c⌉-th smallest of them (infinite when that exceeds m), which covers a new residual from the
+// same distribution with probability at least c. None where no residual is available.
+pub fn conformal_half_widths(
+    calibration: &[Residual],
+    later: &[Residual],
+    targets: &[NaiveDateTime],
+    cutoffs: &[NaiveDateTime],
+    opts: &ConformalOptions,
+) -> Result<Vec<Vec<Option<f64>>>> {
+    if let Some(c) = opts.coverages.iter().find(|c| !(**c > 0.0 && **c < 1.0)) {
+        return Err(anyhow!("coverages must be in (0, 1), got {c}"));
+    }
+    if opts.calibration_days == 0 {
+        return Err(anyhow!("calibration window must be at least one day"));
+    }
+    let window = Duration::days(opts.calibration_days as i64);
+    let history: Vec<Residual> = if opts.rolling {
+        calibration.iter().chain(later).copied().collect()
+    } else {
+        calibration.to_vec()
+    };
+    let hour = |t: NaiveDateTime| period_of_day(t, Duration::hours(1));
+    let mut out = vec![Vec::with_capacity(targets.len()); opts.coverages.len()];
+    let mut pool: Vec<f64> = Vec::new();
+    for (&t, &cutoff) in targets.iter().zip(cutoffs) {
+        let rows = if opts.rolling {
+            let to = history.partition_point(|r| r.time < cutoff);
+            let from = history[..to].partition_point(|r| r.time < cutoff - window);
+            &history[from..to]
+        } else {
+            &history[..]
+        };
+        pool.clear();
+        pool.extend(
+            rows.iter()
+                .filter(|r| !opts.by_hour || hour(r.time) == hour(t))
+                .map(|r| r.value.abs()),
+        );
+        let m = pool.len();
+        for (widths, &c) in out.iter_mut().zip(opts.coverages.iter()) {
+            let rank = ((m + 1) as f64 * c).ceil() as usize;
+            widths.push(match m {
+                0 => None,
+                _ if rank > m => Some(f64::INFINITY),
+                _ => Some(*pool.select_nth_unstable_by(rank - 1, f64::total_cmp).1),
+            });
+        }
+    }
+    Ok(out)
+}
+
+// Score intervals forecast ± w (`half_widths` per coverage, as from conformal_half_widths) against
+// the actuals, over the rows with a forecast and a width.
+pub fn conformal_scores(
+    actual: &[f64],
+    forecast: &[Option<f64>],
+    coverages: &[f64],
+    half_widths: &[Vec<Option<f64>>],
+) -> Vec<ConformalScore> {
+    coverages
+        .iter()
+        .zip(half_widths)
+        .map(|(&nominal, widths)| {
+            let (mut inside, mut width, mut n) = (0usize, 0.0, 0usize);
+            for ((y, f), w) in actual.iter().zip(forecast).zip(widths) {
+                if let (Some(f), Some(w)) = (f, w) {
+                    inside += usize::from((y - f).abs() <= *w);
+                    width += 2.0 * w;
+                    n += 1;
+                }
+            }
+            ConformalScore {
+                nominal,
+                coverage: inside as f64 / n as f64,
+                mean_width: width / n as f64,
+            }
+        })
+        .collect()
+}
+
+// Rows of a frame with their actuals and forecasts.
+struct Scored {
+    times: Vec<NaiveDateTime>,
+    actual: Vec<f64>,
+    forecast: Vec<Option<f64>>,
+}
+
+impl Scored {
+    fn new(df: &DataFrame, forecast: &Series, opts: &TrainEvalOptions) -> Result<Self> {
+        let times = column_utc(df.column(&opts.date_col)?)?
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("null timestamp in {}", opts.date_col))?;
+        let actual = target_series(df, &opts.target_col)?
+            .f64()?
+            .iter()
+            .map(|v| v.ok_or_else(|| anyhow!("null target in {}", opts.target_col)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            times,
+            actual,
+            forecast: forecast.f64()?.iter().collect(),
+        })
+    }
+
+    fn residuals(&self) -> Vec<Residual> {
+        self.times
+            .iter()
+            .zip(&self.actual)
+            .zip(&self.forecast)
+            .filter_map(|((&time, y), f)| {
+                Some(Residual {
+                    time,
+                    value: y - (*f)?,
+                })
+            })
+            .collect()
+    }
+}
+
+// Conformal intervals for `test` (rows in time order, as `train`): the model of `opts` is fitted on
+// `train` without its last `calibration_days`, which calibrate the intervals around its test
+// forecasts. Returns the scores and the test rows with date, actual, `yhat` and the bounds of every
+// coverage (see conformal_cols).
+pub(crate) fn conformal_forecasts(
+    train: &DataFrame,
+    test: &DataFrame,
+    feature_cols: &[String],
+    opts: &TrainEvalOptions,
+    conf: &ConformalOptions,
+) -> Result<(Vec<ConformalScore>, DataFrame)> {
+    let train_times = column_utc(train.column(&opts.date_col)?)?;
+    let last = train_times
+        .iter()
+        .flatten()
+        .max()
+        .copied()
+        .ok_or_else(|| anyhow!("no training rows"))?;
+    let split = last - Duration::days(conf.calibration_days as i64);
+    let proper = BooleanChunked::from_iter_values(
+        "mask".into(),
+        train_times.iter().map(|t| t.is_some_and(|t| t <= split)),
+    );
+    let (fit_rows, calibration) = (train.filter(&proper)?, train.filter(&!&proper)?);
+    if fit_rows.height() == 0 || calibration.height() == 0 {
+        return Err(anyhow!(
+            "training period too short for a {}-day calibration window",
+            conf.calibration_days
+        ));
+    }
+    let model = fit_model(&fit_rows, feature_cols, opts)?;
+
+    let cal = Scored::new(&calibration, &model.predict_df(&calibration)?, opts)?;
+    let yhat = model.predict_df(test)?;
+    let scored = Scored::new(test, &yhat, opts)?;
+    let cutoffs: Vec<NaiveDateTime> = scored
+        .times
+        .iter()
+        .map(|&t| known_before(t, opts).unwrap_or(t))
+        .collect();
+    let widths = conformal_half_widths(
+        &cal.residuals(),
+        &scored.residuals(),
+        &scored.times,
+        &cutoffs,
+        conf,
+    )?;
+    let scores = conformal_scores(&scored.actual, &scored.forecast, &conf.coverages, &widths);
+
+    let mut columns = vec![
+        test.column(&opts.date_col)?.clone(),
+        test.column(&opts.target_col)?.clone(),
+        yhat.into(),
+    ];
+    for (&c, w) in conf.coverages.iter().zip(widths.iter()) {
+        let (lo, hi) = conformal_cols(c);
+        let bound = |sign: f64| -> Vec<Option<f64>> {
+            scored
+                .forecast
+                .iter()
+                .zip(w)
+                .map(|(f, w)| Some(f.as_ref()? + sign * w.as_ref()?))
+                .collect()
+        };
+        columns.push(Column::new(lo.into(), bound(-1.0)));
+        columns.push(Column::new(hi.into(), bound(1.0)));
+    }
+    Ok((scores, DataFrame::new(columns)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn hour(k: i64) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2024-01-01 00:00", "%Y-%m-%d %H:%M").unwrap()
+            + Duration::hours(k)
+    }
+
+    fn residuals(from: i64, values: &[f64]) -> Vec<Residual> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(k, &value)| Residual {
+                time: hour(from + k as i64),
+                value,
+            })
+            .collect()
+    }
+
+    fn gaussian(rng: &mut StdRng, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|_| {
+                let (u, v): (f64, f64) = (rng.random(), rng.random());
+                (-2.0 * (1.0 - u).ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+            })
+            .collect()
+    }
+
+    fn options(coverages: &[f64], rolling: bool) -> ConformalOptions {
+        ConformalOptions {
+            coverages: coverages.to_vec(),
+            calibration_days: 30,
+            by_hour: false,
+            rolling,
+        }
+    }
+
+    #[test]
+    fn split_intervals_cover_their_level_of_iid_noise() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let calibration = residuals(0, &gaussian(&mut rng, 500));
+        let actual = gaussian(&mut rng, 20_000);
+        let targets: Vec<NaiveDateTime> = (0..actual.len() as i64).map(|k| hour(500 + k)).collect();
+        let coverages = [0.5, 0.8, 0.9];
+        let widths = conformal_half_widths(
+            &calibration,
+            &[],
+            &targets,
+            &targets,
+            &options(&coverages, false),
+        )
+        .unwrap();
+        let forecast = vec![Some(0.0); actual.len()];
+        for score in conformal_scores(&actual, &forecast, &coverages, &widths) {
+            // At least the level and at most 1/(m+1) above it, up to the sampling error of the
+            // calibration residuals.
+            assert!(
+                (score.coverage - score.nominal).abs() < 0.04,
+                "{} covered {}",
+                score.nominal,
+                score.coverage
+            );
+        }
+        // The 90% half-width of standard normal noise is about 1.645.
+        assert!((widths[2][0].unwrap() - 1.645).abs() < 0.15);
+    }
+
+    #[test]
+    fn rolling_intervals_only_use_residuals_known_before_the_cutoff() {
+        let calibration = residuals(0, &[1.0; 9]);
+        let later = residuals(9, &[100.0, 100.0]);
+        let targets = [hour(9), hour(11)];
+        let widths = conformal_half_widths(
+            &calibration,
+            &later,
+            &targets,
+            &targets,
+            &options(&[0.9], true),
+        )
+        .unwrap();
+        // Nine small residuals before hour 9; eleven before hour 11, two of them large.
+        assert_eq!(widths[0], vec![Some(1.0), Some(100.0)]);
+
+        // Split intervals never see the later residuals.
+        let widths = conformal_half_widths(
+            &calibration,
+            &later,
+            &targets,
+            &targets,
+            &options(&[0.9], false),
+        )
+        .unwrap();
+        assert_eq!(widths[0], vec![Some(1.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn rolling_windows_and_hours_restrict_the_residual_pool() {
+        // Two days of residuals: 5 on the first day, 1 on the second, 0.5 at 06:00 UTC.
+        let mut values = vec![5.0; 24];
+        values.extend(vec![1.0; 24]);
+        values[24 + 6] = 0.5;
+        let history = residuals(0, &values);
+        let targets = [hour(48), hour(48 + 6)];
+        let opts = ConformalOptions {
+            calibration_days: 1,
+            ..options(&[0.5], true)
+        };
+        let widths = conformal_half_widths(&history, &[], &targets, &targets, &opts).unwrap();
+        assert_eq!(widths[0][0], Some(1.0));
+
+        let by_hour = ConformalOptions {
+            by_hour: true,
+            calibration_days: 2,
+            ..opts
+        };
+        let widths = conformal_half_widths(&history, &[], &targets, &targets, &by_hour).unwrap();
+        // 00:00 UTC is 01:00 local: residuals 5 and 1; 06:00 UTC: residuals 5 and 0.5.
+        assert_eq!(widths[0], vec![Some(5.0), Some(5.0)]);
+    }
+
+    #[test]
+    fn too_few_residuals_give_unbounded_or_no_intervals() {
+        let calibration = residuals(0, &[1.0, 2.0, 3.0]);
+        let targets = [hour(3)];
+        let widths = conformal_half_widths(
+            &calibration,
+            &[],
+            &targets,
+            &targets,
+            &options(&[0.5, 0.9], false),
+        )
+        .unwrap();
+        // ⌈4·0.5⌉ = 2nd smallest; ⌈4·0.9⌉ = 4 > 3 residuals.
+        assert_eq!(widths, vec![vec![Some(2.0)], vec![Some(f64::INFINITY)]]);
+
+        let none =
+            conformal_half_widths(&[], &[], &targets, &targets, &options(&[0.9], true)).unwrap();
+        assert_eq!(none, vec![vec![None]]);
+
+        assert!(conformal_half_widths(
+            &calibration,
+            &[],
+            &targets,
+            &targets,
+            &options(&[1.0], true)
+        )
+        .is_err());
+        assert_eq!(
+            conformal_cols(0.9),
+            ("lo90".to_string(), "hi90".to_string())
+        );
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{fit_regressor, Estimator, Regressor};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Timelike};
+use nexergy_core::time::{column_utc, utc_to_wall_clock, wall_clock_to_utc, LOCAL_TZ};
+use polars::prelude::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// Column with the delivery period of a row within its local day (0..24 for hours, 0..96 for quarter
+// hours, by wall-clock time; the repeated hour when DST ends shares its period).
+pub const PERIOD_COL: &str = "period";
+
+// Day lags always computed for the target, for the same-hour-yesterday and -last-week baselines.
+pub const BASELINE_DAYS: [usize; 2] = [1, 7];
+
+pub fn periods_per_day(step: Duration) -> usize {
+    (86_400 / step.num_seconds().max(1)) as usize
+}
+
+pub(crate) fn period_of_day(t: NaiveDateTime, step: Duration) -> u32 {
+    let local = utc_to_wall_clock(t, LOCAL_TZ);
+    (i64::from(local.num_seconds_from_midnight()) / step.num_seconds().max(1)) as u32
+}
+
+// Add PERIOD_COL for the timestamps in `time_col`.
+pub fn with_period(df: &DataFrame, time_col: &str, step: Duration) -> Result<DataFrame> {
+    let periods: Vec<Option<u32>> = column_utc(df.column(time_col)?)?
+        .into_iter()
+        .map(|t| t.map(|t| period_of_day(t, step)))
+        .collect();
+    let mut out = df.clone();
+    out.with_column(Column::new(PERIOD_COL.into(), periods))?;
+    Ok(out)
+}
+
+// Name of the value of `col` at the same wall-clock time `days` local days earlier.
+pub fn day_lag_name(col: &str, days: usize) -> String {
+    format!("{col}_d{days}")
+}
+
+// The same wall-clock time `days` local days earlier (so DST changes keep the delivery hour); None
+// when that time does not exist.
+fn same_time_days_before(t: NaiveDateTime, days: usize) -> Option<NaiveDateTime> {
+    let local = utc_to_wall_clock(t, LOCAL_TZ) - Duration::days(days as i64);
+    wall_clock_to_utc(local, LOCAL_TZ)
+}
+
+// Values of `cols` at the same delivery period `days` days earlier (`<col>_d<days>`), looked up by
+// timestamp. With days >= 1 these are all known at the day-ahead gate on D-1.
+pub fn day_lag_features(
+    df: &DataFrame,
+    time_col: &str,
+    cols: &[String],
+    days: &[usize],
+) -> Result<(DataFrame, Vec<String>)> {
+    let ts = column_utc(df.column(time_col)?)?;
+    let mut out = df.clone();
+    let mut added = Vec::with_capacity(cols.len() * days.len());
+    for c in cols {
+        let values: HashMap<NaiveDateTime, f64> = ts
+            .iter()
+            .zip(df.column(c)?.f64()?)
+            .filter_map(|(t, v)| Some(((*t)?, v?)))
+            .collect();
+        for &d in days {
+            let name = day_lag_name(c, d);
+            let lagged: Vec<Option<f64>> = ts
+                .iter()
+                .map(|t| {
+                    let prev = same_time_days_before((*t)?, d)?;
+                    values.get(&prev).copied()
+                })
+                .collect();
+            out.with_column(Column::new(name.as_str().into(), lagged))?;
+            added.push(name);
+        }
+    }
+    Ok((out, added))
+}
+
+// Mean, min, max and last value of `col` over the previous local day (`<col>_d1_mean`, ...): the
+// most recent prices known at the gate.
+pub fn previous_day_stats(
+    df: &DataFrame,
+    time_col: &str,
+    col: &str,
+) -> Result<(DataFrame, Vec<String>)> {
+    let ts = column_utc(df.column(time_col)?)?;
+    let local_day = |t: NaiveDateTime| utc_to_wall_clock(t, LOCAL_TZ).date();
+    // day -> (sum, count, min, max, (last time, last value))
+    type DayStats = (f64, usize, f64, f64, (NaiveDateTime, f64));
+    type Stat = fn(&DayStats) -> f64;
+    let mut days: BTreeMap<NaiveDate, DayStats> = BTreeMap::new();
+    for (t, v) in ts.iter().zip(df.column(col)?.f64()?) {
+        let (Some(t), Some(v)) = (*t, v) else {
+            continue;
+        };
+        let e =
+            days.entry(local_day(t))
+                .or_insert((0.0, 0, f64::INFINITY, f64::NEG_INFINITY, (t, v)));
+        e.0 += v;
+        e.1 += 1;
+        e.2 = e.2.min(v);
+        e.3 = e.3.max(v);
+        if t >= e.4 .0 {
+            e.4 = (t, v);
+        }
+    }
+    let prev: Vec<Option<&DayStats>> = ts
+        .iter()
+        .map(|t| days.get(&local_day((*t)?).pred_opt()?))
+        .collect();
+    let base = day_lag_name(col, 1);
+    let stats: [(&str, Stat); 4] = [
+        ("mean", |s| s.0 / s.1 as f64),
+        ("min", |s| s.2),
+        ("max", |s| s.3),
+        ("last", |s| s.4 .1),
+    ];
+    let mut out = df.clone();
+    let mut added = Vec::with_capacity(stats.len());
+    for (suffix, f) in stats {
+        let name = format!("{base}_{suffix}");
+        let values: Vec<Option<f64>> = prev.iter().map(|s| s.map(f)).collect();
+        out.with_column(Column::new(name.as_str().into(), values))?;
+        added.push(name);
+    }
+    Ok((out, added))
+}
+
+// One model per delivery period; a delivery day's periods are forecast together from the
+// information available at the gate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayAheadModel {
+    pub feature_names: Vec<String>,
+    // Indexed by period; None where training had no rows for the period.
+    pub periods: Vec<Option<Regressor>>,
+}
+
+impl DayAheadModel {
+    // Features with a non-zero weight in at least one linear period model, with the number of such
+    // models.
+    pub fn kept_features(&self) -> Vec<(&str, usize)> {
+        self.feature_names
+            .iter()
+            .enumerate()
+            .map(|(j, name)| {
+                let n = self
+                    .periods
+                    .iter()
+                    .flatten()
+                    .filter(|m| matches!(m, Regressor::Linear(m) if m.weights[j + 1] != 0.0))
+                    .count();
+                (name.as_str(), n)
+            })
+            .filter(|(_, n)| *n > 0)
+            .collect()
+    }
+
+    pub fn predict_df(&self, df: &DataFrame) -> Result<Series> {
+        let period = df.column(PERIOD_COL)?.u32()?;
+        let feats: Vec<Float64Chunked> = self
+            .feature_names
+            .iter()
+            .map(|c| Ok(df.column(c)?.f64()?.clone()))
+            .collect::<Result<_>>()?;
+        let mut x = vec![0.0; feats.len()];
+        let out: Vec<Option<f64>> = (0..df.height())
+            .map(|i| {
+                let model = self.periods.get(period.get(i)? as usize)?.as_ref()?;
+                for (xj, f) in x.iter_mut().zip(feats.iter()) {
+                    *xj = f.get(i).unwrap_or(0.0);
+                }
+                Some(model.predict_row(&x))
+            })
+            .collect();
+        Ok(Series::new("yhat".into(), out))
+    }
+}
+
+// Fit one model per period of `train` (see with_period).
+pub fn fit_day_ahead(
+    train: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    step: Duration,
+    estimator: &Estimator,
+) -> Result<DayAheadModel> {
+    let period = train.column(PERIOD_COL)?.u32()?;
+    let n = periods_per_day(step);
+    let mut rows: Vec<Vec<IdxSize>> = vec![Vec::new(); n];
+    for (i, p) in period.into_iter().enumerate() {
+        match p {
+            Some(p) if (p as usize) < n => rows[p as usize].push(i as IdxSize),
+            Some(p) => return Err(anyhow!("period {p} out of range for {n} periods per day")),
+            None => {}
+        }
+    }
+    let periods = rows
+        .into_par_iter()
+        .map(|idx| {
+            if idx.is_empty() {
+                return Ok(None);
+            }
+            let part = train.take(&IdxCa::from_vec("idx".into(), idx))?;
+            fit_regressor(&part, target_col, feature_cols, estimator).map(Some)
+        })
+        .collect::<Result<_>>()?;
+    Ok(DayAheadModel {
+        feature_names: feature_cols.to_vec(),
+        periods,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+    use nexergy_core::asof::gate_closure;
+    use nexergy_core::time::utc_column;
+
+    fn utc(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    // Hourly prices from `from` to `to` (UTC, inclusive) whose value is the delivery time in hours
+    // since the epoch, so every feature value names the delivery hour it came from.
+    fn prices(from: &str, to: &str) -> DataFrame {
+        let (mut t, end) = (utc(from), utc(to));
+        let mut ts = Vec::new();
+        while t <= end {
+            ts.push(t);
+            t += Duration::hours(1);
+        }
+        let values: Vec<f64> = ts.iter().map(|t| hours_since_epoch(*t)).collect();
+        DataFrame::new(vec![
+            utc_column("t", &ts).unwrap(),
+            Column::new("price".into(), values),
+        ])
+        .unwrap()
+    }
+
+    fn hours_since_epoch(t: NaiveDateTime) -> f64 {
+        (t.and_utc().timestamp() / 3600) as f64
+    }
+
+    fn delivered(value: f64) -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(value as i64 * 3600, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn periods(df: &DataFrame) -> Vec<u32> {
+        with_period(df, "t", Duration::hours(1))
+            .unwrap()
+            .column(PERIOD_COL)
+            .unwrap()
+            .u32()
+            .unwrap()
+            .into_no_null_iter()
+            .collect()
+    }
+
+    #[test]
+    fn the_short_day_skips_period_2_and_the_long_day_repeats_it() {
+        // Local 2024-03-31 (23 hours) and 2024-10-27 (25 hours).
+        let short = periods(&prices("2024-03-30 23:00", "2024-03-31 21:00"));
+        let mut expected: Vec<u32> = (0..24).collect();
+        expected.retain(|p| *p != 2);
+        assert_eq!(short, expected);
+
+        let long = periods(&prices("2024-10-26 22:00", "2024-10-27 22:00"));
+        let mut expected: Vec<u32> = (0..24).collect();
+        expected.insert(2, 2);
+        assert_eq!(long, expected);
+
+        assert_eq!(periods_per_day(Duration::minutes(15)), 96);
+        // 01:45 local in winter is 00:45 UTC.
+        assert_eq!(
+            period_of_day(utc("2024-01-10 00:45"), Duration::minutes(15)),
+            7
+        );
+    }
+
+    #[test]
+    fn day_lags_keep_the_wall_clock_hour_across_dst() {
+        let df = prices("2024-03-29 23:00", "2024-04-01 21:00");
+        let (out, added) = day_lag_features(&df, "t", &["price".to_string()], &[1]).unwrap();
+        assert_eq!(added, vec!["price_d1"]);
+        let lag = out.column("price_d1").unwrap().f64().unwrap();
+        let ts = column_utc(out.column("t").unwrap()).unwrap();
+        let at = |s: &str| {
+            let i = ts.iter().position(|t| *t == Some(utc(s))).unwrap();
+            lag.get(i).map(delivered)
+        };
+        // 03:00 local on the short day lags 03:00 local the day before (02:00 UTC then).
+        assert_eq!(at("2024-03-31 01:00"), Some(utc("2024-03-30 02:00")));
+        // 02:00 local did not exist on the short day.
+        assert_eq!(at("2024-04-01 00:00"), None);
+        assert_eq!(at("2024-04-01 01:00"), Some(utc("2024-03-31 01:00")));
+    }
+
+    #[test]
+    fn gate_features_come_from_days_already_priced_at_the_gate() {
+        let df = prices("2024-10-20 22:00", "2024-10-29 22:00");
+        let (df, mut cols) =
+            day_lag_features(&df, "t", &["price".to_string()], &[1, 2, 7]).unwrap();
+        let (df, stats) = previous_day_stats(&df, "t", "price").unwrap();
+        cols.extend(stats.into_iter().filter(|c| !c.ends_with("_mean")));
+        let gate = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        let ts = column_utc(df.column("t").unwrap()).unwrap();
+        let mut checked = 0;
+        for c in cols.iter() {
+            for (t, v) in ts.iter().zip(df.column(c).unwrap().f64().unwrap()) {
+                let (Some(t), Some(v)) = (*t, v) else {
+                    continue;
+                };
+                // A price is published after the gate closure of its own delivery day, so it may only
+                // feed a forecast issued at a later gate.
+                let source = delivered(v);
+                assert!(
+                    gate_closure(source, gate) < gate_closure(t, gate),
+                    "{c} at {t} uses the price delivered at {source}"
+                );
+                checked += 1;
+            }
+        }
+        assert!(checked > 500);
+    }
+
+    #[test]
+    fn previous_day_stats_cover_the_whole_local_day() {
+        // The day after the 25-hour 2024-10-27.
+        let df = prices("2024-10-26 22:00", "2024-10-28 22:00");
+        let (out, added) = previous_day_stats(&df, "t", "price").unwrap();
+        assert_eq!(
+            added,
+            vec![
+                "price_d1_mean",
+                "price_d1_min",
+                "price_d1_max",
+                "price_d1_last"
+            ]
+        );
+        let ts = column_utc(out.column("t").unwrap()).unwrap();
+        let i = ts
+            .iter()
+            .position(|t| *t == Some(utc("2024-10-27 23:00")))
+            .unwrap();
+        let stat = |name: &str| out.column(name).unwrap().f64().unwrap().get(i).unwrap();
+        let (first, last) = (utc("2024-10-26 22:00"), utc("2024-10-27 22:00"));
+        assert_eq!(delivered(stat("price_d1_min")), first);
+        assert_eq!(delivered(stat("price_d1_max")), last);
+        assert_eq!(delivered(stat("price_d1_last")), last);
+        let mean = (hours_since_epoch(first) + hours_since_epoch(last)) / 2.0;
+        assert!((stat("price_d1_mean") - mean).abs() < 1e-9);
+        // The first local day has no previous day.
+        assert_eq!(
+            out.column("price_d1_mean").unwrap().f64().unwrap().get(0),
+            None
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use crate::ridge::{Moments, MIN_VARIANCE};
+use crate::{LinearModel, Standardized};
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+// How the penalty of an elastic net path is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LambdaSelection {
+    // Expanding-window time-series cross-validation: the rows (in time order) are cut into folds + 1
+    // blocks and fold i trains on blocks 0..=i and is scored on block i + 1.
+    TimeSeriesCv { folds: usize },
+    // Bayesian information criterion n·ln(MSE) + k·ln(n) on the training rows, k non-zero weights.
+    Bic,
+    // Akaike information criterion n·ln(MSE) + 2k.
+    Aic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ElasticNetOptions {
+    // Share of the penalty that is L1: 1 is the lasso, smaller values mix in ridge.
+    pub l1_ratio: f64,
+    // Path length; penalties are spaced geometrically from the smallest that zeroes every weight
+    // down to `lambda_min_ratio` times it.
+    pub n_lambdas: usize,
+    pub lambda_min_ratio: f64,
+    pub selection: LambdaSelection,
+    // Coordinate descent stops when no weight moves more than `tol` in a sweep.
+    pub max_sweeps: usize,
+    pub tol: f64,
+}
+
+impl Default for ElasticNetOptions {
+    fn default() -> Self {
+        Self {
+            l1_ratio: 1.0,
+            n_lambdas: 50,
+            lambda_min_ratio: 1e-3,
+            selection: LambdaSelection::TimeSeriesCv { folds: 5 },
+            max_sweeps: 1000,
+            tol: 1e-6,
+        }
+    }
+}
+
+// One penalty on the path with its score (mean validation MSE or information criterion, lower is
+// better; both on the standardized target).
+#[derive(Debug, Clone)]
+pub struct PathPoint {
+    pub lambda: f64,
+    pub nonzero: usize,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegularizationPath {
+    pub points: Vec<PathPoint>,
+    // Index of the selected point.
+    pub selected: usize,
+}
+
+impl RegularizationPath {
+    pub fn lambda(&self) -> f64 {
+        self.points[self.selected].lambda
+    }
+}
+
+pub struct ElasticNetFit {
+    pub model: LinearModel,
+    pub path: RegularizationPath,
+}
+
+impl LinearModel {
+    // Features with a non-zero weight.
+    pub fn kept_features(&self) -> Vec<&str> {
+        self.feature_names
+            .iter()
+            .zip(self.weights.iter().skip(1))
+            .filter(|(_, w)| **w != 0.0)
+            .map(|(n, _)| n.as_str())
+            .collect()
+    }
+}
+
+fn soft_threshold(z: f64, t: f64) -> f64 {
+    if z > t {
+        z - t
+    } else if z < -t {
+        z + t
+    } else {
+        0.0
+    }
+}
+
+// Weights at every penalty of `lambdas` (decreasing), each solve warm-started from the previous one.
+// Coordinate descent on (1/2)·wᵀGw - bᵀw + λ·(α·|w|₁ + (1-α)/2·|w|²) with G = XᵀX/n, b = Xᵀy/n,
+// keeping q = Gw up to date so every coordinate step costs O(p).
+fn path(m: &Moments, lambdas: &[f64], opts: &ElasticNetOptions) -> Vec<Vec<f64>> {
+    let p = m.xty.len();
+    let mut w = vec![0.0; p];
+    let mut q = vec![0.0; p];
+    let mut out = Vec::with_capacity(lambdas.len());
+    for &lambda in lambdas {
+        let l1 = lambda * opts.l1_ratio;
+        let l2 = lambda * (1.0 - opts.l1_ratio);
+        for _ in 0..opts.max_sweeps {
+            let mut max_step = 0.0_f64;
+            for j in 0..p {
+                let gjj = m.gram[j * p + j];
+                if gjj <= MIN_VARIANCE {
+                    continue;
+                }
+                let z = m.xty[j] - q[j] + gjj * w[j];
+                let wj = soft_threshold(z, l1) / (gjj + l2);
+                let step = wj - w[j];
+                if step != 0.0 {
+                    for (k, qk) in q.iter_mut().enumerate() {
+                        *qk += step * m.gram[k * p + j];
+                    }
+                    w[j] = wj;
+                    max_step = max_step.max(step.abs());
+                }
+            }
+            if max_step < opts.tol {
+                break;
+            }
+        }
+        out.push(w.clone());
+    }
+    out
+}
+
+// Mean squared error of centred weights `w` (fitted on `m`) on standardized rows `rows`.
+fn validation_mse(data: &Standardized, m: &Moments, w: &[f64], rows: Range<usize>) -> f64 {
+    let w0 = m.intercept(w);
+    let mut x = vec![0.0; w.len()];
+    let n = rows.len();
+    let sse: f64 = rows
+        .map(|i| {
+            data.row_into(i, &mut x);
+            let yhat = w0 + x.iter().zip(w).map(|(x, w)| x * w).sum::<f64>();
+            (data.y(i) - yhat).powi(2)
+        })
+        .sum();
+    sse / n as f64
+}
+
+// Elastic net (lasso at l1_ratio 1) on standardized features, fitted by coordinate descent along a
+// warm-started path of penalties; the penalty is selected per `opts.selection` and the returned
+// model uses the weights at that penalty (zero weights drop their feature). Rows must be in time
+// order for cross-validation.
+pub fn fit_elastic_net(
+    df: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    opts: &ElasticNetOptions,
+) -> Result<ElasticNetFit> {
+    if !(opts.l1_ratio > 0.0 && opts.l1_ratio <= 1.0) {
+        return Err(anyhow!("l1_ratio must be in (0, 1], got {}", opts.l1_ratio));
+    }
+    if opts.n_lambdas == 0 || !(opts.lambda_min_ratio > 0.0 && opts.lambda_min_ratio < 1.0) {
+        return Err(anyhow!(
+            "need at least one penalty and a lambda_min_ratio in (0, 1)"
+        ));
+    }
+    let data = Standardized::new(df, target_col, feature_cols)?;
+    let n = data.len();
+    if n == 0 {
+        return Err(anyhow!("no training rows"));
+    }
+    let full = Moments::new(&data, 0..n);
+    let lambda_max = full.xty.iter().fold(0.0_f64, |a, b| a.max(b.abs())) / opts.l1_ratio;
+    let lambda_max = if lambda_max > 0.0 { lambda_max } else { 1.0 };
+    let lambdas: Vec<f64> = (0..opts.n_lambdas)
+        .map(|i| {
+            let t = if opts.n_lambdas > 1 {
+                i as f64 / (opts.n_lambdas - 1) as f64
+            } else {
+                0.0
+            };
+            lambda_max * opts.lambda_min_ratio.powf(t)
+        })
+        .collect();
+    let weights = path(&full, &lambdas, opts);
+    let nonzero: Vec<usize> = weights
+        .iter()
+        .map(|w| w.iter().filter(|v| **v != 0.0).count())
+        .collect();
+
+    let scores: Vec<f64> = match opts.selection {
+        LambdaSelection::TimeSeriesCv { folds } => {
+            let blocks = folds + 1;
+            if folds == 0 || n < 2 * blocks {
+                return Err(anyhow!(
+                    "{n} rows are too few for {folds}-fold time-series cross-validation"
+                ));
+            }
+            let bound = |b: usize| n * b / blocks;
+            let mut sum = vec![0.0; lambdas.len()];
+            for f in 1..=folds {
+                let train = Moments::new(&data, 0..bound(f));
+                for (s, w) in sum.iter_mut().zip(path(&train, &lambdas, opts)) {
+                    *s += validation_mse(&data, &train, &w, bound(f)..bound(f + 1));
+                }
+            }
+            sum.into_iter().map(|s| s / folds as f64).collect()
+        }
+        LambdaSelection::Bic | LambdaSelection::Aic => {
+            let per_weight = if opts.selection == LambdaSelection::Bic {
+                (n as f64).ln()
+            } else {
+                2.0
+            };
+            weights
+                .iter()
+                .zip(nonzero.iter())
+                .map(|(w, &k)| {
+                    let mse = full.mse(w).max(f64::MIN_POSITIVE);
+                    n as f64 * mse.ln() + per_weight * k as f64
+                })
+                .collect()
+        }
+    };
+    let selected = scores
+        .iter()
+        .enumerate()
+        .fold(0, |best, (i, s)| if *s < scores[best] { i } else { best });
+    let points = lambdas
+        .iter()
+        .zip(nonzero)
+        .zip(scores.iter())
+        .map(|((&lambda, nonzero), &score)| PathPoint {
+            lambda,
+            nonzero,
+            score,
+        })
+        .collect();
+
+    let mut w = Vec::with_capacity(feature_cols.len() + 1);
+    w.push(full.intercept(&weights[selected]));
+    w.extend_from_slice(&weights[selected]);
+    Ok(ElasticNetFit {
+        model: data.into_model(feature_cols, w),
+        path: RegularizationPath { points, selected },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridge::tests::{features, raw_coefficients, synthetic};
+
+    fn options(selection: LambdaSelection) -> ElasticNetOptions {
+        ElasticNetOptions {
+            selection,
+            ..ElasticNetOptions::default()
+        }
+    }
+
+    #[test]
+    fn path_starts_at_the_smallest_penalty_that_zeroes_every_weight() {
+        let df = synthetic(200, 1.0, &[1.0, -2.0, 0.5], 2.0);
+        for l1_ratio in [1.0, 0.5] {
+            let opts = ElasticNetOptions {
+                l1_ratio,
+                ..options(LambdaSelection::Bic)
+            };
+            let fit = fit_elastic_net(&df, "y", &features(3), &opts).unwrap();
+            let points = &fit.path.points;
+            assert_eq!(points[0].nonzero, 0);
+            assert_eq!(points.last().unwrap().nonzero, 3);
+
+            // Just below lambda_max the strongest feature enters.
+            let data = Standardized::new(&df, "y", &features(3)).unwrap();
+            let m = Moments::new(&data, 0..data.len());
+            let below = path(&m, &[points[0].lambda * 0.99], &opts);
+            assert_eq!(below[0].iter().filter(|w| **w != 0.0).count(), 1);
+        }
+    }
+
+    #[test]
+    fn small_penalty_recovers_known_weights() {
+        let weights = [2.0, -0.5, 1.25];
+        let df = synthetic(300, 3.0, &weights, 0.0);
+        let opts = ElasticNetOptions {
+            lambda_min_ratio: 1e-7,
+            tol: 1e-10,
+            ..options(LambdaSelection::Bic)
+        };
+        let fit = fit_elastic_net(&df, "y", &features(3), &opts).unwrap();
+        assert_eq!(fit.path.selected, opts.n_lambdas - 1);
+        let (intercept, slopes) = raw_coefficients(&fit.model);
+        assert!((intercept - 3.0).abs() < 1e-3, "{intercept}");
+        for (b, w) in slopes.iter().zip(weights) {
+            assert!((b - w).abs() < 1e-3, "{b} vs {w}");
+        }
+    }
+
+    #[test]
+    fn selection_drops_or_shrinks_irrelevant_features() {
+        let df = synthetic(400, 0.0, &[3.0, 0.0, 0.0, 0.0], 1.0);
+        let fit = fit_elastic_net(&df, "y", &features(4), &options(LambdaSelection::Bic)).unwrap();
+        assert_eq!(fit.model.kept_features(), vec!["x0"]);
+        assert!(fit.path.points[fit.path.selected].nonzero < 4);
+
+        // Cross-validation favours prediction over sparsity, so irrelevant weights may stay small.
+        let cv = LambdaSelection::TimeSeriesCv { folds: 4 };
+        let fit = fit_elastic_net(&df, "y", &features(4), &options(cv)).unwrap();
+        let (_, slopes) = raw_coefficients(&fit.model);
+        assert!((slopes[0] - 3.0).abs() < 0.1, "{slopes:?}");
+        assert!(slopes[1..].iter().all(|b| b.abs() < 0.1), "{slopes:?}");
+    }
+
+    #[test]
+    fn collinear_features_are_fitted() {
+        let mut df = synthetic(100, 1.0, &[2.0], 0.5);
+        let doubled = df.column("x0").unwrap().as_materialized_series() * 2.0;
+        df.with_column(doubled.with_name("x1".into())).unwrap();
+        let fit = fit_elastic_net(&df, "y", &features(2), &options(LambdaSelection::Aic)).unwrap();
+        assert!(fit.model.weights.iter().all(|w| w.is_finite()));
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let df = synthetic(20, 0.0, &[1.0], 0.1);
+        let bad = [
+            ElasticNetOptions {
+                l1_ratio: 0.0,
+                ..ElasticNetOptions::default()
+            },
+            ElasticNetOptions {
+                lambda_min_ratio: 1.0,
+                ..ElasticNetOptions::default()
+            },
+            options(LambdaSelection::TimeSeriesCv { folds: 15 }),
+        ];
+        for opts in bad {
+            assert!(fit_elastic_net(&df, "y", &features(1), &opts).is_err());
+        }
+    }
+}
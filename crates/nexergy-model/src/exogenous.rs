@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use nexergy_core::asof::{asof_join, gate_closure, AsOfMatch, KNOWLEDGE_TIME, VALID_TIME};
+use nexergy_core::scan::{read_lake, LakeScan};
+use nexergy_core::time::{column_utc, datetime_column};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// Column holding each row's decision time (the day-ahead gate closure for its delivery hour).
+pub const DECISION_TIME: &str = "decision_time";
+
+// Feature read from a bitemporal dataset (written with `--knowledge`) and joined as of the decision
+// time, so forecasts, weather and fuel prices enter as they were known before gate closure.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AsOfFeature {
+    // Output column name.
+    pub name: String,
+    pub dir: PathBuf,
+    pub value_col: String,
+    pub mode: AsOfMatch,
+    // Equality filters selecting one series (e.g. area, psr_type).
+    pub filters: Vec<(String, String)>,
+}
+
+// `NAME=DIR[,value=COLUMN][,mode=exact|latest][,COLUMN=VALUE...]`; value defaults to `quantity`,
+// mode to `exact`, and other pairs filter the dataset.
+impl FromStr for AsOfFeature {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(',');
+        let (name, dir) = parts
+            .next()
+            .and_then(|p| p.split_once('='))
+            .ok_or_else(|| anyhow!("as-of feature '{s}' must start with NAME=DIR"))?;
+        let mut feature = AsOfFeature {
+            name: name.to_string(),
+            dir: PathBuf::from(dir),
+            value_col: "quantity".to_string(),
+            mode: AsOfMatch::Exact,
+            filters: Vec::new(),
+        };
+        for part in parts {
+            let (k, v) = part.split_once('=').ok_or_else(|| {
+                anyhow!("expected KEY=VALUE in as-of feature '{s}', got '{part}'")
+            })?;
+            match k {
+                "value" => feature.value_col = v.to_string(),
+                "mode" => {
+                    feature.mode = match v {
+                        "exact" => AsOfMatch::Exact,
+                        "latest" => AsOfMatch::Latest,
+                        _ => return Err(anyhow!("unknown as-of mode '{v}' (exact or latest)")),
+                    }
+                }
+                _ => feature.filters.push((k.to_string(), v.to_string())),
+            }
+        }
+        Ok(feature)
+    }
+}
+
+// Add the decision time of every row: `gate` local time on the day before the local day of `time_col`.
+pub fn with_decision_time(df: &DataFrame, time_col: &str, gate: NaiveTime) -> Result<DataFrame> {
+    let decision: Vec<Option<NaiveDateTime>> = column_utc(df.column(time_col)?)?
+        .into_iter()
+        .map(|t| t.and_then(|t| gate_closure(t, gate)))
+        .collect();
+    let mut out = df.clone();
+    out.with_column(datetime_column(DECISION_TIME, decision, "UTC")?)?;
+    Ok(out)
+}
+
+fn filter_eq(df: &DataFrame, filters: &[(String, String)]) -> Result<DataFrame> {
+    let mut keep = vec![true; df.height()];
+    for (col, value) in filters {
+        let c = df.column(col)?.cast(&DataType::String)?;
+        for (k, v) in keep.iter_mut().zip(c.str()?) {
+            *k &= v == Some(value.as_str());
+        }
+    }
+    let mask = BooleanChunked::from_iter_values("mask".into(), keep.into_iter());
+    Ok(df.filter(&mask)?)
+}
+
+// Join `features` onto `df` (which must have DECISION_TIME, see with_decision_time) as of each row's
+// decision time. Partitions from a week before `from` are read so `latest` features have history.
+// Returns the frame and the added column names.
+pub fn join_asof_features(
+    df: &DataFrame,
+    time_col: &str,
+    features: &[AsOfFeature],
+    (from, to): (Option<NaiveDate>, Option<NaiveDate>),
+) -> Result<(DataFrame, Vec<String>)> {
+    let from = from.map(|d| d - Duration::days(7));
+    let mut out = df.clone();
+    let mut added = Vec::with_capacity(features.len());
+    for f in features {
+        let mut cols = vec![VALID_TIME, KNOWLEDGE_TIME, f.value_col.as_str()];
+        cols.extend(f.filters.iter().map(|(k, _)| k.as_str()));
+        let right = read_lake(&f.dir, &LakeScan::new().between(from, to).columns(&cols))?;
+        let right = filter_eq(&right, &f.filters)?;
+        if right.height() == 0 {
+            return Err(anyhow!(
+                "no rows for as-of feature {} in {}",
+                f.name,
+                f.dir.display()
+            ));
+        }
+        out = asof_join(
+            &out,
+            time_col,
+            DECISION_TIME,
+            &right,
+            &[(f.value_col.as_str(), f.name.as_str())],
+            f.mode,
+        )?;
+        let value = out.column(&f.name)?.cast(&DataType::Float64)?;
+        out.with_column(value)?;
+        added.push(f.name.clone());
+    }
+    Ok((out, added))
+}
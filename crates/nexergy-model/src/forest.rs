@@ -0,0 +1,211 @@
+use crate::tree::{feature_matrix, grow_tree, Binned, Tree, TreeParams};
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ForestOptions {
+    pub n_trees: usize,
+    pub max_depth: usize,
+    pub min_samples_leaf: usize,
+    // Features tried per split; None uses a third of them (at least one).
+    pub max_features: Option<usize>,
+    // Histogram bins per feature (at most 256).
+    pub max_bins: usize,
+    pub seed: u64,
+}
+
+impl Default for ForestOptions {
+    fn default() -> Self {
+        Self {
+            n_trees: 200,
+            max_depth: 16,
+            min_samples_leaf: 5,
+            max_features: None,
+            max_bins: 256,
+            seed: 42,
+        }
+    }
+}
+
+// Bagged regression trees; the forecast is the mean over trees.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForestModel {
+    pub feature_names: Vec<String>,
+    pub trees: Vec<Tree>,
+    // Out-of-bag error: every training row scored by the trees whose bootstrap sample missed it.
+    #[serde(with = "crate::artifact::nan_as_null")]
+    pub oob_mae: f64,
+    #[serde(with = "crate::artifact::nan_as_null")]
+    pub oob_rmse: f64,
+    // Training rows that were out of bag for at least one tree.
+    pub oob_n: usize,
+}
+
+impl ForestModel {
+    pub fn predict_row(&self, x: &[f64]) -> f64 {
+        self.trees.iter().map(|t| t.predict_row(x)).sum::<f64>() / self.trees.len().max(1) as f64
+    }
+
+    pub fn predict_df(&self, df: &DataFrame) -> Result<Series> {
+        let x = feature_matrix(df, &self.feature_names)?;
+        let out: Vec<f64> = (0..df.height())
+            .into_par_iter()
+            .map_init(
+                || vec![0.0; x.len()],
+                |row, i| {
+                    for (v, col) in row.iter_mut().zip(x.iter()) {
+                        *v = col[i];
+                    }
+                    self.predict_row(row)
+                },
+            )
+            .collect();
+        Ok(Series::new("yhat".into(), out))
+    }
+}
+
+// Random forest: every tree is grown in parallel on a bootstrap sample of the rows (drawn with
+// replacement, seeded per tree) on the shared feature histograms, trying a random subset of features
+// at each split. Leaves hold the mean target of their rows.
+pub fn fit_forest(
+    df: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    opts: &ForestOptions,
+) -> Result<ForestModel> {
+    if opts.n_trees == 0 {
+        return Err(anyhow!("a forest needs at least one tree"));
+    }
+    let x = feature_matrix(df, feature_cols)?;
+    let y: Vec<f64> = df
+        .column(target_col)?
+        .f64()?
+        .iter()
+        .map(|v| v.ok_or_else(|| anyhow!("null target in {target_col}")))
+        .collect::<Result<_>>()?;
+    let n = y.len();
+    if n == 0 {
+        return Err(anyhow!("no training rows"));
+    }
+    let binned = Binned::new(&x, n, opts.max_bins);
+    let params = TreeParams {
+        max_depth: opts.max_depth,
+        min_samples_leaf: opts.min_samples_leaf,
+        l2: 0.0,
+        max_features: Some(opts.max_features.unwrap_or(feature_cols.len() / 3).max(1)),
+    };
+    let grad: Vec<f64> = y.iter().map(|v| -v).collect();
+
+    // Trees with the summed out-of-bag predictions and counts they contribute.
+    let (trees, oob_sum, oob_count) = (0..opts.n_trees)
+        .into_par_iter()
+        .map(|t| {
+            let mut rng = StdRng::seed_from_u64(opts.seed.wrapping_add(t as u64));
+            let mut rows: Vec<u32> = (0..n).map(|_| rng.random_range(0..n as u32)).collect();
+            let mut in_bag = vec![false; n];
+            for &r in rows.iter() {
+                in_bag[r as usize] = true;
+            }
+            let tree = grow_tree(&binned, &grad, &mut rows, &params, &mut rng);
+            let mut sum = vec![0.0; n];
+            let mut count = vec![0u32; n];
+            for i in (0..n).filter(|&i| !in_bag[i]) {
+                sum[i] = tree.predict_binned(&binned, i);
+                count[i] = 1;
+            }
+            (vec![tree], sum, count)
+        })
+        .reduce(
+            || (Vec::new(), vec![0.0; n], vec![0u32; n]),
+            |(mut t1, mut s1, mut c1), (t2, s2, c2)| {
+                t1.extend(t2);
+                s1.iter_mut().zip(s2).for_each(|(a, b)| *a += b);
+                c1.iter_mut().zip(c2).for_each(|(a, b)| *a += b);
+                (t1, s1, c1)
+            },
+        );
+
+    let (mut abs, mut sq, mut oob_n) = (0.0, 0.0, 0usize);
+    for i in (0..n).filter(|&i| oob_count[i] > 0) {
+        let err = oob_sum[i] / oob_count[i] as f64 - y[i];
+        abs += err.abs();
+        sq += err * err;
+        oob_n += 1;
+    }
+    let (oob_mae, oob_rmse) = if oob_n > 0 {
+        (abs / oob_n as f64, (sq / oob_n as f64).sqrt())
+    } else {
+        (f64::NAN, f64::NAN)
+    };
+    Ok(ForestModel {
+        feature_names: feature_cols.to_vec(),
+        trees,
+        oob_mae,
+        oob_rmse,
+        oob_n,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gbt::tests::{mse_and_variance, step_data};
+    use crate::ridge::tests::features;
+
+    fn options() -> ForestOptions {
+        ForestOptions {
+            n_trees: 100,
+            ..ForestOptions::default()
+        }
+    }
+
+    #[test]
+    fn forest_fits_a_step_far_better_than_the_mean() {
+        let model = fit_forest(&step_data(2000, 1), "y", &features(2), &options()).unwrap();
+        assert_eq!(model.trees.len(), 100);
+
+        let test = step_data(1000, 2);
+        let (mse, var) = mse_and_variance(&test, &model.predict_df(&test).unwrap());
+        assert!(mse < 0.05 * var, "mse {mse} vs variance {var}");
+    }
+
+    #[test]
+    fn out_of_bag_error_is_finite_and_below_the_variance() {
+        let train = step_data(2000, 1);
+        let model = fit_forest(&train, "y", &features(2), &options()).unwrap();
+        let (_, var) = mse_and_variance(&train, &model.predict_df(&train).unwrap());
+        // With 100 bootstrap samples practically every row is out of bag for some tree.
+        assert_eq!(model.oob_n, 2000);
+        assert!(model.oob_mae.is_finite() && model.oob_rmse.is_finite());
+        assert!(model.oob_mae <= model.oob_rmse);
+        assert!(
+            model.oob_rmse.powi(2) < 0.1 * var,
+            "oob rmse {}",
+            model.oob_rmse
+        );
+    }
+
+    #[test]
+    fn forests_are_deterministic_for_a_seed() {
+        let df = step_data(300, 3);
+        let opts = ForestOptions {
+            n_trees: 10,
+            ..ForestOptions::default()
+        };
+        let a = fit_forest(&df, "y", &features(2), &opts).unwrap();
+        let b = fit_forest(&df, "y", &features(2), &opts).unwrap();
+        let row = [0.1, 0.7];
+        assert_eq!(a.predict_row(&row), b.predict_row(&row));
+        assert_eq!(a.oob_rmse, b.oob_rmse);
+
+        let none = ForestOptions {
+            n_trees: 0,
+            ..ForestOptions::default()
+        };
+        assert!(fit_forest(&df, "y", &features(2), &none).is_err());
+    }
+}
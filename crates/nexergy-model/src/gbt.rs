@@ -0,0 +1,318 @@
+use crate::quantile::{pinball, quantile_of};
+use crate::tree::{feature_matrix, grow_tree, Binned, Node, Tree, TreeParams};
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Loss minimised by boosting.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Loss {
+    SquaredError,
+    // Pinball loss of this quantile: trees are grown on its gradient and their leaf values re-fitted
+    // to the quantile of the residuals in each leaf.
+    Quantile(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GbtOptions {
+    pub loss: Loss,
+    pub n_trees: usize,
+    pub max_depth: usize,
+    // Shrinkage applied to every tree.
+    pub learning_rate: f64,
+    // Share of training rows drawn (without replacement) for each tree.
+    pub subsample: f64,
+    // L2 penalty on leaf values.
+    pub l2: f64,
+    pub min_samples_leaf: usize,
+    // Histogram bins per feature (at most 256).
+    pub max_bins: usize,
+    // The latest share of rows (rows are in time order) is held out for early stopping; 0 trains on
+    // every row for all n_trees.
+    pub validation_fraction: f64,
+    // Stop when the validation error has not improved for this many trees.
+    pub early_stopping_rounds: usize,
+    pub seed: u64,
+}
+
+impl Default for GbtOptions {
+    fn default() -> Self {
+        Self {
+            loss: Loss::SquaredError,
+            n_trees: 500,
+            max_depth: 6,
+            learning_rate: 0.05,
+            subsample: 0.8,
+            l2: 1.0,
+            min_samples_leaf: 20,
+            max_bins: 256,
+            validation_fraction: 0.1,
+            early_stopping_rounds: 20,
+            seed: 42,
+        }
+    }
+}
+
+// Gradient-boosted regression trees.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GbtModel {
+    pub feature_names: Vec<String>,
+    pub base_score: f64,
+    // Leaf values include the learning rate.
+    pub trees: Vec<Tree>,
+    // Validation loss (MSE, or pinball loss for a quantile) of the kept trees when early stopping was
+    // used.
+    pub validation_loss: Option<f64>,
+}
+
+impl GbtModel {
+    pub fn predict_row(&self, x: &[f64]) -> f64 {
+        self.base_score + self.trees.iter().map(|t| t.predict_row(x)).sum::<f64>()
+    }
+
+    pub fn predict_df(&self, df: &DataFrame) -> Result<Series> {
+        let x = feature_matrix(df, &self.feature_names)?;
+        let mut row = vec![0.0; x.len()];
+        let out: Vec<f64> = (0..df.height())
+            .map(|i| {
+                for (v, col) in row.iter_mut().zip(x.iter()) {
+                    *v = col[i];
+                }
+                self.predict_row(&row)
+            })
+            .collect();
+        Ok(Series::new("yhat".into(), out))
+    }
+}
+
+impl Loss {
+    fn mean(&self, pred: &[f64], y: &[f64]) -> f64 {
+        let total: f64 = match *self {
+            Loss::SquaredError => pred.iter().zip(y).map(|(p, y)| (p - y).powi(2)).sum(),
+            Loss::Quantile(q) => pred.iter().zip(y).map(|(p, y)| pinball(*y - *p, q)).sum(),
+        };
+        total / y.len().max(1) as f64
+    }
+
+    // Derivative of the loss with respect to the prediction.
+    fn gradient(&self, pred: f64, y: f64) -> f64 {
+        match *self {
+            Loss::SquaredError => pred - y,
+            Loss::Quantile(q) => {
+                if y > pred {
+                    -q
+                } else {
+                    1.0 - q
+                }
+            }
+        }
+    }
+}
+
+// Histogram-based gradient boosting: features are binned once on the training rows, every tree is
+// grown on a row subsample with histogram splits evaluated in parallel over features, and boosting
+// stops early when the error on the held-out latest rows stops improving (the model keeps the trees
+// up to the best round).
+pub fn fit_gbt(
+    df: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    opts: &GbtOptions,
+) -> Result<GbtModel> {
+    if opts.learning_rate <= 0.0 || !(opts.subsample > 0.0 && opts.subsample <= 1.0) {
+        return Err(anyhow!(
+            "learning rate must be positive and subsample in (0, 1]"
+        ));
+    }
+    if matches!(opts.loss, Loss::Quantile(q) if !(q > 0.0 && q < 1.0)) {
+        return Err(anyhow!("quantile must be in (0, 1)"));
+    }
+    if !(0.0..1.0).contains(&opts.validation_fraction) {
+        return Err(anyhow!("validation fraction must be in [0, 1)"));
+    }
+    let x = feature_matrix(df, feature_cols)?;
+    let y: Vec<f64> = df
+        .column(target_col)?
+        .f64()?
+        .iter()
+        .map(|v| v.ok_or_else(|| anyhow!("null target in {target_col}")))
+        .collect::<Result<_>>()?;
+    let n = y.len();
+    let n_val = (n as f64 * opts.validation_fraction) as usize;
+    let early_stopping = n_val > 0 && opts.early_stopping_rounds > 0;
+    let n_train = if early_stopping { n - n_val } else { n };
+    if n_train == 0 {
+        return Err(anyhow!("no training rows"));
+    }
+    let binned = Binned::new(&x, n_train, opts.max_bins);
+    let params = TreeParams {
+        max_depth: opts.max_depth,
+        min_samples_leaf: opts.min_samples_leaf,
+        l2: opts.l2,
+        max_features: None,
+    };
+
+    let base_score = match opts.loss {
+        Loss::SquaredError => y[..n_train].iter().sum::<f64>() / n_train as f64,
+        Loss::Quantile(q) => quantile_of(&mut y[..n_train].to_vec(), q),
+    };
+    let mut pred = vec![base_score; n];
+    let mut grad = vec![0.0; n_train];
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+    let mut trees: Vec<Tree> = Vec::new();
+    let mut best = (f64::INFINITY, 0);
+    for _ in 0..opts.n_trees {
+        for (i, g) in grad.iter_mut().enumerate() {
+            *g = opts.loss.gradient(pred[i], y[i]);
+        }
+        let mut rows: Vec<u32> = (0..n_train as u32)
+            .filter(|_| opts.subsample >= 1.0 || rng.random::<f64>() < opts.subsample)
+            .collect();
+        if rows.is_empty() {
+            continue;
+        }
+        let mut tree = grow_tree(&binned, &grad, &mut rows, &params, &mut rng);
+        if let Loss::Quantile(q) = opts.loss {
+            renew_leaves(&mut tree, &binned, &rows, &pred, &y, q);
+        }
+        tree.scale(opts.learning_rate);
+        for (i, p) in pred.iter_mut().enumerate() {
+            *p += tree.predict_binned(&binned, i);
+        }
+        trees.push(tree);
+        if early_stopping {
+            let val = opts.loss.mean(&pred[n_train..], &y[n_train..]);
+            if val < best.0 {
+                best = (val, trees.len());
+            } else if trees.len() - best.1 >= opts.early_stopping_rounds {
+                break;
+            }
+        }
+    }
+    let validation_loss = if early_stopping {
+        trees.truncate(best.1);
+        Some(best.0)
+    } else {
+        None
+    };
+    Ok(GbtModel {
+        feature_names: feature_cols.to_vec(),
+        base_score,
+        trees,
+        validation_loss,
+    })
+}
+
+// Set every leaf of `tree` to the `q`-quantile of the residuals of the `rows` that reach it (gradient
+// steps alone move a quantile leaf by a constant whatever the residual sizes).
+fn renew_leaves(tree: &mut Tree, binned: &Binned, rows: &[u32], pred: &[f64], y: &[f64], q: f64) {
+    let mut residuals: HashMap<usize, Vec<f64>> = HashMap::new();
+    for &r in rows {
+        let r = r as usize;
+        residuals
+            .entry(tree.leaf_binned(binned, r))
+            .or_default()
+            .push(y[r] - pred[r]);
+    }
+    for (leaf, mut res) in residuals {
+        tree.nodes[leaf] = Node::Leaf(quantile_of(&mut res, q));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ridge::tests::features;
+
+    // `n` rows of x0 and x1 uniform on [-1, 1] with y a step in x0 (x1 is irrelevant) plus uniform
+    // noise on [-1, 1].
+    pub(crate) fn step_data(n: usize, seed: u64) -> DataFrame {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let x0: Vec<f64> = (0..n).map(|_| rng.random_range(-1.0..1.0)).collect();
+        let x1: Vec<f64> = (0..n).map(|_| rng.random_range(-1.0..1.0)).collect();
+        let y: Vec<f64> = x0
+            .iter()
+            .map(|&x| if x > 0.2 { 10.0 } else { -5.0 } + rng.random_range(-1.0..1.0))
+            .collect();
+        df!("x0" => x0, "x1" => x1, "y" => y).unwrap()
+    }
+
+    pub(crate) fn targets(df: &DataFrame) -> Vec<f64> {
+        df.column("y")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect()
+    }
+
+    // Mean squared error of `pred` against y, and the variance of y (the error of its mean).
+    pub(crate) fn mse_and_variance(df: &DataFrame, pred: &Series) -> (f64, f64) {
+        let y = targets(df);
+        let n = y.len() as f64;
+        let mean = y.iter().sum::<f64>() / n;
+        let pred: Vec<f64> = pred.f64().unwrap().into_no_null_iter().collect();
+        let mse = y
+            .iter()
+            .zip(pred.iter())
+            .map(|(y, p)| (y - p).powi(2))
+            .sum::<f64>()
+            / n;
+        let var = y.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / n;
+        (mse, var)
+    }
+
+    #[test]
+    fn boosting_fits_a_step_far_better_than_the_mean() {
+        let opts = GbtOptions {
+            n_trees: 200,
+            min_samples_leaf: 10,
+            ..GbtOptions::default()
+        };
+        let model = fit_gbt(&step_data(2000, 1), "y", &features(2), &opts).unwrap();
+        assert!(!model.trees.is_empty() && model.trees.len() <= 200);
+
+        let test = step_data(1000, 2);
+        let (mse, var) = mse_and_variance(&test, &model.predict_df(&test).unwrap());
+        // The noise alone has variance 1/3.
+        assert!(mse < 0.05 * var, "mse {mse} vs variance {var}");
+        assert!(mse < 1.0);
+    }
+
+    #[test]
+    fn predictions_are_deterministic_for_a_seed() {
+        let df = step_data(500, 3);
+        let opts = GbtOptions {
+            n_trees: 20,
+            ..GbtOptions::default()
+        };
+        let a = fit_gbt(&df, "y", &features(2), &opts).unwrap();
+        let b = fit_gbt(&df, "y", &features(2), &opts).unwrap();
+        let row = [0.5, -0.5];
+        assert_eq!(a.predict_row(&row), b.predict_row(&row));
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let df = step_data(50, 4);
+        for opts in [
+            GbtOptions {
+                learning_rate: 0.0,
+                ..GbtOptions::default()
+            },
+            GbtOptions {
+                subsample: 1.5,
+                ..GbtOptions::default()
+            },
+            GbtOptions {
+                validation_fraction: 1.0,
+                ..GbtOptions::default()
+            },
+        ] {
+            assert!(fit_gbt(&df, "y", &features(2), &opts).is_err());
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDateTime};
+use nexergy_core::time::{column_tz, column_utc, datetime_column};
+use polars::prelude::*;
+use std::collections::HashMap;
+
+fn step_micros(step: Duration) -> Result<i64> {
+    match step.num_microseconds() {
+        Some(s) if s > 0 => Ok(s),
+        _ => Err(anyhow!("time step must be positive")),
+    }
+}
+
+// Reindex `df` onto the complete UTC grid of `step` (e.g. 1 hour or 15 minutes) between its first and
+// last timestamp in `time_col`. Steps without a row get a row of nulls (except the timestamp), rows
+// off the grid are dropped and duplicate timestamps keep the last row. Returns the reindexed frame,
+// sorted by time, and the number of inserted steps.
+pub fn reindex_to_grid(
+    df: &DataFrame,
+    time_col: &str,
+    step: Duration,
+) -> Result<(DataFrame, usize)> {
+    let step = step_micros(step)?;
+    let col = df.column(time_col)?;
+    let tz = column_tz(col)?;
+    let mut rows: HashMap<i64, IdxSize> = HashMap::with_capacity(df.height());
+    for (i, t) in column_utc(col)?.into_iter().enumerate() {
+        if let Some(t) = t {
+            let us = t.and_utc().timestamp_micros();
+            if us.rem_euclid(step) == 0 {
+                rows.insert(us, i as IdxSize);
+            }
+        }
+    }
+    let (Some(&first), Some(&last)) = (rows.keys().min(), rows.keys().max()) else {
+        return Ok((df.clear(), 0));
+    };
+
+    let n = ((last - first) / step + 1) as usize;
+    let mut idx: Vec<Option<IdxSize>> = Vec::with_capacity(n);
+    let mut grid: Vec<Option<NaiveDateTime>> = Vec::with_capacity(n);
+    for k in 0..n as i64 {
+        let us = first + k * step;
+        idx.push(rows.get(&us).copied());
+        grid.push(chrono::DateTime::from_timestamp_micros(us).map(|t| t.naive_utc()));
+    }
+    let inserted = idx.iter().filter(|i| i.is_none()).count();
+    let mut out = df.take(&IdxCa::from_iter(idx))?;
+    out.with_column(datetime_column(time_col, grid, tz.name())?)?;
+    Ok((out, inserted))
+}
+
+// Lags `lag_1..lag_<num_lags>` of `target_col` by timestamp offset: lag_k at t is the value at
+// t - k * step, null when that step has no value. Unlike row shifts this stays correct when hours are
+// missing or the frame is not sorted.
+pub fn make_time_lag_features(
+    df: &DataFrame,
+    time_col: &str,
+    target_col: &str,
+    num_lags: usize,
+    step: Duration,
+) -> Result<DataFrame> {
+    let step = step_micros(step)?;
+    let ts: Vec<Option<i64>> = column_utc(df.column(time_col)?)?
+        .into_iter()
+        .map(|t| t.map(|t| t.and_utc().timestamp_micros()))
+        .collect();
+    let target = df.column(target_col)?.f64()?;
+    let values: HashMap<i64, f64> = ts
+        .iter()
+        .zip(target)
+        .filter_map(|(t, v)| Some(((*t)?, v?)))
+        .collect();
+
+    let mut out = df.clone();
+    for lag in 1..=num_lags {
+        let offset = lag as i64 * step;
+        let vals: Vec<Option<f64>> = ts
+            .iter()
+            .map(|t| t.and_then(|t| values.get(&(t - offset)).copied()))
+            .collect();
+        out.hstack_mut(&[Column::new(format!("lag_{}", lag).into(), vals)])?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nexergy_core::time::{local_column, utc_column, utc_to_wall_clock, LOCAL_TZ};
+
+    fn utc(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn frame(time: Column, values: &[f64]) -> DataFrame {
+        DataFrame::new(vec![time, Column::new("price".into(), values)]).unwrap()
+    }
+
+    fn floats(df: &DataFrame, name: &str) -> Vec<Option<f64>> {
+        df.column(name).unwrap().f64().unwrap().iter().collect()
+    }
+
+    fn times(df: &DataFrame, name: &str) -> Vec<NaiveDateTime> {
+        column_utc(df.column(name).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(Option::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn a_missing_hour_gives_null_lags_not_the_previous_row() {
+        // 02:00 is missing; rows are deliberately out of order.
+        let ts = [
+            utc("2024-01-01 03:00"),
+            utc("2024-01-01 00:00"),
+            utc("2024-01-01 01:00"),
+            utc("2024-01-01 04:00"),
+        ];
+        let df = frame(utc_column("t", &ts).unwrap(), &[3.0, 0.0, 1.0, 4.0]);
+        let out = make_time_lag_features(&df, "t", "price", 2, Duration::hours(1)).unwrap();
+        assert_eq!(
+            floats(&out, "lag_1"),
+            vec![None, None, Some(0.0), Some(3.0)]
+        );
+        assert_eq!(floats(&out, "lag_2"), vec![Some(1.0), None, None, None]);
+    }
+
+    #[test]
+    fn reindexing_inserts_null_rows_and_drops_off_grid_and_duplicate_rows() {
+        let ts = [
+            utc("2024-01-01 00:00"),
+            utc("2024-01-01 01:00"),
+            utc("2024-01-01 01:30"),
+            utc("2024-01-01 01:00"),
+            utc("2024-01-01 03:00"),
+        ];
+        let df = frame(utc_column("t", &ts).unwrap(), &[0.0, 1.0, 1.5, 10.0, 3.0]);
+        let (grid, inserted) = reindex_to_grid(&df, "t", Duration::hours(1)).unwrap();
+        assert_eq!(inserted, 1);
+        assert_eq!(
+            times(&grid, "t"),
+            vec![
+                utc("2024-01-01 00:00"),
+                utc("2024-01-01 01:00"),
+                utc("2024-01-01 02:00"),
+                utc("2024-01-01 03:00"),
+            ]
+        );
+        assert_eq!(
+            floats(&grid, "price"),
+            vec![Some(0.0), Some(10.0), None, Some(3.0)]
+        );
+
+        let lagged = make_time_lag_features(&grid, "t", "price", 1, Duration::hours(1)).unwrap();
+        assert_eq!(
+            floats(&lagged, "lag_1"),
+            vec![None, Some(0.0), Some(10.0), None]
+        );
+    }
+
+    #[test]
+    fn dst_days_keep_their_23_and_25_hours() {
+        // Local 2024-03-31 has no 02:00 and local 2024-10-27 has two; a local-time column must be
+        // gridded and lagged on UTC instants.
+        for (from, to, hours) in [
+            ("2024-03-30 23:00", "2024-03-31 21:00", 23),
+            ("2024-10-26 22:00", "2024-10-27 22:00", 25),
+        ] {
+            let (from, to) = (utc(from), utc(to));
+            let n = (to - from).num_hours() as usize + 1;
+            assert_eq!(n, hours);
+            let ts: Vec<NaiveDateTime> = (0..n as i64).map(|k| from + Duration::hours(k)).collect();
+            let values: Vec<f64> = (0..n).map(|k| k as f64).collect();
+            let df = frame(local_column("t", &ts).unwrap(), &values);
+
+            let (grid, inserted) = reindex_to_grid(&df, "t", Duration::hours(1)).unwrap();
+            assert_eq!((grid.height(), inserted), (hours, 0));
+            assert_eq!(
+                grid.column("t").unwrap().dtype(),
+                df.column("t").unwrap().dtype()
+            );
+
+            let lagged =
+                make_time_lag_features(&grid, "t", "price", 1, Duration::hours(1)).unwrap();
+            let lag: Vec<Option<f64>> = floats(&lagged, "lag_1");
+            let expected: Vec<Option<f64>> =
+                (0..n).map(|k| k.checked_sub(1).map(|p| p as f64)).collect();
+            assert_eq!(lag, expected);
+        }
+
+        // The repeated 02:00 on 2024-10-27 lags the first 02:00, one hour earlier.
+        let first = utc("2024-10-27 00:00");
+        let second = first + Duration::hours(1);
+        assert_eq!(
+            utc_to_wall_clock(first, LOCAL_TZ),
+            utc_to_wall_clock(second, LOCAL_TZ)
+        );
+    }
+}
@@ -1,9 +1,58 @@
 use anyhow::{anyhow, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use nexergy_core::scan::{read_lake, LakeScan};
+use nexergy_core::time::{
+    column_dates, column_tz, column_utc, datetime_column, to_timestamp_column, utc_dtype,
+};
 use polars::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
-use walkdir::WalkDir;
 
-#[derive(Debug, Clone)]
+mod artifact;
+mod backtest;
+mod conformal;
+mod day_ahead;
+mod elastic_net;
+mod exogenous;
+mod forest;
+mod gbt;
+mod lags;
+mod neighbours;
+mod quantile;
+mod ridge;
+mod tree;
+
+pub use artifact::{
+    predict, ArtifactMetrics, ModelArtifact, SavedModel, TrainingWindow, ARTIFACT_VERSION,
+};
+pub use backtest::{
+    backtest, forecast_table, BacktestOptions, BacktestResult, TrainWindow, FIT_DAY, ISSUE_TIME,
+};
+pub use conformal::{
+    conformal_cols, conformal_half_widths, conformal_scores, ConformalOptions, ConformalScore,
+    Residual,
+};
+pub use day_ahead::{
+    day_lag_features, fit_day_ahead, periods_per_day, previous_day_stats, with_period,
+    DayAheadModel, PERIOD_COL,
+};
+pub use elastic_net::{
+    fit_elastic_net, ElasticNetFit, ElasticNetOptions, LambdaSelection, PathPoint,
+    RegularizationPath,
+};
+pub use exogenous::{join_asof_features, with_decision_time, AsOfFeature, DECISION_TIME};
+pub use forest::{fit_forest, ForestModel, ForestOptions};
+pub use gbt::{fit_gbt, GbtModel, GbtOptions, Loss};
+pub use lags::{make_time_lag_features, reindex_to_grid};
+pub use neighbours::{join_zone_prices, lag_columns, zone_slug, NeighbourFeatures};
+pub use quantile::{
+    fit_quantile_linear, interval_scores, non_crossing, quantile_col, quantile_estimator,
+    quantile_scores, IntervalScore, QuantileScore,
+};
+pub use ridge::fit_ridge;
+pub use tree::{Node, Tree};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LinearModel {
     pub feature_names: Vec<String>,
     pub weights: Vec<f64>, // length = features + 1 (bias)
@@ -28,6 +77,8 @@ impl LinearModel {
     }
 }
 
+// Row-shifted lags; only correct when every step is present (see reindex_to_grid and
+// make_time_lag_features).
 pub fn make_lag_features(df: &DataFrame, target_col: &str, num_lags: usize) -> Result<DataFrame> {
     let mut df2 = df.clone();
     let target = df2.column(target_col)?.f64()?.clone();
@@ -50,122 +101,259 @@ pub fn drop_nulls_by_cols(df: &DataFrame, cols: &[String]) -> Result<DataFrame>
     Ok(df.clone().drop_nulls(Some(cols))?)
 }
 
-fn reorder_by_string_column(df: &DataFrame, col: &str) -> Result<DataFrame> {
-    let s = df.column(col)?;
-    let mut pairs: Vec<(usize, String)> = Vec::with_capacity(df.height());
-    for i in 0..df.height() {
-        let av = s.get(i)?;
-        let v = match av {
-            AnyValue::String(v) => v.to_string(),
-            AnyValue::StringOwned(ref v) => v.as_str().to_string(),
-            _ => String::new(),
-        };
-        pairs.push((i, v));
-    }
-    pairs.sort_by(|a, b| a.1.cmp(&b.1));
-    let idx: Vec<u32> = pairs.into_iter().map(|(i, _)| i as u32).collect();
-    let take_idx = UInt32Chunked::from_vec("idx".into(), idx);
-    Ok(df.take(&take_idx)?)
+// Sort `df` by the timestamp column `col`. A string column (a lake written before timestamps were
+// typed) is converted to Datetime (UTC) first.
+pub fn sort_by_time(df: &DataFrame, col: &str) -> Result<DataFrame> {
+    let mut df = df.clone();
+    let ts = to_timestamp_column(df.column(col)?, &utc_dtype())?;
+    df.with_column(ts)?;
+    Ok(df.sort([col], SortMultipleOptions::default())?)
 }
 
-pub fn fit_linear_gd(
-    df: &DataFrame,
-    target_col: &str,
-    feature_cols: &[String],
-    learning_rate: f64,
-    epochs: usize,
-) -> Result<LinearModel> {
-    if feature_cols.is_empty() {
-        return Err(anyhow!("no features provided"));
-    }
-    let mut w = vec![0.0_f64; feature_cols.len() + 1];
-    let y_ca = df
-        .column(target_col)?
-        .as_series()
-        .ok_or_else(|| anyhow!("expected Series for target column"))?
-        .f64()?;
-    let n = y_ca.len();
-    let mut feats: Vec<ChunkedArray<Float64Type>> = Vec::with_capacity(feature_cols.len());
-    for c in feature_cols.iter() {
-        let ca = df
-            .column(c)?
+// Training data on the scale of LinearModel weights: features and target are standardized with
+// their train mean and (population) standard deviation; a constant column gets std 1.
+pub(crate) struct Standardized {
+    pub feats: Vec<Float64Chunked>,
+    pub y: Float64Chunked,
+    pub feature_means: Vec<f64>,
+    pub feature_stds: Vec<f64>,
+    pub target_mean: f64,
+    pub target_std: f64,
+}
+
+impl Standardized {
+    pub fn new(df: &DataFrame, target_col: &str, feature_cols: &[String]) -> Result<Self> {
+        if feature_cols.is_empty() {
+            return Err(anyhow!("no features provided"));
+        }
+        let y_ca = df
+            .column(target_col)?
             .as_series()
-            .ok_or_else(|| anyhow!("expected Series for feature column"))?
+            .ok_or_else(|| anyhow!("expected Series for target column"))?
             .f64()?
             .clone();
-        feats.push(ca);
-    }
+        let n = y_ca.len();
+        let mut feats: Vec<ChunkedArray<Float64Type>> = Vec::with_capacity(feature_cols.len());
+        for c in feature_cols.iter() {
+            let ca = df
+                .column(c)?
+                .as_series()
+                .ok_or_else(|| anyhow!("expected Series for feature column"))?
+                .f64()?
+                .clone();
+            feats.push(ca);
+        }
 
-    // Compute standardization parameters on train
-    let mut feat_means: Vec<f64> = Vec::with_capacity(feature_cols.len());
-    let mut feat_stds: Vec<f64> = Vec::with_capacity(feature_cols.len());
-    for ca in feats.iter() {
-        let mut sum = 0.0_f64;
-        let mut cnt = 0usize;
+        // Compute standardization parameters on train
+        let mut feat_means: Vec<f64> = Vec::with_capacity(feature_cols.len());
+        let mut feat_stds: Vec<f64> = Vec::with_capacity(feature_cols.len());
+        for ca in feats.iter() {
+            let mut sum = 0.0_f64;
+            let mut cnt = 0usize;
+            for i in 0..n {
+                if let Some(v) = ca.get(i) {
+                    sum += v;
+                    cnt += 1;
+                }
+            }
+            let mean = if cnt > 0 { sum / (cnt as f64) } else { 0.0 };
+            let mut ssd = 0.0_f64;
+            for i in 0..n {
+                if let Some(v) = ca.get(i) {
+                    let d = v - mean;
+                    ssd += d * d;
+                }
+            }
+            let std = if cnt > 1 {
+                (ssd / (cnt as f64)).sqrt()
+            } else {
+                1.0
+            };
+            feat_means.push(mean);
+            feat_stds.push(if std > 0.0 { std } else { 1.0 });
+        }
+        let mut y_sum = 0.0_f64;
+        let mut y_cnt = 0usize;
         for i in 0..n {
-            if let Some(v) = ca.get(i) {
-                sum += v;
-                cnt += 1;
+            if let Some(v) = y_ca.get(i) {
+                y_sum += v;
+                y_cnt += 1;
             }
         }
-        let mean = if cnt > 0 { sum / (cnt as f64) } else { 0.0 };
-        let mut ssd = 0.0_f64;
+        let y_mean = if y_cnt > 0 {
+            y_sum / (y_cnt as f64)
+        } else {
+            0.0
+        };
+        let mut y_ssd = 0.0_f64;
         for i in 0..n {
-            if let Some(v) = ca.get(i) {
-                let d = v - mean;
-                ssd += d * d;
+            if let Some(v) = y_ca.get(i) {
+                let d = v - y_mean;
+                y_ssd += d * d;
             }
         }
-        let std = if cnt > 1 {
-            (ssd / (cnt as f64)).sqrt()
+        let y_std = if y_cnt > 1 {
+            (y_ssd / (y_cnt as f64)).sqrt()
         } else {
             1.0
         };
-        feat_means.push(mean);
-        feat_stds.push(if std > 0.0 { std } else { 1.0 });
+        let y_std = if y_std > 0.0 { y_std } else { 1.0 };
+        Ok(Self {
+            feats,
+            y: y_ca,
+            feature_means: feat_means,
+            feature_stds: feat_stds,
+            target_mean: y_mean,
+            target_std: y_std,
+        })
     }
-    let mut y_sum = 0.0_f64;
-    let mut y_cnt = 0usize;
-    for i in 0..n {
-        if let Some(v) = y_ca.get(i) {
-            y_sum += v;
-            y_cnt += 1;
+
+    pub fn len(&self) -> usize {
+        self.y.len()
+    }
+
+    // Standardized target of row `i` (0 when null).
+    pub fn y(&self, i: usize) -> f64 {
+        if let Some(v) = self.y.get(i) {
+            (v - self.target_mean) / self.target_std
+        } else {
+            0.0
         }
     }
-    let y_mean = if y_cnt > 0 {
-        y_sum / (y_cnt as f64)
-    } else {
-        0.0
-    };
-    let mut y_ssd = 0.0_f64;
-    for i in 0..n {
-        if let Some(v) = y_ca.get(i) {
-            let d = v - y_mean;
-            y_ssd += d * d;
+
+    // Standardized features of row `i` into `x` (nulls are read as 0 before scaling).
+    pub fn row_into(&self, i: usize, x: &mut [f64]) {
+        for (j, (xj, f)) in x.iter_mut().zip(self.feats.iter()).enumerate() {
+            let raw = f.get(i).unwrap_or(0.0);
+            *xj = (raw - self.feature_means[j]) / self.feature_stds[j];
         }
     }
-    let y_std = if y_cnt > 1 {
-        (y_ssd / (y_cnt as f64)).sqrt()
-    } else {
-        1.0
-    };
-    let y_std = if y_std > 0.0 { y_std } else { 1.0 };
+
+    pub fn into_model(self, feature_cols: &[String], weights: Vec<f64>) -> LinearModel {
+        LinearModel {
+            feature_names: feature_cols.to_vec(),
+            weights,
+            feature_means: self.feature_means,
+            feature_stds: self.feature_stds,
+            target_mean: self.target_mean,
+            target_std: self.target_std,
+        }
+    }
+}
+
+// How a LinearModel is fitted.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LinearSolver {
+    // Closed-form ridge regression with this L2 penalty on the standardized weights (see fit_ridge).
+    Ridge { l2: f64 },
+    // Full-batch gradient descent on the squared error (see fit_linear_gd).
+    GradientDescent { learning_rate: f64, epochs: usize },
+    // Lasso / elastic net path with a selected penalty (see fit_elastic_net).
+    ElasticNet(ElasticNetOptions),
+    // Linear quantile regression of this quantile with an L2 penalty (see fit_quantile_linear).
+    Quantile { quantile: f64, l2: f64 },
+}
+
+pub fn fit_linear(
+    df: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    solver: LinearSolver,
+) -> Result<LinearModel> {
+    match solver {
+        LinearSolver::Ridge { l2 } => fit_ridge(df, target_col, feature_cols, l2),
+        LinearSolver::GradientDescent {
+            learning_rate,
+            epochs,
+        } => fit_linear_gd(df, target_col, feature_cols, learning_rate, epochs),
+        LinearSolver::ElasticNet(o) => {
+            fit_elastic_net(df, target_col, feature_cols, &o).map(|fit| fit.model)
+        }
+        LinearSolver::Quantile { quantile, l2 } => {
+            fit_quantile_linear(df, target_col, feature_cols, quantile, l2)
+        }
+    }
+}
+
+// Model class fitted by train_eval and backtest.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Estimator {
+    Linear(LinearSolver),
+    GradientBoosting(GbtOptions),
+    RandomForest(ForestOptions),
+}
+
+// A fitted Estimator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Regressor {
+    Linear(LinearModel),
+    GradientBoosting(GbtModel),
+    RandomForest(ForestModel),
+}
+
+impl Regressor {
+    pub fn feature_names(&self) -> &[String] {
+        match self {
+            Regressor::Linear(m) => &m.feature_names,
+            Regressor::GradientBoosting(m) => &m.feature_names,
+            Regressor::RandomForest(m) => &m.feature_names,
+        }
+    }
+
+    pub fn predict_row(&self, x: &[f64]) -> f64 {
+        match self {
+            Regressor::Linear(m) => m.predict_row(x),
+            Regressor::GradientBoosting(m) => m.predict_row(x),
+            Regressor::RandomForest(m) => m.predict_row(x),
+        }
+    }
+
+    pub fn predict_df(&self, df: &DataFrame) -> Result<Series> {
+        match self {
+            Regressor::Linear(m) => predict_df(m, df),
+            Regressor::GradientBoosting(m) => m.predict_df(df),
+            Regressor::RandomForest(m) => m.predict_df(df),
+        }
+    }
+}
+
+pub fn fit_regressor(
+    df: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    estimator: &Estimator,
+) -> Result<Regressor> {
+    Ok(match estimator {
+        Estimator::Linear(solver) => {
+            Regressor::Linear(fit_linear(df, target_col, feature_cols, *solver)?)
+        }
+        Estimator::GradientBoosting(o) => {
+            Regressor::GradientBoosting(fit_gbt(df, target_col, feature_cols, o)?)
+        }
+        Estimator::RandomForest(o) => {
+            Regressor::RandomForest(fit_forest(df, target_col, feature_cols, o)?)
+        }
+    })
+}
+
+pub fn fit_linear_gd(
+    df: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    learning_rate: f64,
+    epochs: usize,
+) -> Result<LinearModel> {
+    let data = Standardized::new(df, target_col, feature_cols)?;
+    let n = data.len();
+    let mut w = vec![0.0_f64; feature_cols.len() + 1];
+    let mut xi = vec![0.0_f64; feature_cols.len()];
 
     for _ in 0..epochs {
         let mut g0 = 0.0_f64; // bias grad
         let mut g = vec![0.0_f64; feature_cols.len()];
         for i in 0..n {
-            let yi = if let Some(v) = y_ca.get(i) {
-                (v - y_mean) / y_std
-            } else {
-                0.0
-            };
-            let mut xi: Vec<f64> = Vec::with_capacity(feature_cols.len());
-            for (j, f) in feats.iter().enumerate() {
-                let raw = f.get(i).unwrap_or(0.0);
-                let std = (raw - feat_means[j]) / feat_stds[j];
-                xi.push(std);
-            }
+            let yi = data.y(i);
+            data.row_into(i, &mut xi);
             let yhat = w[0]
                 + xi.iter()
                     .enumerate()
@@ -183,26 +371,14 @@ pub fn fit_linear_gd(
             w[j + 1] -= learning_rate * g[j] * scale;
         }
     }
-    Ok(LinearModel {
-        feature_names: feature_cols.to_vec(),
-        weights: w,
-        feature_means: feat_means,
-        feature_stds: feat_stds,
-        target_mean: y_mean,
-        target_std: y_std,
-    })
+    Ok(data.into_model(feature_cols, w))
 }
 
 pub fn predict_df(model: &LinearModel, df: &DataFrame) -> Result<Series> {
     let mut out: Vec<f64> = Vec::with_capacity(df.height());
     let mut feats: Vec<ChunkedArray<Float64Type>> = Vec::with_capacity(model.feature_names.len());
     for c in model.feature_names.iter() {
-        let ca = df
-            .column(c)?
-            .as_series()
-            .ok_or_else(|| anyhow!("expected Series for feature column"))?
-            .f64()?
-            .clone();
+        let ca = df.column(c)?.as_materialized_series().f64()?.clone();
         feats.push(ca);
     }
     for i in 0..df.height() {
@@ -220,25 +396,18 @@ pub fn train_test_split_by_year(
     date_col: &str,
     cutoff_year: i32,
 ) -> Result<(DataFrame, DataFrame)> {
-    let s = df.column(date_col)?;
+    // Years are calendar years in the column's time zone (UTC for datetime_utc).
+    let dates = column_dates(df.column(date_col)?)?;
     let mut train_idx: Vec<u32> = Vec::new();
     let mut test_idx: Vec<u32> = Vec::new();
-    for i in 0..df.height() {
-        let av = s.get(i)?;
-        let v = match av {
-            AnyValue::String(v) => v,
-            AnyValue::StringOwned(ref v) => v.as_str(),
-            _ => continue,
+    for (i, d) in dates.iter().enumerate() {
+        let Some(y) = d.map(|d| d.year()) else {
+            continue;
         };
-        // Expect YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS
-        if v.len() >= 4 {
-            if let Ok(y) = v[0..4].parse::<i32>() {
-                if y <= cutoff_year {
-                    train_idx.push(i as u32);
-                } else if y == cutoff_year + 1 {
-                    test_idx.push(i as u32);
-                }
-            }
+        if y <= cutoff_year {
+            train_idx.push(i as u32);
+        } else if y == cutoff_year + 1 {
+            test_idx.push(i as u32);
         }
     }
     let train = df.take(&UInt32Chunked::from_vec("idx".into(), train_idx))?;
@@ -286,23 +455,7 @@ pub fn root_mean_squared_error(y_true: &Series, y_pred: &Series) -> Result<f64>
 }
 
 pub fn read_partitioned_parquet(dir: &Path) -> Result<DataFrame> {
-    let mut dfs: Vec<DataFrame> = Vec::new();
-    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.extension().and_then(|s| s.to_str()) == Some("parquet") {
-            let df = ParquetReader::new(std::fs::File::open(path)?).finish()?;
-            dfs.push(df);
-        }
-    }
-    if dfs.is_empty() {
-        return Err(anyhow!("no parquet files found under {}", dir.display()));
-    }
-    let mut it = dfs.into_iter();
-    let mut acc = it.next().unwrap();
-    for df in it {
-        acc.vstack_mut(&df)?;
-    }
-    Ok(acc)
+    read_lake(dir, &LakeScan::new())
 }
 
 fn drop_non_finite_by_cols(df: &DataFrame, cols: &[String]) -> Result<DataFrame> {
@@ -314,13 +467,10 @@ fn drop_non_finite_by_cols(df: &DataFrame, cols: &[String]) -> Result<DataFrame>
             .as_series()
             .ok_or_else(|| anyhow!("expected Series for numeric column"))?
             .f64()?;
-        for i in 0..n {
-            if let Some(v) = ca.get(i) {
-                if !v.is_finite() {
-                    keep[i] = false;
-                }
-            } else {
-                keep[i] = false;
+        for (i, k) in keep.iter_mut().enumerate() {
+            match ca.get(i) {
+                Some(v) if v.is_finite() => {}
+                _ => *k = false,
             }
         }
     }
@@ -328,14 +478,100 @@ fn drop_non_finite_by_cols(df: &DataFrame, cols: &[String]) -> Result<DataFrame>
     Ok(df.filter(&mask)?)
 }
 
+// Test-set score of a naive forecast.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineScore {
+    pub name: String,
+    #[serde(with = "artifact::nan_as_null")]
+    pub mae: f64,
+    #[serde(with = "artifact::nan_as_null")]
+    pub rmse: f64,
+}
+
 pub struct TrainEvalResult {
-    pub model: Option<LinearModel>,
+    // One-step model (ForecastMode::OneStep).
+    pub model: Option<Regressor>,
+    // Penalty path of a one-step elastic net fit.
+    pub path: Option<RegularizationPath>,
+    // Per-period models (ForecastMode::DayAhead).
+    pub day_ahead_model: Option<DayAheadModel>,
+    // Test-set scores; NaN (and no quantile or conformal results) without test rows.
     pub mae: f64,
     pub rmse: f64,
     pub train_n: usize,
     pub test_n: usize,
-    pub baseline_mae: Option<f64>,
-    pub baseline_rmse: Option<f64>,
+    pub training_window: Option<TrainingWindow>,
+    // Persistence (lag_1) for one-step forecasts; same hour yesterday and last week for day-ahead.
+    pub baselines: Vec<BaselineScore>,
+    // Grid steps without a price, left as nulls before lagging.
+    pub missing_steps: usize,
+    // Scores of the requested quantiles (in increasing order) and of the intervals between symmetric
+    // pairs of them.
+    pub quantiles: Vec<QuantileScore>,
+    pub intervals: Vec<IntervalScore>,
+    // Test rows with the date, actual and one (non-crossing) forecast column per quantile (see
+    // quantile_col).
+    pub quantile_forecasts: Option<DataFrame>,
+    // Conformal interval scores and test rows with their bounds (see conformal_cols).
+    pub conformal: Vec<ConformalScore>,
+    pub conformal_forecasts: Option<DataFrame>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForecastMode {
+    // Each step from the `lags` steps before it (one step ahead).
+    OneStep,
+    // Every period of delivery day D from what is known at the D-1 gate: prices of D-1 and earlier,
+    // with one model per period.
+    DayAhead,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainEvalOptions {
+    pub mode: ForecastMode,
+    pub target_col: String,
+    pub date_col: String,
+    pub lags: usize,
+    // Day-ahead mode: same-period values this many days back (of the target and neighbour columns).
+    pub day_lags: Vec<usize>,
+    // Spacing of the price series; lags are multiples of it.
+    pub step: Duration,
+    pub cutoff_year: i32,
+    pub estimator: Estimator,
+    pub neighbours: Option<NeighbourFeatures>,
+    // Bitemporal features joined as known at `gate` (local time) on the day before delivery.
+    pub asof_features: Vec<AsOfFeature>,
+    pub gate: NaiveTime,
+    // Date range read from the lake (from inclusive, to exclusive); partitions outside it are skipped.
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    // Quantiles in (0, 1) to forecast besides the point forecast, each with its own model of the
+    // estimator's class (see quantile_estimator).
+    pub quantiles: Vec<f64>,
+    // Conformal prediction intervals around the point forecast.
+    pub conformal: Option<ConformalOptions>,
+}
+
+impl Default for TrainEvalOptions {
+    fn default() -> Self {
+        Self {
+            mode: ForecastMode::OneStep,
+            target_col: "price_eur_mwh".to_string(),
+            date_col: "datetime_utc".to_string(),
+            lags: 24,
+            day_lags: vec![1, 2, 7],
+            step: Duration::hours(1),
+            cutoff_year: 2024,
+            estimator: Estimator::Linear(LinearSolver::Ridge { l2: 0.01 }),
+            neighbours: None,
+            asof_features: Vec::new(),
+            gate: NaiveTime::from_hms_opt(12, 0, 0).expect("valid gate time"),
+            from: None,
+            to: None,
+            quantiles: Vec::new(),
+            conformal: None,
+        }
+    }
 }
 
 pub fn train_eval_from_curated(
@@ -347,76 +583,331 @@ pub fn train_eval_from_curated(
     learning_rate: f64,
     epochs: usize,
 ) -> Result<TrainEvalResult> {
-    let df_prices = read_partitioned_parquet(prices_dir)?;
-    let df_prices = reorder_by_string_column(&df_prices, date_col)?;
-    let mut df_feat = make_lag_features(&df_prices, target_col, lags)?;
-    let mut cols_needed: Vec<String> = vec![target_col.to_string(), date_col.to_string()];
-    let mut lag_cols: Vec<String> = Vec::new();
-    for lag in 1..=lags {
-        let name = format!("lag_{}", lag);
-        cols_needed.push(name.clone());
-        lag_cols.push(name);
+    let opts = TrainEvalOptions {
+        target_col: target_col.to_string(),
+        date_col: date_col.to_string(),
+        lags,
+        cutoff_year,
+        estimator: Estimator::Linear(LinearSolver::GradientDescent {
+            learning_rate,
+            epochs,
+        }),
+        ..TrainEvalOptions::default()
+    };
+    train_eval(prices_dir, &opts)
+}
+
+// Output of build_features.
+pub(crate) struct Features {
+    pub df: DataFrame,
+    pub feature_cols: Vec<String>,
+    // (name, column) of each naive forecast scored on the test set.
+    pub baselines: Vec<(String, String)>,
+    // Grid steps without a price, left as nulls before lagging.
+    pub missing_steps: usize,
+}
+
+// Target lags plus, when configured, lagged neighbour prices and spreads and as-of features,
+// computed on the complete time grid so that missing hours give null lags instead of shifted ones.
+// Day-ahead mode replaces step lags with same-period day lags and previous-day statistics and adds the
+// period and baseline columns. Rows with null or non-finite values are dropped.
+pub(crate) fn build_features(prices_dir: &Path, opts: &TrainEvalOptions) -> Result<Features> {
+    build_features_until(prices_dir, opts, None)
+}
+
+// build_features, with the grid extended up to `forecast_until` (exclusive) when set so that steps
+// without a price yet get features; rows are then kept whether or not their target and baselines are
+// known.
+pub(crate) fn build_features_until(
+    prices_dir: &Path,
+    opts: &TrainEvalOptions,
+    forecast_until: Option<NaiveDateTime>,
+) -> Result<Features> {
+    let (target_col, date_col) = (opts.target_col.as_str(), opts.date_col.as_str());
+    let df_prices = read_lake(
+        prices_dir,
+        &LakeScan::new()
+            .between(opts.from, opts.to)
+            .columns(&[date_col, target_col]),
+    )?;
+    let mut df_prices = sort_by_time(&df_prices, date_col)?;
+    let last_step = forecast_until.map(|until| until - opts.step);
+    let last_price = column_utc(df_prices.column(date_col)?)?
+        .into_iter()
+        .flatten()
+        .max();
+    if let Some(last_step) = last_step.filter(|t| last_price.is_none_or(|p| p < *t)) {
+        // A row without a price at the last step to forecast; reindexing fills the steps before it.
+        let tz = column_tz(df_prices.column(date_col)?)?;
+        let end = DataFrame::new(vec![
+            datetime_column(date_col, [Some(last_step)], tz.name())?,
+            Column::new(target_col.into(), [None::<f64>]),
+        ])?;
+        df_prices = df_prices.vstack(&end)?;
+    }
+    let (df_prices, missing_steps) = reindex_to_grid(&df_prices, date_col, opts.step)?;
+    let day_ahead = opts.mode == ForecastMode::DayAhead;
+    if day_ahead && opts.day_lags.contains(&0) {
+        return Err(anyhow!("day-ahead lags must be at least one day"));
+    }
+    let target = [target_col.to_string()];
+    let (mut df_feat, mut feature_cols, baselines) = if day_ahead {
+        let (df, mut cols) = day_lag_features(&df_prices, date_col, &target, &opts.day_lags)?;
+        let (df, _) = day_lag_features(&df, date_col, &target, &day_ahead::BASELINE_DAYS)?;
+        let (df, stats) = previous_day_stats(&df, date_col, target_col)?;
+        cols.extend(stats);
+        let baselines = vec![
+            (
+                "same_hour_yesterday".to_string(),
+                format!("{target_col}_d1"),
+            ),
+            (
+                "same_hour_last_week".to_string(),
+                format!("{target_col}_d7"),
+            ),
+        ];
+        (with_period(&df, date_col, opts.step)?, cols, baselines)
+    } else {
+        let df = make_time_lag_features(&df_prices, date_col, target_col, opts.lags, opts.step)?;
+        let cols: Vec<String> = (1..=opts.lags).map(|i| format!("lag_{}", i)).collect();
+        (
+            df,
+            cols,
+            vec![("persistence".to_string(), "lag_1".to_string())],
+        )
+    };
+    if let Some(nb) = &opts.neighbours {
+        let zones: Vec<&str> = nb.zones.iter().map(String::as_str).collect();
+        let zone_prices = read_lake(
+            &nb.zone_prices_dir,
+            &LakeScan::new()
+                .between(opts.from, opts.to)
+                .partition_in("zone", &zones)
+                .columns(&["zone", "datetime_utc", "price_eur_mwh"]),
+        )?;
+        let (joined, cols) =
+            join_zone_prices(&df_feat, &zone_prices, date_col, target_col, &nb.zones)?;
+        let (lagged, cols) = if day_ahead {
+            day_lag_features(&joined, date_col, &cols, &opts.day_lags)?
+        } else {
+            lag_columns(&joined, &cols, &nb.lags)?
+        };
+        df_feat = lagged;
+        feature_cols.extend(cols);
     }
+    if !opts.asof_features.is_empty() {
+        let with_decision = with_decision_time(&df_feat, date_col, opts.gate)?;
+        let (joined, cols) = join_asof_features(
+            &with_decision,
+            date_col,
+            &opts.asof_features,
+            (opts.from, opts.to),
+        )?;
+        df_feat = joined;
+        feature_cols.extend(cols);
+    }
+    // Filter non-finite only on numeric columns (target + features + baselines)
+    let mut numeric_cols = Vec::new();
+    if forecast_until.is_none() {
+        numeric_cols.push(target_col.to_string());
+    }
+    numeric_cols.extend(feature_cols.iter().cloned());
+    if forecast_until.is_none() {
+        for (_, c) in baselines.iter() {
+            if !numeric_cols.contains(c) {
+                numeric_cols.push(c.clone());
+            }
+        }
+    }
+    let mut cols_needed = numeric_cols.clone();
+    cols_needed.push(date_col.to_string());
     df_feat = drop_nulls_by_cols(&df_feat, &cols_needed)?;
-    // Filter non-finite only on numeric columns (target + lags)
-    let mut numeric_cols = vec![target_col.to_string()];
-    numeric_cols.extend(lag_cols.into_iter());
     df_feat = drop_non_finite_by_cols(&df_feat, &numeric_cols)?;
+    Ok(Features {
+        df: df_feat,
+        feature_cols,
+        baselines,
+        missing_steps,
+    })
+}
+
+// Model of `opts.mode` fitted on `train`.
+pub(crate) enum FittedModel {
+    // With the penalty path when fitted by elastic net.
+    OneStep(Regressor, Option<RegularizationPath>),
+    DayAhead(DayAheadModel),
+}
+
+impl FittedModel {
+    pub fn predict_df(&self, df: &DataFrame) -> Result<Series> {
+        match self {
+            FittedModel::OneStep(m, _) => m.predict_df(df),
+            FittedModel::DayAhead(m) => m.predict_df(df),
+        }
+    }
+}
+
+pub(crate) fn fit_model(
+    train: &DataFrame,
+    feature_cols: &[String],
+    opts: &TrainEvalOptions,
+) -> Result<FittedModel> {
+    let target_col = opts.target_col.as_str();
+    Ok(match opts.mode {
+        ForecastMode::OneStep => match opts.estimator {
+            Estimator::Linear(LinearSolver::ElasticNet(o)) => {
+                let fit = fit_elastic_net(train, target_col, feature_cols, &o)?;
+                FittedModel::OneStep(Regressor::Linear(fit.model), Some(fit.path))
+            }
+            ref estimator => FittedModel::OneStep(
+                fit_regressor(train, target_col, feature_cols, estimator)?,
+                None,
+            ),
+        },
+        ForecastMode::DayAhead => FittedModel::DayAhead(fit_day_ahead(
+            train,
+            target_col,
+            feature_cols,
+            opts.step,
+            &opts.estimator,
+        )?),
+    })
+}
+
+pub(crate) fn target_series(df: &DataFrame, col: &str) -> Result<Series> {
+    Ok(df.column(col)?.as_materialized_series().clone())
+}
+
+// Score the (name, column) baselines of `df` against `target_col`.
+pub(crate) fn score_baselines(
+    df: &DataFrame,
+    target_col: &str,
+    baselines: &[(String, String)],
+) -> Result<Vec<BaselineScore>> {
+    let y_true = target_series(df, target_col)?;
+    baselines
+        .iter()
+        .map(|(name, col)| {
+            let yhat = target_series(df, col)?;
+            Ok(BaselineScore {
+                name: name.clone(),
+                mae: mean_absolute_error(&y_true, &yhat)?,
+                rmse: root_mean_squared_error(&y_true, &yhat)?,
+            })
+        })
+        .collect()
+}
 
-    let (train, test) = train_test_split_by_year(&df_feat, date_col, cutoff_year)?;
-    let feature_cols: Vec<String> = (1..=lags).map(|i| format!("lag_{}", i)).collect();
+pub fn train_eval(prices_dir: &Path, opts: &TrainEvalOptions) -> Result<TrainEvalResult> {
+    let (target_col, date_col) = (opts.target_col.as_str(), opts.date_col.as_str());
+    let Features {
+        df: df_feat,
+        feature_cols,
+        baselines: baseline_cols,
+        missing_steps,
+    } = build_features(prices_dir, opts)?;
+    let (train, test) = train_test_split_by_year(&df_feat, date_col, opts.cutoff_year)?;
 
     let train_n = train.height();
     let test_n = test.height();
 
-    // Baseline: persistence (yhat = lag_1)
-    let baseline = if test_n > 0 {
-        let y_true = test
-            .column(target_col)?
-            .as_series()
-            .ok_or_else(|| anyhow!("expected Series for target column"))?
-            .clone();
-        let yhat = test
-            .column("lag_1")?
-            .as_series()
-            .ok_or_else(|| anyhow!("expected Series for lag_1"))?
-            .clone();
-        Some((
-            mean_absolute_error(&y_true, &yhat)?,
-            root_mean_squared_error(&y_true, &yhat)?,
-        ))
+    let baselines = if test_n > 0 {
+        score_baselines(&test, target_col, &baseline_cols)?
     } else {
-        None
+        Vec::new()
     };
 
-    if train_n == 0 || test_n == 0 {
-        return Ok(TrainEvalResult {
-            model: None,
-            mae: f64::NAN,
-            rmse: f64::NAN,
-            train_n,
-            test_n,
-            baseline_mae: baseline.map(|b| b.0),
-            baseline_rmse: baseline.map(|b| b.1),
+    let mut result = TrainEvalResult {
+        model: None,
+        path: None,
+        day_ahead_model: None,
+        mae: f64::NAN,
+        rmse: f64::NAN,
+        train_n,
+        test_n,
+        training_window: None,
+        baselines,
+        missing_steps,
+        quantiles: Vec::new(),
+        intervals: Vec::new(),
+        quantile_forecasts: None,
+        conformal: Vec::new(),
+        conformal_forecasts: None,
+    };
+    let train_times = column_utc(train.column(date_col)?)?;
+    let mut train_times = train_times.iter().flatten();
+    if let Some(&first) = train_times.next() {
+        result.training_window = Some(TrainingWindow {
+            first,
+            last: train_times.last().copied().unwrap_or(first),
+            rows: train_n,
         });
     }
+    if train_n == 0 {
+        return Ok(result);
+    }
+    // Quantile estimators up front so an unsupported one fails before any fitting.
+    let mut quantiles = opts.quantiles.clone();
+    quantiles.sort_by(f64::total_cmp);
+    quantiles.dedup();
+    let quantile_models: Vec<(f64, Estimator)> = quantiles
+        .iter()
+        .map(|&q| Ok((q, quantile_estimator(&opts.estimator, q)?)))
+        .collect::<Result<_>>()?;
 
-    let model = fit_linear_gd(&train, target_col, &feature_cols, learning_rate, epochs)?;
-    let y_true = test
-        .column(target_col)?
-        .as_series()
-        .ok_or_else(|| anyhow!("expected Series for target column"))?
-        .clone();
-    let y_pred = predict_df(&model, &test)?;
-    let mae = mean_absolute_error(&y_true, &y_pred)?;
-    let rmse = root_mean_squared_error(&y_true, &y_pred)?;
-    Ok(TrainEvalResult {
-        model: Some(model),
-        mae,
-        rmse,
-        train_n,
-        test_n,
-        baseline_mae: baseline.map(|b| b.0),
-        baseline_rmse: baseline.map(|b| b.1),
-    })
+    let fitted = fit_model(&train, &feature_cols, opts)?;
+    let y_pred = (test_n > 0).then(|| fitted.predict_df(&test)).transpose()?;
+    match fitted {
+        FittedModel::OneStep(model, path) => {
+            result.model = Some(model);
+            result.path = path;
+        }
+        FittedModel::DayAhead(model) => result.day_ahead_model = Some(model),
+    }
+    // Without test rows (e.g. a final fit on all data) the model is returned unscored.
+    let Some(y_pred) = y_pred else {
+        return Ok(result);
+    };
+    let y_true = target_series(&test, target_col)?;
+    result.mae = mean_absolute_error(&y_true, &y_pred)?;
+    result.rmse = root_mean_squared_error(&y_true, &y_pred)?;
+
+    if !quantiles.is_empty() {
+        let forecasts = quantile_forecasts(&train, &test, &feature_cols, opts, &quantile_models)?;
+        result.quantiles = quantile_scores(&y_true, &quantiles, &forecasts)?;
+        result.intervals = interval_scores(&y_true, &quantiles, &forecasts)?;
+        let mut columns = vec![test.column(date_col)?.clone(), y_true.into()];
+        columns.extend(forecasts.into_iter().map(Column::from));
+        result.quantile_forecasts = Some(DataFrame::new(columns)?);
+    }
+    if let Some(conf) = &opts.conformal {
+        let (scores, forecasts) =
+            conformal::conformal_forecasts(&train, &test, &feature_cols, opts, conf)?;
+        result.conformal = scores;
+        result.conformal_forecasts = Some(forecasts);
+    }
+    Ok(result)
+}
+
+// Forecasts of `test` by each (quantile, estimator) of `models` (quantiles increasing), fitted on
+// `train` in `opts.mode` and sorted per row so they do not cross.
+fn quantile_forecasts(
+    train: &DataFrame,
+    test: &DataFrame,
+    feature_cols: &[String],
+    opts: &TrainEvalOptions,
+    models: &[(f64, Estimator)],
+) -> Result<Vec<Series>> {
+    let forecasts: Vec<Series> = models
+        .iter()
+        .map(|&(q, estimator)| {
+            let q_opts = TrainEvalOptions {
+                estimator,
+                ..opts.clone()
+            };
+            let yhat = fit_model(train, feature_cols, &q_opts)?.predict_df(test)?;
+            Ok(yhat.with_name(quantile_col(q).into()))
+        })
+        .collect::<Result<_>>()?;
+    non_crossing(&forecasts)
 }
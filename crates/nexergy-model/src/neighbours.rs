@@ -0,0 +1,93 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use nexergy_core::time::column_utc;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// Neighbour day-ahead prices joined as features from the multi-zone dataset written by
+// `ingest-entsoe zone-prices` (columns zone, datetime_utc, price_eur_mwh).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NeighbourFeatures {
+    pub zone_prices_dir: PathBuf,
+    pub zones: Vec<String>,
+    // Lags (in steps) of the joined price and spread columns; neighbour prices clear together with
+    // NL, so same-hour values are not available when forecasting.
+    pub lags: Vec<usize>,
+}
+
+// Column-safe zone name: "DE-LU" -> "de_lu".
+pub fn zone_slug(zone: &str) -> String {
+    zone.to_ascii_lowercase().replace('-', "_")
+}
+
+// Join zone prices onto `df` by UTC instant: adds price_<zone> and spread_<zone> (target minus zone
+// price) per zone, null where the zone has no price. Returns the frame and the added column names.
+pub fn join_zone_prices(
+    df: &DataFrame,
+    zone_prices: &DataFrame,
+    key_col: &str,
+    target_col: &str,
+    zones: &[String],
+) -> Result<(DataFrame, Vec<String>)> {
+    let zp_zone = zone_prices.column("zone")?.str()?;
+    let zp_ts = column_utc(zone_prices.column("datetime_utc")?)?;
+    let zp_price = zone_prices.column("price_eur_mwh")?.f64()?;
+    let mut lookup: HashMap<(&str, NaiveDateTime), f64> =
+        HashMap::with_capacity(zone_prices.height());
+    for (i, t) in zp_ts.into_iter().enumerate() {
+        if let (Some(z), Some(t), Some(p)) = (zp_zone.get(i), t, zp_price.get(i)) {
+            lookup.insert((z, t), p);
+        }
+    }
+
+    let keys = column_utc(df.column(key_col)?)?;
+    let target = df.column(target_col)?.f64()?;
+    let mut out = df.clone();
+    let mut added = Vec::with_capacity(zones.len() * 2);
+    for zone in zones {
+        if !zp_zone.into_iter().any(|z| z == Some(zone.as_str())) {
+            return Err(anyhow!("no prices for zone {zone} in zone price dataset"));
+        }
+        let price: Vec<Option<f64>> = keys
+            .iter()
+            .map(|k| k.and_then(|k| lookup.get(&(zone.as_str(), k)).copied()))
+            .collect();
+        let spread: Vec<Option<f64>> = target
+            .into_iter()
+            .zip(price.iter())
+            .map(|(t, p)| Some(t? - (*p)?))
+            .collect();
+        let slug = zone_slug(zone);
+        let (price_col, spread_col) = (format!("price_{slug}"), format!("spread_{slug}"));
+        out.hstack_mut(&[
+            Column::new(price_col.as_str().into(), price),
+            Column::new(spread_col.as_str().into(), spread),
+        ])?;
+        added.push(price_col);
+        added.push(spread_col);
+    }
+    Ok((out, added))
+}
+
+// Row-lagged copies `<col>_lag_<k>` of `cols`; `df` must be on a complete time grid (see
+// reindex_to_grid) for row lags to be time lags.
+pub fn lag_columns(
+    df: &DataFrame,
+    cols: &[String],
+    lags: &[usize],
+) -> Result<(DataFrame, Vec<String>)> {
+    let mut out = df.clone();
+    let mut added = Vec::with_capacity(cols.len() * lags.len());
+    for c in cols {
+        let s = df.column(c)?.as_materialized_series().clone();
+        for &lag in lags {
+            let name = format!("{c}_lag_{lag}");
+            let shifted = s.shift(lag as i64).with_name(name.as_str().into());
+            out.hstack_mut(&[shifted.into()])?;
+            added.push(name);
+        }
+    }
+    Ok((out, added))
+}
@@ -0,0 +1,417 @@
+use crate::gbt::Loss;
+use crate::ridge::{cholesky_solve, MIN_VARIANCE};
+use crate::{Estimator, GbtOptions, LinearModel, LinearSolver, Standardized};
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+use rayon::prelude::*;
+
+// IRLS stops after this many reweightings or when no standardized weight moves more than IRLS_TOL.
+const IRLS_MAX_ITER: usize = 100;
+const IRLS_TOL: f64 = 1e-6;
+// Residuals (standardized scale) are floored at this size when reweighting.
+const IRLS_EPS: f64 = 1e-6;
+
+// Pinball (quantile) loss of residual `r` = actual - forecast at quantile `q`.
+pub(crate) fn pinball(r: f64, q: f64) -> f64 {
+    if r >= 0.0 {
+        q * r
+    } else {
+        (q - 1.0) * r
+    }
+}
+
+// Empirical `q`-quantile of `values` (reordered in place): the smallest value with at least a share q
+// of the values at or below it, which minimises the pinball loss. NaN when empty.
+pub(crate) fn quantile_of(values: &mut [f64], q: f64) -> f64 {
+    if values.is_empty() {
+        return f64::NAN;
+    }
+    let k = ((values.len() as f64 * q).ceil() as usize).clamp(1, values.len()) - 1;
+    *values.select_nth_unstable_by(k, f64::total_cmp).1
+}
+
+// Column holding the forecast of quantile `q`, e.g. q5 or q97.5.
+pub fn quantile_col(q: f64) -> String {
+    format!("q{}", (q * 100.0 * 1e6).round() / 1e6)
+}
+
+// Estimator fitting the `q`-quantile instead of the mean: linear models switch to quantile regression
+// (keeping a ridge penalty if set) and boosting to the pinball loss.
+pub fn quantile_estimator(estimator: &Estimator, q: f64) -> Result<Estimator> {
+    if !(q > 0.0 && q < 1.0) {
+        return Err(anyhow!("quantiles must be in (0, 1), got {q}"));
+    }
+    match estimator {
+        Estimator::Linear(solver) => {
+            let l2 = match solver {
+                LinearSolver::Ridge { l2 } | LinearSolver::Quantile { l2, .. } => *l2,
+                _ => 0.01,
+            };
+            Ok(Estimator::Linear(LinearSolver::Quantile {
+                quantile: q,
+                l2,
+            }))
+        }
+        Estimator::GradientBoosting(o) => Ok(Estimator::GradientBoosting(GbtOptions {
+            loss: Loss::Quantile(q),
+            ..*o
+        })),
+        Estimator::RandomForest(_) => Err(anyhow!(
+            "quantile forecasts need a linear or gradient-boosting estimator"
+        )),
+    }
+}
+
+// Linear quantile regression on standardized features: minimises
+// (1/n)·Σ ρ_q(y - w0 - x·w) + (l2/2)·|w|² by iteratively reweighted least squares, replacing ρ_q(r)
+// with the quadratic c·r²/(2|r'|) that touches it at the previous residual r' (c = q above the line,
+// 1 - q below). The intercept is not penalised and constant features get weight 0.
+pub fn fit_quantile_linear(
+    df: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    q: f64,
+    l2: f64,
+) -> Result<LinearModel> {
+    if !(q > 0.0 && q < 1.0) {
+        return Err(anyhow!("quantile must be in (0, 1), got {q}"));
+    }
+    if !(l2 >= 0.0 && l2.is_finite()) {
+        return Err(anyhow!(
+            "L2 penalty must be finite and non-negative, got {l2}"
+        ));
+    }
+    let data = Standardized::new(df, target_col, feature_cols)?;
+    let (n, p) = (data.len(), feature_cols.len());
+    if n == 0 {
+        return Err(anyhow!("no training rows"));
+    }
+
+    // Non-constant features; column 0 of the system is the intercept.
+    let active: Vec<usize> = {
+        let sq = (0..n)
+            .into_par_iter()
+            .fold(
+                || (vec![0.0; p], vec![0.0; p]),
+                |(mut sq, mut x), i| {
+                    data.row_into(i, &mut x);
+                    sq.iter_mut().zip(x.iter()).for_each(|(s, v)| *s += v * v);
+                    (sq, x)
+                },
+            )
+            .map(|(sq, _)| sq)
+            .reduce(
+                || vec![0.0; p],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    a
+                },
+            );
+        (0..p)
+            .filter(|&j| sq[j] / n as f64 > MIN_VARIANCE)
+            .collect()
+    };
+    let k = active.len() + 1;
+    let row = |i: usize, x: &mut Vec<f64>, z: &mut [f64]| {
+        data.row_into(i, x);
+        z[0] = 1.0;
+        for (zj, &j) in z[1..].iter_mut().zip(active.iter()) {
+            *zj = x[j];
+        }
+    };
+
+    // Least squares (equal weights) to start from.
+    let mut w = vec![0.0; k];
+    let mut first = true;
+    for _ in 0..IRLS_MAX_ITER {
+        let (mut a, b) = (0..n)
+            .into_par_iter()
+            .fold(
+                || (vec![0.0; k * k], vec![0.0; k], vec![0.0; p], vec![0.0; k]),
+                |(mut a, mut b, mut x, mut z), i| {
+                    row(i, &mut x, &mut z);
+                    let y = data.y(i);
+                    let v = if first {
+                        1.0
+                    } else {
+                        let r = y - z.iter().zip(w.iter()).map(|(z, w)| z * w).sum::<f64>();
+                        let c = if r >= 0.0 { q } else { 1.0 - q };
+                        c / r.abs().max(IRLS_EPS)
+                    };
+                    for r in 0..k {
+                        let vz = v * z[r];
+                        b[r] += vz * y;
+                        for c in r..k {
+                            a[r * k + c] += vz * z[c];
+                        }
+                    }
+                    (a, b, x, z)
+                },
+            )
+            .map(|(a, b, _, _)| (a, b))
+            .reduce(
+                || (vec![0.0; k * k], vec![0.0; k]),
+                |(mut a1, mut b1), (a2, b2)| {
+                    a1.iter_mut().zip(a2).for_each(|(a, b)| *a += b);
+                    b1.iter_mut().zip(b2).for_each(|(a, b)| *a += b);
+                    (a1, b1)
+                },
+            );
+        // (ZᵀVZ + n·l2·I) w = ZᵀVy, with the intercept unpenalised.
+        let penalty = l2 * n as f64;
+        for r in 0..k {
+            for c in 0..r {
+                a[r * k + c] = a[c * k + r];
+            }
+            if r > 0 {
+                a[r * k + r] += penalty;
+            }
+        }
+        let next = cholesky_solve(a, k, b).ok_or_else(|| {
+            anyhow!("quantile regression system is singular (collinear features); use a positive L2 penalty")
+        })?;
+        let step = next
+            .iter()
+            .zip(w.iter())
+            .fold(0.0_f64, |m, (a, b)| m.max((a - b).abs()));
+        w = next;
+        if !first && step < IRLS_TOL {
+            break;
+        }
+        first = false;
+    }
+
+    let mut weights = vec![0.0; p + 1];
+    weights[0] = w[0];
+    for (&j, v) in active.iter().zip(w[1..].iter()) {
+        weights[j + 1] = *v;
+    }
+    Ok(data.into_model(feature_cols, weights))
+}
+
+// Sort every row's quantile forecasts (one series per quantile, in increasing quantile order) so
+// they cannot cross; rearranging never increases the pinball loss summed over the quantiles.
+pub fn non_crossing(forecasts: &[Series]) -> Result<Vec<Series>> {
+    let cols: Vec<&Float64Chunked> = forecasts
+        .iter()
+        .map(|s| s.f64())
+        .collect::<PolarsResult<_>>()?;
+    let n = cols.first().map_or(0, |c| c.len());
+    let mut out: Vec<Vec<Option<f64>>> = vec![Vec::with_capacity(n); cols.len()];
+    let mut row: Vec<f64> = Vec::with_capacity(cols.len());
+    for i in 0..n {
+        row.clear();
+        row.extend(cols.iter().filter_map(|c| c.get(i)));
+        if row.len() == cols.len() {
+            row.sort_by(f64::total_cmp);
+            for (o, v) in out.iter_mut().zip(row.iter()) {
+                o.push(Some(*v));
+            }
+        } else {
+            for (o, c) in out.iter_mut().zip(cols.iter()) {
+                o.push(c.get(i));
+            }
+        }
+    }
+    Ok(forecasts
+        .iter()
+        .zip(out)
+        .map(|(s, v)| Series::new(s.name().clone(), v))
+        .collect())
+}
+
+// Test-set score of one quantile forecast.
+#[derive(Debug, Clone)]
+pub struct QuantileScore {
+    pub quantile: f64,
+    pub pinball: f64,
+    // Share of actuals at or below the forecast (ideally the quantile).
+    pub coverage: f64,
+}
+
+// Test-set score of the central interval between the `lower` and `upper` quantile forecasts.
+#[derive(Debug, Clone)]
+pub struct IntervalScore {
+    pub lower: f64,
+    pub upper: f64,
+    // Share of actuals inside the interval (ideally upper - lower).
+    pub coverage: f64,
+    pub mean_width: f64,
+}
+
+// Values of rows where the actual and every forecast are present.
+fn complete_rows<'a>(
+    y_true: &'a Float64Chunked,
+    preds: &'a [&'a Float64Chunked],
+) -> impl Iterator<Item = (f64, Vec<f64>)> + 'a {
+    (0..y_true.len()).filter_map(move |i| {
+        let y = y_true.get(i)?;
+        let p: Option<Vec<f64>> = preds.iter().map(|c| c.get(i)).collect();
+        Some((y, p?))
+    })
+}
+
+pub fn quantile_scores(
+    y_true: &Series,
+    quantiles: &[f64],
+    forecasts: &[Series],
+) -> Result<Vec<QuantileScore>> {
+    let y = y_true.f64()?;
+    quantiles
+        .iter()
+        .zip(forecasts)
+        .map(|(&q, f)| {
+            let f = f.f64()?;
+            let (mut loss, mut below, mut n) = (0.0, 0usize, 0usize);
+            for (y, p) in complete_rows(y, &[f]) {
+                loss += pinball(y - p[0], q);
+                below += usize::from(y <= p[0]);
+                n += 1;
+            }
+            let n = n as f64;
+            Ok(QuantileScore {
+                quantile: q,
+                pinball: loss / n,
+                coverage: below as f64 / n,
+            })
+        })
+        .collect()
+}
+
+// Scores of the intervals formed by symmetric pairs of `quantiles` (q and 1 - q), widest first.
+pub fn interval_scores(
+    y_true: &Series,
+    quantiles: &[f64],
+    forecasts: &[Series],
+) -> Result<Vec<IntervalScore>> {
+    let y = y_true.f64()?;
+    let mut out = Vec::new();
+    for (i, &lower) in quantiles.iter().enumerate().filter(|(_, q)| **q < 0.5) {
+        let Some(j) = quantiles
+            .iter()
+            .position(|u| (u - (1.0 - lower)).abs() < 1e-9)
+        else {
+            continue;
+        };
+        let (lo, hi) = (forecasts[i].f64()?, forecasts[j].f64()?);
+        let (mut inside, mut width, mut n) = (0usize, 0.0, 0usize);
+        for (y, p) in complete_rows(y, &[lo, hi]) {
+            inside += usize::from(p[0] <= y && y <= p[1]);
+            width += p[1] - p[0];
+            n += 1;
+        }
+        let n = n as f64;
+        out.push(IntervalScore {
+            lower,
+            upper: quantiles[j],
+            coverage: inside as f64 / n,
+            mean_width: width / n,
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gbt::fit_gbt;
+    use crate::ridge::tests::features;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // y = 2 + 3·x0 plus right-skewed (exponential, mean 4) noise, so the quantiles are not a shift
+    // of the mean by a symmetric amount.
+    fn skewed(n: usize, seed: u64) -> DataFrame {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let x0: Vec<f64> = (0..n).map(|_| rng.random_range(-5.0..5.0)).collect();
+        let y: Vec<f64> = x0
+            .iter()
+            .map(|x| {
+                let u: f64 = rng.random();
+                2.0 + 3.0 * x - 4.0 * (1.0 - u).ln()
+            })
+            .collect();
+        df!("x0" => x0, "y" => y).unwrap()
+    }
+
+    // Share of rows whose target is at or below the prediction.
+    fn share_below(df: &DataFrame, predict: impl Fn(f64) -> f64) -> f64 {
+        let x = df.column("x0").unwrap().f64().unwrap();
+        let y = df.column("y").unwrap().f64().unwrap();
+        let below = x
+            .into_no_null_iter()
+            .zip(y.into_no_null_iter())
+            .filter(|(x, y)| *y <= predict(*x))
+            .count();
+        below as f64 / df.height() as f64
+    }
+
+    #[test]
+    fn linear_quantiles_put_q_of_the_residuals_below_the_fit() {
+        let (train, test) = (skewed(3000, 1), skewed(3000, 2));
+        for q in [0.1, 0.5, 0.9] {
+            let model = fit_quantile_linear(&train, "y", &features(1), q, 0.0).unwrap();
+            let fitted = share_below(&train, |x| model.predict_row(&[x]));
+            let held_out = share_below(&test, |x| model.predict_row(&[x]));
+            assert!(
+                (fitted - q).abs() < 0.01,
+                "q {q}: {fitted} below on training rows"
+            );
+            assert!(
+                (held_out - q).abs() < 0.03,
+                "q {q}: {held_out} below on new rows"
+            );
+            // The slope is that of the mean; only the level moves with q.
+            let slope = model.predict_row(&[1.0]) - model.predict_row(&[0.0]);
+            assert!((slope - 3.0).abs() < 0.15, "q {q}: slope {slope}");
+        }
+    }
+
+    #[test]
+    fn boosted_quantiles_put_q_of_the_targets_below_the_forecast() {
+        let (train, test) = (skewed(3000, 3), skewed(2000, 4));
+        for q in [0.1, 0.9] {
+            let opts = GbtOptions {
+                loss: Loss::Quantile(q),
+                n_trees: 200,
+                ..GbtOptions::default()
+            };
+            let model = fit_gbt(&train, "y", &features(1), &opts).unwrap();
+            let held_out = share_below(&test, |x| model.predict_row(&[x]));
+            assert!((held_out - q).abs() < 0.05, "q {q}: {held_out} below");
+        }
+    }
+
+    #[test]
+    fn pinball_and_empirical_quantiles() {
+        assert_eq!(pinball(2.0, 0.9), 1.8);
+        assert!((pinball(-2.0, 0.9) - 0.2).abs() < 1e-12);
+        let mut v = vec![5.0, 1.0, 4.0, 2.0, 3.0];
+        assert_eq!(quantile_of(&mut v, 0.5), 3.0);
+        assert_eq!(quantile_of(&mut v, 0.2), 1.0);
+        assert_eq!(quantile_of(&mut v, 0.21), 2.0);
+        assert_eq!(quantile_of(&mut v, 1.0), 5.0);
+        assert!(quantile_of(&mut [], 0.5).is_nan());
+        assert_eq!(quantile_col(0.05), "q5");
+        assert_eq!(quantile_col(0.975), "q97.5");
+    }
+
+    #[test]
+    fn crossing_forecasts_are_sorted_per_row_and_scored() {
+        let lo = Series::new("q10".into(), [1.0, 5.0, 2.0]);
+        let hi = Series::new("q90".into(), [3.0, 4.0, 6.0]);
+        let sorted = non_crossing(&[lo, hi]).unwrap();
+        let values = |s: &Series| -> Vec<f64> { s.f64().unwrap().into_no_null_iter().collect() };
+        assert_eq!(values(&sorted[0]), vec![1.0, 4.0, 2.0]);
+        assert_eq!(values(&sorted[1]), vec![3.0, 5.0, 6.0]);
+        assert_eq!(sorted[0].name().as_str(), "q10");
+
+        let actual = Series::new("y".into(), [2.0, 6.0, 1.0]);
+        let intervals = interval_scores(&actual, &[0.1, 0.9], &sorted).unwrap();
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].coverage - 1.0 / 3.0).abs() < 1e-12);
+        assert!((intervals[0].mean_width - 7.0 / 3.0).abs() < 1e-12);
+        let scores = quantile_scores(&actual, &[0.1, 0.9], &sorted).unwrap();
+        assert!((scores[1].coverage - 2.0 / 3.0).abs() < 1e-12);
+    }
+}
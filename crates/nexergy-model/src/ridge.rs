@@ -0,0 +1,381 @@
+use crate::{LinearModel, Standardized};
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+use rayon::prelude::*;
+use std::ops::Range;
+
+// Below this variance (standardized scale) a feature is treated as constant and gets weight 0.
+pub(crate) const MIN_VARIANCE: f64 = 1e-12;
+
+// Second moments of a range of standardized rows, centred on that range's means and divided by its
+// length: XᵀX/n (full p×p, row-major), Xᵀy/n and yᵀy/n.
+pub(crate) struct Moments {
+    pub gram: Vec<f64>,
+    pub xty: Vec<f64>,
+    pub yty: f64,
+    pub x_mean: Vec<f64>,
+    pub y_mean: f64,
+}
+
+impl Moments {
+    pub fn new(data: &Standardized, rows: Range<usize>) -> Self {
+        let p = data.feats.len();
+        let n = rows.len();
+        let mut x = vec![0.0; p];
+        let mut x_mean = vec![0.0; p];
+        let mut y_mean = 0.0;
+        for i in rows.clone() {
+            data.row_into(i, &mut x);
+            for (m, v) in x_mean.iter_mut().zip(x.iter()) {
+                *m += v;
+            }
+            y_mean += data.y(i);
+        }
+        let scale = if n > 0 { 1.0 / n as f64 } else { 0.0 };
+        for m in x_mean.iter_mut() {
+            *m *= scale;
+        }
+        y_mean *= scale;
+
+        // Upper triangle, accumulated per thread.
+        let (mut gram, mut xty, mut yty) = rows
+            .into_par_iter()
+            .fold(
+                || (vec![0.0; p * p], vec![0.0; p], 0.0, vec![0.0; p]),
+                |(mut gram, mut xty, mut yty, mut x), i| {
+                    data.row_into(i, &mut x);
+                    for (v, m) in x.iter_mut().zip(x_mean.iter()) {
+                        *v -= m;
+                    }
+                    let yi = data.y(i) - y_mean;
+                    yty += yi * yi;
+                    for j in 0..p {
+                        xty[j] += x[j] * yi;
+                        for k in j..p {
+                            gram[j * p + k] += x[j] * x[k];
+                        }
+                    }
+                    (gram, xty, yty, x)
+                },
+            )
+            .map(|(gram, xty, yty, _)| (gram, xty, yty))
+            .reduce(
+                || (vec![0.0; p * p], vec![0.0; p], 0.0),
+                |(mut g1, mut b1, y1), (g2, b2, y2)| {
+                    g1.iter_mut().zip(g2).for_each(|(a, b)| *a += b);
+                    b1.iter_mut().zip(b2).for_each(|(a, b)| *a += b);
+                    (g1, b1, y1 + y2)
+                },
+            );
+        for j in 0..p {
+            for k in j..p {
+                gram[j * p + k] *= scale;
+                gram[k * p + j] = gram[j * p + k];
+            }
+            xty[j] *= scale;
+        }
+        yty *= scale;
+        Self {
+            gram,
+            xty,
+            yty,
+            x_mean,
+            y_mean,
+        }
+    }
+
+    // Intercept that goes with weights `w` fitted on the centred data.
+    pub fn intercept(&self, w: &[f64]) -> f64 {
+        self.y_mean
+            - w.iter()
+                .zip(self.x_mean.iter())
+                .map(|(w, m)| w * m)
+                .sum::<f64>()
+    }
+
+    // Mean squared residual of `w` on these rows: yᵀy/n - 2·wᵀXᵀy/n + wᵀ(XᵀX/n)w.
+    pub fn mse(&self, w: &[f64]) -> f64 {
+        let p = w.len();
+        let mut quad = 0.0;
+        for j in 0..p {
+            if w[j] != 0.0 {
+                let gw: f64 = (0..p).map(|k| self.gram[j * p + k] * w[k]).sum();
+                quad += w[j] * gw;
+            }
+        }
+        let lin: f64 = w.iter().zip(self.xty.iter()).map(|(w, b)| w * b).sum();
+        (self.yty - 2.0 * lin + quad).max(0.0)
+    }
+}
+
+// Ridge regression in closed form. On the standardized data (see Standardized) it minimises
+// (1/2n)·Σ(y - w0 - x·w)² + (l2/2)·|w|² by solving (XᵀX/n + l2·I) w = Xᵀy/n for centred X and y with a
+// Cholesky factorisation; the intercept w0 is not penalised. Constant features get weight 0. With
+// l2 = 0 this is ordinary least squares, which fails on perfectly collinear features.
+pub fn fit_ridge(
+    df: &DataFrame,
+    target_col: &str,
+    feature_cols: &[String],
+    l2: f64,
+) -> Result<LinearModel> {
+    if !(l2 >= 0.0 && l2.is_finite()) {
+        return Err(anyhow!(
+            "L2 penalty must be finite and non-negative, got {l2}"
+        ));
+    }
+    let data = Standardized::new(df, target_col, feature_cols)?;
+    let (n, p) = (data.len(), feature_cols.len());
+    if n == 0 {
+        return Err(anyhow!("no training rows"));
+    }
+
+    let m = Moments::new(&data, 0..n);
+    let active: Vec<usize> = (0..p)
+        .filter(|&j| m.gram[j * p + j] > MIN_VARIANCE)
+        .collect();
+    let k = active.len();
+    let mut a = vec![0.0; k * k];
+    for (r, &j) in active.iter().enumerate() {
+        for (c, &l) in active.iter().enumerate() {
+            a[r * k + c] = m.gram[j * p + l];
+        }
+        a[r * k + r] += l2;
+    }
+    let b: Vec<f64> = active.iter().map(|&j| m.xty[j]).collect();
+    let solved = cholesky_solve(a, k, b).ok_or_else(|| {
+        anyhow!("ridge system is singular (collinear features); use a positive L2 penalty")
+    })?;
+
+    let mut w = vec![0.0; p + 1];
+    for (&j, v) in active.iter().zip(solved) {
+        w[j + 1] = v;
+    }
+    w[0] = m.intercept(&w[1..]);
+    Ok(data.into_model(feature_cols, w))
+}
+
+// Solve A·x = b for a symmetric positive definite m×m matrix A (row-major) via A = L·Lᵀ. None when A
+// is not positive definite.
+pub(crate) fn cholesky_solve(mut a: Vec<f64>, m: usize, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    // L overwrites the lower triangle of A.
+    for j in 0..m {
+        let mut d = a[j * m + j];
+        for k in 0..j {
+            d -= a[j * m + k] * a[j * m + k];
+        }
+        if d <= MIN_VARIANCE * 1e-3 || !d.is_finite() {
+            return None;
+        }
+        let d = d.sqrt();
+        a[j * m + j] = d;
+        for i in j + 1..m {
+            let mut v = a[i * m + j];
+            for k in 0..j {
+                v -= a[i * m + k] * a[j * m + k];
+            }
+            a[i * m + j] = v / d;
+        }
+    }
+    // L·z = b, then Lᵀ·x = z.
+    for i in 0..m {
+        for k in 0..i {
+            b[i] -= a[i * m + k] * b[k];
+        }
+        b[i] /= a[i * m + i];
+    }
+    for i in (0..m).rev() {
+        for k in i + 1..m {
+            b[i] -= a[k * m + i] * b[k];
+        }
+        b[i] /= a[i * m + i];
+    }
+    Some(b)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Deterministic uniform draws on [lo, hi) (splitmix64), so fixtures do not depend on a RNG crate.
+    pub(crate) struct Uniform(u64);
+
+    impl Uniform {
+        pub(crate) fn new(seed: u64) -> Self {
+            Self(seed)
+        }
+
+        pub(crate) fn sample(&mut self, lo: f64, hi: f64) -> f64 {
+            self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^= z >> 31;
+            lo + (hi - lo) * (z >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    // `n` rows of features x0, x1, ... uniform on different ranges and y = intercept + x·weights
+    // plus uniform noise of amplitude `noise`.
+    pub(crate) fn synthetic(n: usize, intercept: f64, weights: &[f64], noise: f64) -> DataFrame {
+        let mut rng = Uniform::new(7);
+        let xs: Vec<Vec<f64>> = (0..weights.len())
+            .map(|j| {
+                let scale = (j + 1) as f64 * 10.0;
+                (0..n).map(|_| rng.sample(-scale, scale)).collect()
+            })
+            .collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let signal: f64 = xs.iter().zip(weights).map(|(x, w)| x[i] * w).sum();
+                intercept + signal + noise * rng.sample(-1.0, 1.0)
+            })
+            .collect();
+        let mut columns: Vec<Column> = xs
+            .into_iter()
+            .enumerate()
+            .map(|(j, x)| Column::new(format!("x{j}").into(), x))
+            .collect();
+        columns.push(Column::new("y".into(), y));
+        DataFrame::new(columns).unwrap()
+    }
+
+    pub(crate) fn features(p: usize) -> Vec<String> {
+        (0..p).map(|j| format!("x{j}")).collect()
+    }
+
+    // Intercept and per-feature slopes of a model on the raw (unstandardized) scale.
+    pub(crate) fn raw_coefficients(m: &LinearModel) -> (f64, Vec<f64>) {
+        let slopes: Vec<f64> = m.weights[1..]
+            .iter()
+            .zip(m.feature_stds.iter())
+            .map(|(w, s)| w * m.target_std / s)
+            .collect();
+        let shift: f64 = slopes
+            .iter()
+            .zip(m.feature_means.iter())
+            .map(|(b, mu)| b * mu)
+            .sum();
+        (m.target_mean + m.target_std * m.weights[0] - shift, slopes)
+    }
+
+    // Least squares with an intercept from the normal equations on the raw data, by Gaussian
+    // elimination with partial pivoting.
+    fn ols(df: &DataFrame, p: usize) -> Vec<f64> {
+        let col = |name: &str| -> Vec<f64> {
+            df.column(name)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_no_null_iter()
+                .collect()
+        };
+        let xs: Vec<Vec<f64>> = features(p).iter().map(|c| col(c)).collect();
+        let y = col("y");
+        let k = p + 1;
+        let rows: Vec<Vec<f64>> = (0..y.len())
+            .map(|i| {
+                std::iter::once(1.0)
+                    .chain(xs.iter().map(|x| x[i]))
+                    .collect()
+            })
+            .collect();
+        let mut a: Vec<Vec<f64>> = (0..k)
+            .map(|r| {
+                let mut row: Vec<f64> = (0..k)
+                    .map(|c| rows.iter().map(|z| z[r] * z[c]).sum())
+                    .collect();
+                row.push(rows.iter().zip(&y).map(|(z, y)| z[r] * y).sum());
+                row
+            })
+            .collect();
+        for c in 0..k {
+            let pivot = (c..k)
+                .max_by(|&i, &j| a[i][c].abs().total_cmp(&a[j][c].abs()))
+                .unwrap();
+            a.swap(c, pivot);
+            let pivot_row = a[c].clone();
+            for (_, row) in a.iter_mut().enumerate().filter(|(r, _)| *r != c) {
+                let f = row[c] / pivot_row[c];
+                for (v, p) in row.iter_mut().zip(&pivot_row) {
+                    *v -= f * p;
+                }
+            }
+        }
+        a.iter()
+            .enumerate()
+            .map(|(r, row)| row[k] / row[r])
+            .collect()
+    }
+
+    fn assert_close(a: f64, b: f64, tol: f64) {
+        assert!((a - b).abs() <= tol, "{a} vs {b}");
+    }
+
+    #[test]
+    fn recovers_known_weights_without_noise() {
+        let weights = [2.0, -0.5, 0.0, 1.25];
+        let df = synthetic(200, 3.0, &weights, 0.0);
+        let m = fit_ridge(&df, "y", &features(weights.len()), 0.0).unwrap();
+        let (intercept, slopes) = raw_coefficients(&m);
+        assert_close(intercept, 3.0, 1e-8);
+        for (b, w) in slopes.iter().zip(weights) {
+            assert_close(*b, w, 1e-8);
+        }
+    }
+
+    #[test]
+    fn zero_penalty_is_ordinary_least_squares() {
+        let df = synthetic(300, -1.0, &[0.7, 1.5, -2.0], 5.0);
+        let m = fit_ridge(&df, "y", &features(3), 0.0).unwrap();
+        let (intercept, slopes) = raw_coefficients(&m);
+        let expected = ols(&df, 3);
+        assert_close(intercept, expected[0], 1e-8);
+        for (b, e) in slopes.iter().zip(&expected[1..]) {
+            assert_close(*b, *e, 1e-8);
+        }
+    }
+
+    #[test]
+    fn penalty_shrinks_weights() {
+        let df = synthetic(300, 0.0, &[1.0, 1.0], 1.0);
+        let norm = |l2: f64| -> f64 {
+            let m = fit_ridge(&df, "y", &features(2), l2).unwrap();
+            m.weights[1..].iter().map(|w| w * w).sum()
+        };
+        assert!(norm(1.0) < norm(0.1) && norm(0.1) < norm(0.0));
+    }
+
+    #[test]
+    fn collinear_features_need_a_penalty() {
+        let mut df = synthetic(100, 1.0, &[2.0], 0.5);
+        let doubled = df.column("x0").unwrap().as_materialized_series() * 2.0;
+        df.with_column(doubled.with_name("x1".into())).unwrap();
+        let err = fit_ridge(&df, "y", &features(2), 0.0).unwrap_err();
+        assert!(err.to_string().contains("singular"), "{err}");
+        assert!(fit_ridge(&df, "y", &features(2), 0.01).is_ok());
+    }
+
+    #[test]
+    fn constant_features_get_zero_weight() {
+        let mut df = synthetic(50, 1.0, &[2.0], 0.0);
+        df.with_column(Column::new("x1".into(), vec![4.0; 50]))
+            .unwrap();
+        let m = fit_ridge(&df, "y", &features(2), 0.0).unwrap();
+        assert_eq!(m.weights[2], 0.0);
+        assert_close(raw_coefficients(&m).1[0], 2.0, 1e-8);
+    }
+
+    #[test]
+    fn cholesky_solves_positive_definite_systems_only() {
+        let a = vec![4.0, 2.0, 0.6, 2.0, 5.0, 1.0, 0.6, 1.0, 3.0];
+        let x = [1.0, -2.0, 0.5];
+        let b: Vec<f64> = (0..3)
+            .map(|r| (0..3).map(|c| a[r * 3 + c] * x[c]).sum())
+            .collect();
+        let solved = cholesky_solve(a, 3, b).unwrap();
+        for (s, e) in solved.iter().zip(x) {
+            assert_close(*s, e, 1e-12);
+        }
+        assert!(cholesky_solve(vec![1.0, 2.0, 2.0, 1.0], 2, vec![1.0, 1.0]).is_none());
+    }
+}
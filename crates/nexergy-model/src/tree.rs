@@ -0,0 +1,372 @@
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Feature columns as f64 (nulls as NaN) in `feature_cols` order.
+pub(crate) fn feature_matrix(df: &DataFrame, feature_cols: &[String]) -> Result<Vec<Vec<f64>>> {
+    if feature_cols.is_empty() {
+        return Err(anyhow!("no features provided"));
+    }
+    feature_cols
+        .iter()
+        .map(|c| {
+            Ok(df
+                .column(c)?
+                .cast(&DataType::Float64)?
+                .f64()?
+                .iter()
+                .map(|v| v.unwrap_or(f64::NAN))
+                .collect())
+        })
+        .collect()
+}
+
+// Features quantised into at most 256 bins. Bin b of feature f holds the values in
+// (edges[f][b-1], edges[f][b]]; values above the last edge fall in the last bin and NaN in bin 0, so
+// `bin <= b` is the same test as `x <= edges[f][b]` (or NaN).
+pub(crate) struct Binned {
+    pub edges: Vec<Vec<f64>>,
+    // Column-major: bins[f][row].
+    pub bins: Vec<Vec<u8>>,
+}
+
+impl Binned {
+    // Edges from the values of `fit_rows` (at most max_bins - 1 per feature, at quantiles when there
+    // are more distinct values); every row is binned.
+    pub fn new(x: &[Vec<f64>], fit_rows: usize, max_bins: usize) -> Self {
+        let max_edges = max_bins.clamp(2, 256) - 1;
+        let edges: Vec<Vec<f64>> = x
+            .par_iter()
+            .map(|col| {
+                let mut v: Vec<f64> = col[..fit_rows]
+                    .iter()
+                    .copied()
+                    .filter(|v| !v.is_nan())
+                    .collect();
+                v.sort_by(f64::total_cmp);
+                v.dedup();
+                if v.len() <= max_edges {
+                    v
+                } else {
+                    let mut e: Vec<f64> = (1..=max_edges)
+                        .map(|i| v[(i * v.len() / (max_edges + 1)).min(v.len() - 1)])
+                        .collect();
+                    e.dedup();
+                    e
+                }
+            })
+            .collect();
+        let bins = x
+            .par_iter()
+            .zip(edges.par_iter())
+            .map(|(col, e)| {
+                col.iter()
+                    .map(|&v| {
+                        if v.is_nan() {
+                            0
+                        } else {
+                            e.partition_point(|edge| *edge < v).min(255) as u8
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { edges, bins }
+    }
+
+    pub fn n_features(&self) -> usize {
+        self.bins.len()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Node {
+    // Rows with `x[feature] <= threshold` (or NaN) go left; `bin` is the same test on binned rows.
+    Split {
+        feature: usize,
+        threshold: f64,
+        bin: u8,
+        left: usize,
+        right: usize,
+    },
+    Leaf(f64),
+}
+
+// Regression tree; the root is node 0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tree {
+    pub nodes: Vec<Node>,
+}
+
+impl Tree {
+    pub fn predict_row(&self, x: &[f64]) -> f64 {
+        let mut i = 0;
+        loop {
+            match self.nodes[i] {
+                Node::Split {
+                    feature,
+                    threshold,
+                    left,
+                    right,
+                    ..
+                } => i = if x[feature] > threshold { right } else { left },
+                Node::Leaf(v) => return v,
+            }
+        }
+    }
+
+    pub(crate) fn predict_binned(&self, binned: &Binned, row: usize) -> f64 {
+        match self.nodes[self.leaf_binned(binned, row)] {
+            Node::Leaf(v) => v,
+            Node::Split { .. } => unreachable!("leaf_binned returns a leaf"),
+        }
+    }
+
+    // Index of the leaf a binned row falls in.
+    pub(crate) fn leaf_binned(&self, binned: &Binned, row: usize) -> usize {
+        let mut i = 0;
+        loop {
+            match self.nodes[i] {
+                Node::Split {
+                    feature,
+                    bin,
+                    left,
+                    right,
+                    ..
+                } => {
+                    i = if binned.bins[feature][row] > bin {
+                        right
+                    } else {
+                        left
+                    }
+                }
+                Node::Leaf(_) => return i,
+            }
+        }
+    }
+
+    pub(crate) fn scale(&mut self, factor: f64) {
+        for n in self.nodes.iter_mut() {
+            if let Node::Leaf(v) = n {
+                *v *= factor;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TreeParams {
+    pub max_depth: usize,
+    pub min_samples_leaf: usize,
+    // L2 penalty on leaf values.
+    pub l2: f64,
+    // Features tried per split (all when None or >= the feature count).
+    pub max_features: Option<usize>,
+}
+
+// Grow a tree on the squared-error gradients `grad` (prediction minus target) of `rows` (repeats
+// allowed, as in bootstrap samples). Nodes are split depth-first on the histogram split with the
+// largest gain G_L²/(n_L+λ) + G_R²/(n_R+λ) - G²/(n+λ); leaves hold the Newton step -G/(n+λ).
+// Fitting targets directly (grad = -y, λ = 0) gives leaf means, as in a random forest.
+pub(crate) fn grow_tree(
+    binned: &Binned,
+    grad: &[f64],
+    rows: &mut [u32],
+    params: &TreeParams,
+    rng: &mut StdRng,
+) -> Tree {
+    let mut tree = Tree { nodes: Vec::new() };
+    grow_node(binned, grad, rows, params, 0, rng, &mut tree.nodes);
+    tree
+}
+
+fn grow_node(
+    binned: &Binned,
+    grad: &[f64],
+    rows: &mut [u32],
+    params: &TreeParams,
+    depth: usize,
+    rng: &mut StdRng,
+    nodes: &mut Vec<Node>,
+) -> usize {
+    let idx = nodes.len();
+    let g: f64 = rows.iter().map(|&r| grad[r as usize]).sum();
+    let n = rows.len() as f64;
+    nodes.push(Node::Leaf(-g / (n + params.l2)));
+    if depth >= params.max_depth || rows.len() < 2 * params.min_samples_leaf.max(1) {
+        return idx;
+    }
+    let Some((feature, bin)) = best_split(binned, grad, rows, params, g, rng) else {
+        return idx;
+    };
+    let col = &binned.bins[feature];
+    // Partition rows in place: bin <= split bin first.
+    let mut mid = 0;
+    for i in 0..rows.len() {
+        if col[rows[i] as usize] <= bin {
+            rows.swap(i, mid);
+            mid += 1;
+        }
+    }
+    let (l, r) = rows.split_at_mut(mid);
+    let left = grow_node(binned, grad, l, params, depth + 1, rng, nodes);
+    let right = grow_node(binned, grad, r, params, depth + 1, rng, nodes);
+    nodes[idx] = Node::Split {
+        feature,
+        threshold: binned.edges[feature][bin as usize],
+        bin,
+        left,
+        right,
+    };
+    idx
+}
+
+// (feature, bin) of the split with the largest positive gain, over a random subset of features when
+// `max_features` is set.
+fn best_split(
+    binned: &Binned,
+    grad: &[f64],
+    rows: &[u32],
+    params: &TreeParams,
+    g_total: f64,
+    rng: &mut StdRng,
+) -> Option<(usize, u8)> {
+    let p = binned.n_features();
+    let features: Vec<usize> = match params.max_features {
+        Some(k) if k < p => sample(rng, p, k.max(1)).into_vec(),
+        _ => (0..p).collect(),
+    };
+    let n_total = rows.len() as f64;
+    let lambda = params.l2;
+    let min_leaf = params.min_samples_leaf.max(1) as f64;
+    let parent = g_total * g_total / (n_total + lambda);
+    features
+        .par_iter()
+        .filter_map(|&f| {
+            let edges = binned.edges[f].len();
+            if edges == 0 {
+                return None;
+            }
+            let col = &binned.bins[f];
+            let mut hist = [(0.0_f64, 0.0_f64); 256];
+            for &r in rows {
+                let h = &mut hist[col[r as usize] as usize];
+                h.0 += grad[r as usize];
+                h.1 += 1.0;
+            }
+            let (mut gl, mut nl) = (0.0, 0.0);
+            let mut best: Option<(f64, u8)> = None;
+            // Split after bin b; b = edges would send every row left.
+            for (b, h) in hist.iter().enumerate().take(edges) {
+                gl += h.0;
+                nl += h.1;
+                let nr = n_total - nl;
+                if nl < min_leaf {
+                    continue;
+                }
+                if nr < min_leaf {
+                    break;
+                }
+                let gr = g_total - gl;
+                let gain = gl * gl / (nl + lambda) + gr * gr / (nr + lambda) - parent;
+                if gain > 1e-12 && best.is_none_or(|(bg, _)| gain > bg) {
+                    best = Some((gain, b as u8));
+                }
+            }
+            best.map(|(gain, b)| (gain, f, b))
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)))
+        .map(|(_, f, b)| (f, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn params(max_depth: usize) -> TreeParams {
+        TreeParams {
+            max_depth,
+            min_samples_leaf: 1,
+            l2: 0.0,
+            max_features: None,
+        }
+    }
+
+    #[test]
+    fn bins_follow_the_edges_with_nan_in_bin_zero() {
+        let x = vec![vec![3.0, 1.0, 2.0, f64::NAN, 5.0, 2.0]];
+        let binned = Binned::new(&x, x[0].len(), 256);
+        assert_eq!(binned.edges[0], vec![1.0, 2.0, 3.0, 5.0]);
+        assert_eq!(binned.bins[0], vec![2, 0, 1, 0, 3, 1]);
+        // Bin b holds the values up to edge b, so `bin <= b` is `x <= edge` (or NaN).
+        for (&v, &bin) in x[0].iter().zip(binned.bins[0].iter()) {
+            for (b, &edge) in binned.edges[0].iter().enumerate() {
+                assert_eq!(bin as usize <= b, v.is_nan() || v <= edge);
+            }
+        }
+
+        // Edges come from the fit rows only and are capped at max_bins - 1.
+        let x = vec![(0..100).map(f64::from).collect::<Vec<f64>>()];
+        let binned = Binned::new(&x, 50, 8);
+        assert_eq!(binned.edges[0].len(), 7);
+        assert!(binned.edges[0].iter().all(|e| *e < 50.0));
+        assert_eq!(binned.bins[0][99], 7);
+    }
+
+    #[test]
+    fn fitting_targets_splits_a_step_at_the_step_with_leaf_means() {
+        let x = vec![(0..100).map(|i| f64::from(i) / 100.0).collect::<Vec<f64>>()];
+        let y: Vec<f64> = x[0]
+            .iter()
+            .map(|&v| if v < 0.5 { 0.0 } else { 10.0 })
+            .collect();
+        let grad: Vec<f64> = y.iter().map(|v| -v).collect();
+        let binned = Binned::new(&x, 100, 256);
+        let mut rows: Vec<u32> = (0..100).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+        let tree = grow_tree(&binned, &grad, &mut rows, &params(1), &mut rng);
+
+        match tree.nodes[0] {
+            Node::Split {
+                feature, threshold, ..
+            } => assert_eq!((feature, threshold), (0, 0.49)),
+            Node::Leaf(_) => panic!("the root of a step should split"),
+        }
+        assert_eq!(tree.predict_row(&[0.2]), 0.0);
+        assert_eq!(tree.predict_row(&[0.9]), 10.0);
+        assert_eq!(tree.predict_row(&[f64::NAN]), 0.0);
+        for (row, &v) in x[0].iter().enumerate() {
+            assert_eq!(tree.predict_binned(&binned, row), tree.predict_row(&[v]));
+        }
+    }
+
+    #[test]
+    fn depth_and_leaf_size_limit_growth() {
+        let x = vec![(0..40).map(f64::from).collect::<Vec<f64>>()];
+        let grad: Vec<f64> = x[0].iter().map(|v| -v * v).collect();
+        let binned = Binned::new(&x, 40, 256);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut rows: Vec<u32> = (0..40).collect();
+        let stump = grow_tree(&binned, &grad, &mut rows, &params(0), &mut rng);
+        assert_eq!(stump.nodes.len(), 1);
+
+        let mut rows: Vec<u32> = (0..40).collect();
+        let coarse = TreeParams {
+            min_samples_leaf: 15,
+            ..params(8)
+        };
+        let tree = grow_tree(&binned, &grad, &mut rows, &coarse, &mut rng);
+        let leaves = tree
+            .nodes
+            .iter()
+            .filter(|n| matches!(n, Node::Leaf(_)))
+            .count();
+        // Leaves of at least 15 of the 40 rows: at most two.
+        assert_eq!(leaves, 2);
+    }
+}